@@ -0,0 +1,449 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Rem, Sub},
+    str::FromStr,
+};
+
+/// An arbitrary precision integer stored as a sign and a little-endian
+/// magnitude of 32 bit limbs. The magnitude never has trailing zero limbs and
+/// zero is never negative, so the derived equality is numeric equality.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseBigIntError;
+
+fn trim(mag: &mut Vec<u32>) {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+}
+
+fn cmp_mag(lhs: &[u32], rhs: &[u32]) -> Ordering {
+    lhs.len()
+        .cmp(&rhs.len())
+        .then_with(|| lhs.iter().rev().cmp(rhs.iter().rev()))
+}
+
+fn add_mag(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    let (long, short) = if lhs.len() >= rhs.len() {
+        (lhs, rhs)
+    } else {
+        (rhs, lhs)
+    };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, limb) in long.iter().enumerate() {
+        let sum = *limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Computes `lhs - rhs`, which requires `lhs >= rhs` in magnitude.
+fn sub_mag(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(lhs.len());
+    let mut borrow = 0i64;
+    for (i, limb) in lhs.iter().enumerate() {
+        let mut diff = *limb as i64 - *rhs.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        result.push(diff as u32);
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_mag(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    if lhs.is_empty() || rhs.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0u32; lhs.len() + rhs.len()];
+    for (i, l) in lhs.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, r) in rhs.iter().enumerate() {
+            let cur = result[i + j] as u64 + *l as u64 * *r as u64 + carry;
+            result[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        result[i + rhs.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+/// Divides by a single limb, returning the quotient and remainder.
+fn divrem_small(lhs: &[u32], rhs: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; lhs.len()];
+    let mut rem = 0u64;
+    for (i, limb) in lhs.iter().enumerate().rev() {
+        let cur = (rem << 32) | *limb as u64;
+        quotient[i] = (cur / rhs as u64) as u32;
+        rem = cur % rhs as u64;
+    }
+    trim(&mut quotient);
+    (quotient, rem as u32)
+}
+
+/// Schoolbook binary long division, returning the quotient and remainder.
+fn divrem_mag(lhs: &[u32], rhs: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(lhs, rhs) == Ordering::Less {
+        return (Vec::new(), lhs.to_vec());
+    }
+    if let [rhs] = rhs {
+        let (quotient, rem) = divrem_small(lhs, *rhs);
+        let mut rem = vec![rem];
+        trim(&mut rem);
+        return (quotient, rem);
+    }
+    let mut quotient = vec![0u32; lhs.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for bit in (0..lhs.len() * 32).rev() {
+        // rem = rem << 1 | next bit of lhs
+        let mut carry = (lhs[bit / 32] >> (bit % 32)) & 1;
+        for limb in rem.iter_mut() {
+            let next = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+        if carry > 0 {
+            rem.push(carry);
+        }
+        if cmp_mag(&rem, rhs) != Ordering::Less {
+            rem = sub_mag(&rem, rhs);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    trim(&mut quotient);
+    (quotient, rem)
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut mag: Vec<u32>) -> Self {
+        trim(&mut mag);
+        let negative = negative && !mag.is_empty();
+        BigInt { negative, mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_parts(false, self.mag.clone())
+    }
+
+    /// The non-negative greatest common divisor of `self` and `other`.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let rem = &a % &b;
+            a = b;
+            b = rem;
+        }
+        a
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let mag = self
+            .mag
+            .iter()
+            .rev()
+            .fold(0u64, |acc, limb| (acc << 32) | *limb as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(mag)
+        } else {
+            i64::try_from(mag).ok()
+        }
+    }
+
+    /// Truncating division returning both the quotient and the remainder,
+    /// which takes the sign of the dividend like Rust's `/` and `%`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rhs` is zero.
+    pub fn div_rem(&self, rhs: &BigInt) -> (BigInt, BigInt) {
+        assert!(!rhs.is_zero(), "attempt to divide by zero");
+        let (quotient, rem) = divrem_mag(&self.mag, &rhs.mag);
+        (
+            BigInt::from_parts(self.negative != rhs.negative, quotient),
+            BigInt::from_parts(self.negative, rem),
+        )
+    }
+
+    /// The largest integer whose square is at most `self`, found with
+    /// Newton's method so it stays exact for any size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `self` is negative.
+    pub fn sqrt(&self) -> BigInt {
+        assert!(
+            !self.is_negative(),
+            "attempt to take the square root of a negative number"
+        );
+        let two = BigInt::from(2);
+        if *self < two {
+            return self.clone();
+        }
+        let mut root = self.clone();
+        loop {
+            let next = &(&root + &(self / &root)) / &two;
+            if next >= root {
+                return root;
+            }
+            root = next;
+        }
+    }
+
+    /// Flooring division returning both the quotient and the remainder,
+    /// which takes the sign of the divisor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rhs` is zero.
+    pub fn div_rem_floor(&self, rhs: &BigInt) -> (BigInt, BigInt) {
+        let (quotient, rem) = self.div_rem(rhs);
+        if !rem.is_zero() && rem.is_negative() != rhs.is_negative() {
+            (&quotient - &BigInt::from(1), &rem + rhs)
+        } else {
+            (quotient, rem)
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let mag = n.unsigned_abs();
+        BigInt::from_parts(n < 0, vec![mag as u32, (mag >> 32) as u32])
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+        let mut mag = Vec::new();
+        for chunk in digits.as_bytes().chunks(9) {
+            let scale = 10u32.pow(chunk.len() as u32);
+            let value = chunk
+                .iter()
+                .fold(0u32, |acc, digit| acc * 10 + (digit - b'0') as u32);
+            mag = add_mag(&mul_mag(&mag, &[scale]), &[value]);
+        }
+        Ok(BigInt::from_parts(negative, mag))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (quotient, rem) = divrem_small(&mag, 1_000_000_000);
+            chunks.push(rem);
+            mag = quotient;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(head) = chunks.next() {
+            write!(f, "{}", head)?;
+        }
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.mag.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, rhs: &BigInt) -> BigInt {
+        if self.negative == rhs.negative {
+            return BigInt::from_parts(self.negative, add_mag(&self.mag, &rhs.mag));
+        }
+        match cmp_mag(&self.mag, &rhs.mag) {
+            Ordering::Less => BigInt::from_parts(rhs.negative, sub_mag(&rhs.mag, &self.mag)),
+            _ => BigInt::from_parts(self.negative, sub_mag(&self.mag, &rhs.mag)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, rhs: &BigInt) -> BigInt {
+        self + &-rhs
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, rhs: &BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != rhs.negative, mul_mag(&self.mag, &rhs.mag))
+    }
+}
+
+impl Div for &BigInt {
+    type Output = BigInt;
+
+    fn div(self, rhs: &BigInt) -> BigInt {
+        self.div_rem(rhs).0
+    }
+}
+
+impl Rem for &BigInt {
+    type Output = BigInt;
+
+    fn rem(self, rhs: &BigInt) -> BigInt {
+        self.div_rem(rhs).1
+    }
+}
+
+macro_rules! forward_owned_binop {
+    ($($trait:ident $method:ident),*) => {
+        $(
+            impl $trait for BigInt {
+                type Output = BigInt;
+
+                fn $method(self, rhs: BigInt) -> BigInt {
+                    (&self).$method(&rhs)
+                }
+            }
+        )*
+    };
+}
+
+forward_owned_binop!(Add add, Sub sub, Mul mul, Div div, Rem rem);
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        -&self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigInt;
+
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_display() {
+        let cases = vec![
+            "0",
+            "42",
+            "-42",
+            "4294967296",
+            "-9223372036854775808",
+            "265252859812191058636308480000000",
+            "1000000000000000000000000000001",
+        ];
+        for input in cases {
+            assert_eq!(input, big(input).to_string());
+        }
+        assert_eq!("0", big("-0").to_string());
+        assert_eq!(Err(super::ParseBigIntError), "12a".parse::<BigInt>());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432");
+        assert_eq!(big("123456789011358024580135802458"), &a + &b);
+        assert_eq!(big("123456789013333333222333333322"), &a - &b);
+        assert_eq!(
+            big("-121932631137021795212620027521140070120989178480"),
+            &a * &b
+        );
+        assert_eq!(big("-124999998860"), &a / &b);
+        assert_eq!(big("925925953827160370"), &a % &b);
+        assert_eq!(a, &(&(&a / &b) * &b) + &(&a % &b));
+        assert_eq!(BigInt::from(-7) / BigInt::from(2), BigInt::from(-3));
+        assert_eq!(BigInt::from(-7) % BigInt::from(2), BigInt::from(-1));
+        let floor = |lhs: i64, rhs: i64| BigInt::from(lhs).div_rem_floor(&BigInt::from(rhs));
+        assert_eq!((BigInt::from(-4), BigInt::from(1)), floor(-7, 2));
+        assert_eq!((BigInt::from(-4), BigInt::from(-1)), floor(7, -2));
+        assert_eq!((BigInt::from(3), BigInt::from(-1)), floor(-7, -2));
+        assert_eq!((BigInt::from(-3), BigInt::from(0)), floor(-6, 2));
+        assert_eq!(BigInt::from(4), BigInt::from(17).sqrt());
+        assert_eq!(BigInt::from(0), BigInt::from(0).sqrt());
+        assert_eq!(
+            big("11111111100000"),
+            big("123456789876543210000000000").sqrt()
+        );
+    }
+
+    #[test]
+    fn conversion() {
+        assert_eq!(Some(i64::MIN), BigInt::from(i64::MIN).to_i64());
+        assert_eq!(Some(i64::MAX), BigInt::from(i64::MAX).to_i64());
+        assert_eq!(None, (BigInt::from(i64::MAX) + BigInt::from(1)).to_i64());
+        assert!(BigInt::from(-1) < BigInt::from(0));
+        assert_eq!(BigInt::from(6), BigInt::from(-12).gcd(&BigInt::from(18)));
+        assert!(big("-100000000000000000000") < BigInt::from(-1));
+    }
+}
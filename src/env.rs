@@ -1,25 +1,83 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
 use crate::{
     error::Error,
+    symbol::Symbol,
+    syntax::SyntaxRules,
     value::{IOFunc, PrimitiveFunc, Value},
 };
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// How deep calls may nest by default. Each level takes tens of kilobytes of
+/// stack in debug builds, so this fits the stack `main` runs on.
+const DEPTH_LIMIT: usize = 1000;
+
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct Closure {
-    vars: HashMap<String, usize>,
+    vars: Rc<HashMap<String, usize>>,
+}
+
+/// Text waiting to be read from an input port, consumed from the front.
+#[derive(Debug)]
+pub struct InputPort {
+    contents: String,
+    pos: usize,
+}
+
+impl InputPort {
+    fn new(contents: String) -> Self {
+        InputPort { contents, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> &str {
+        &self.contents[self.pos..]
+    }
+
+    pub fn consume(&mut self, len: usize) {
+        self.pos += len;
+    }
+}
+
+/// Bytes waiting to be read from a binary input port, consumed from the front.
+#[derive(Debug)]
+pub struct BinaryInputPort {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl BinaryInputPort {
+    /// Consumes and returns up to `len` bytes, fewer if the port runs out.
+    pub fn read(&mut self, len: usize) -> &[u8] {
+        let start = self.pos;
+        self.pos = self.bytes.len().min(start + len);
+        &self.bytes[start..self.pos]
+    }
 }
 
 #[derive(Debug)]
 enum Port {
-    ReadPort(BufReader<File>),
-    WritePort(BufWriter<File>),
+    Read(InputPort),
+    Write(BufWriter<File>),
+    BinaryRead(BinaryInputPort),
+    BinaryWrite(BufWriter<File>),
+}
+
+/// The expansion of one macro use, kept so that evaluating the use again,
+/// as a loop does, doesn't introduce a fresh set of aliases each time.
+#[derive(Debug)]
+struct Expansion {
+    /// The use itself, held so that no other form can take its address.
+    form: Rc<[Value]>,
+    /// The macro it was expanded with; once that's redefined, it's stale.
+    rules: Rc<SyntaxRules>,
+    expanded: Value,
 }
 
 // TODO: Will grow forever, thought about saving vals.len() and then use vec.truncate
@@ -30,12 +88,45 @@ pub struct Env {
     vars: HashMap<String, usize>,
     next_port_id: usize,
     ports: HashMap<usize, Port>,
+    current_input: Option<usize>,
+    current_output: Option<usize>,
+    command_line: Vec<String>,
+    /// The directories of the files being loaded, innermost last.
+    load_dirs: Vec<PathBuf>,
+    /// Directories `load` searches for relative paths, in order.
+    load_path: Vec<PathBuf>,
+    /// The libraries defined so far by name, each with its exported names
+    /// and the slots holding them.
+    libraries: HashMap<String, Vec<(String, usize)>>,
+    /// The handlers installed by `with-exception-handler`, innermost last.
+    handlers: Vec<Value>,
+    /// The macros defined with `define-syntax`, by name.
+    macros: HashMap<String, Rc<SyntaxRules>>,
+    /// The fresh names macro expansions gave the identifiers they introduced,
+    /// with the names they were written as.
+    aliases: HashMap<String, Symbol>,
+    /// The expansions of the macro uses evaluated so far, by the address of
+    /// the use.
+    expansions: HashMap<usize, Expansion>,
+    /// How many characters of a result the REPL prints, if it's limited.
+    print_limit: Option<usize>,
+    /// The slots bound with `define-constant`, which `set!` refuses to change.
+    constants: HashSet<usize>,
+    /// The slots `primitive_bindings` put the primitives in.
+    primitives: HashSet<usize>,
+    /// Whether `set!` and `define` refuse to rebind the primitives.
+    safe_primitives: bool,
+    /// How many procedure calls are in progress.
+    depth: usize,
+    /// How deep calls may nest before failing, if it's limited.
+    depth_limit: Option<usize>,
 }
 
 impl Env {
     pub fn get_var(&self, var: &str) -> Result<&Value> {
         match self.vars.get(var) {
             Some(i) => Ok(&self.vals[*i]),
+            None if self.aliases.contains_key(var) => self.get_var(self.aliases[var].as_str()),
             None => Err(Error::UnboundVar(
                 "Getting an unbound variable".to_owned(),
                 var.to_owned(),
@@ -45,10 +136,16 @@ impl Env {
 
     pub fn set_var(&mut self, var: &str, val: Value) -> Result<Value> {
         match self.vars.get(var) {
+            Some(i) if self.constants.contains(i) => Err(Error::Constant(var.to_owned())),
+            Some(i) if self.is_protected(*i) => Err(Error::Primitive(var.to_owned())),
             Some(i) => {
                 self.vals[*i] = val.clone();
                 Ok(val)
             }
+            None if self.aliases.contains_key(var) => {
+                let original = self.aliases[var];
+                self.set_var(original.as_str(), val)
+            }
             None => Err(Error::UnboundVar(
                 "Setting an unbound var".to_owned(),
                 var.to_owned(),
@@ -63,37 +160,181 @@ impl Env {
         val
     }
 
+    /// Like `define_var`, for the definitions a program makes: a constant
+    /// can't be redefined, and with safe primitives neither can a name still
+    /// bound to its primitive.
+    pub fn define_checked(&mut self, var: String, val: Value) -> Result<Value> {
+        self.check_redefinable(&var)?;
+        Ok(self.define_var(var, val))
+    }
+
+    /// Fails if `var` may not be given a new definition, of a value or of a
+    /// macro.
+    fn check_redefinable(&self, var: &str) -> Result<()> {
+        match self.vars.get(var) {
+            Some(i) if self.constants.contains(i) => Err(Error::Constant(var.to_owned())),
+            Some(i) if self.is_protected(*i) => Err(Error::Primitive(var.to_owned())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `define_checked`, but the binding can't be changed with `set_var`.
+    pub fn define_constant(&mut self, var: String, val: Value) -> Result<Value> {
+        let val = self.define_checked(var, val)?;
+        self.constants.insert(self.vals.len() - 1);
+        Ok(val)
+    }
+
+    /// Whether `var` is bound with `define_constant`.
+    pub fn is_constant(&self, var: &str) -> bool {
+        self.vars
+            .get(var)
+            .is_some_and(|i| self.constants.contains(i))
+    }
+
+    fn is_protected(&self, slot: usize) -> bool {
+        self.safe_primitives && self.primitives.contains(&slot)
+    }
+
+    pub fn define_macro(&mut self, name: String, rules: SyntaxRules) -> Result<()> {
+        self.check_redefinable(&name)?;
+        self.macros.insert(name, Rc::new(rules));
+        Ok(())
+    }
+
+    pub fn is_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<Rc<SyntaxRules>> {
+        self.macros.get(name).cloned()
+    }
+
+    /// Records that `alias` was introduced in place of `original`, so that
+    /// wherever the alias isn't bound it refers to the original name.
+    pub fn define_alias(&mut self, alias: Symbol, original: Symbol) {
+        self.aliases.insert(alias.to_string(), original);
+    }
+
+    /// How many aliases macro expansions have introduced.
+    #[cfg(test)]
+    pub fn alias_count(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// The expansion `rules` last gave the macro use `form`, if any.
+    pub fn expansion(&self, form: &Rc<[Value]>, rules: &Rc<SyntaxRules>) -> Option<Value> {
+        self.expansions
+            .get(&(Rc::as_ptr(form).cast::<Value>() as usize))
+            .filter(|expansion| {
+                Rc::ptr_eq(&expansion.form, form) && Rc::ptr_eq(&expansion.rules, rules)
+            })
+            .map(|expansion| expansion.expanded.clone())
+    }
+
+    pub fn cache_expansion(&mut self, form: Rc<[Value]>, rules: Rc<SyntaxRules>, expanded: Value) {
+        let address = Rc::as_ptr(&form).cast::<Value>() as usize;
+        let expansion = Expansion {
+            form,
+            rules,
+            expanded,
+        };
+        self.expansions.insert(address, expansion);
+    }
+
+    /// Registers a library exporting the current bindings of `exports`.
+    pub fn define_library(&mut self, name: String, exports: &[Symbol]) -> Result<()> {
+        let exports = exports
+            .iter()
+            .map(|export| match self.vars.get(export.as_str()) {
+                Some(i) => Ok((export.to_string(), *i)),
+                None => Err(Error::UnboundVar(
+                    "Exporting an unbound variable".to_owned(),
+                    export.to_string(),
+                )),
+            })
+            .collect::<Result<_>>()?;
+        self.libraries.insert(name, exports);
+        Ok(())
+    }
+
+    /// Binds the exports of a library in the current scope. They share their
+    /// slots with the library, so a `set!` on either side is seen by both.
+    pub fn import_library(&mut self, name: &str) -> Result<()> {
+        let exports = self.libraries.get(name).ok_or_else(|| {
+            Error::UnboundVar("Importing an unknown library".to_owned(), name.to_owned())
+        })?;
+        for (var, i) in exports {
+            self.vars.insert(var.clone(), *i);
+        }
+        Ok(())
+    }
+
+    /// The names currently bound, sorted for display.
+    pub fn bound_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.vars.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The current bindings in the order their slots were defined.
+    pub fn bindings(&self) -> Vec<(&str, &Value)> {
+        let mut bindings: Vec<(&str, usize)> = self
+            .vars
+            .iter()
+            .map(|(var, i)| (var.as_str(), *i))
+            .collect();
+        bindings.sort_unstable_by_key(|(_, i)| *i);
+        bindings
+            .into_iter()
+            .map(|(var, i)| (var, &self.vals[i]))
+            .collect()
+    }
+
     pub fn make_closure(&mut self) -> Closure {
-        let vars = self.vars.clone();
+        let vars = Rc::new(self.vars.clone());
         Closure { vars }
     }
 
     pub fn with_closure(&mut self, closure: &Closure) {
-        for (var, val) in &closure.vars {
+        for (var, val) in closure.vars.iter() {
             self.vars.insert(var.to_owned(), *val);
         }
     }
 
     pub fn load_closure(&mut self, closure: Closure) {
-        self.vars = closure.vars;
+        self.vars = Rc::try_unwrap(closure.vars).unwrap_or_else(|vars| (*vars).clone());
     }
 
-    pub fn make_read_port(&mut self, path: &str) -> Result<Value> {
-        let file = File::open(path).map_err(Error::IO)?;
-        let reader = BufReader::new(file);
+    fn add_port(&mut self, port: Port) -> Value {
         let port_id = self.next_port_id;
         self.next_port_id += 1;
-        self.ports.insert(port_id, Port::ReadPort(reader));
-        Ok(Value::Port(port_id))
+        self.ports.insert(port_id, port);
+        Value::Port(port_id)
+    }
+
+    pub fn make_read_port(&mut self, path: &str) -> Result<Value> {
+        let contents = std::fs::read_to_string(path).map_err(Error::IO)?;
+        Ok(self.make_string_read_port(contents))
+    }
+
+    pub fn make_string_read_port(&mut self, contents: String) -> Value {
+        self.add_port(Port::Read(InputPort::new(contents)))
     }
 
     pub fn make_write_port(&mut self, path: &str) -> Result<Value> {
-        let file = File::open(path).map_err(Error::IO)?;
-        let writer = BufWriter::new(file);
-        let port_id = self.next_port_id;
-        self.next_port_id += 1;
-        self.ports.insert(port_id, Port::WritePort(writer));
-        Ok(Value::Port(port_id))
+        let file = File::create(path).map_err(Error::IO)?;
+        Ok(self.add_port(Port::Write(BufWriter::new(file))))
+    }
+
+    pub fn make_binary_read_port(&mut self, path: &str) -> Result<Value> {
+        let bytes = std::fs::read(path).map_err(Error::IO)?;
+        Ok(self.add_port(Port::BinaryRead(BinaryInputPort { bytes, pos: 0 })))
+    }
+
+    pub fn make_binary_write_port(&mut self, path: &str) -> Result<Value> {
+        let file = File::create(path).map_err(Error::IO)?;
+        Ok(self.add_port(Port::BinaryWrite(BufWriter::new(file))))
     }
 
     pub fn close_port(&mut self, port_id: &usize) -> Result<Value> {
@@ -101,8 +342,156 @@ impl Env {
         Ok(Value::Bool(true))
     }
 
-    pub fn get_read_port(&mut self, port_id: &usize) -> Result<&mut BufReader<File>> {
-        if let Some(Port::ReadPort(reader)) = self.ports.get_mut(port_id) {
+    /// The program arguments returned by `command-line`.
+    pub fn command_line(&self) -> &[String] {
+        &self.command_line
+    }
+
+    pub fn set_command_line(&mut self, args: Vec<String>) {
+        self.command_line = args;
+    }
+
+    /// Finds the file a path given to `load` refers to. Relative paths are
+    /// taken first from the directory of the file being loaded, so a file can
+    /// load its neighbours from any working directory, and then from each
+    /// directory of the load path.
+    pub fn find_load_path(&self, path: &str) -> Result<PathBuf> {
+        let local = match self.load_dirs.last() {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        let mut tried = vec![local];
+        if Path::new(path).is_relative() {
+            tried.extend(self.load_path.iter().map(|dir| dir.join(path)));
+        }
+        if let Some(found) = tried.iter().find(|path| path.is_file()) {
+            return Ok(found.clone());
+        }
+        let tried: Vec<String> = tried
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        Err(Error::IO(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} not found, tried {}", path, tried.join(", ")),
+        )))
+    }
+
+    /// Adds a directory to the end of the load path.
+    pub fn add_to_load_path(&mut self, dir: PathBuf) {
+        self.load_path.push(dir);
+    }
+
+    /// Marks `path` as the file being loaded until `end_load`.
+    pub fn begin_load(&mut self, path: &Path) {
+        let dir = path.parent().unwrap_or(Path::new("")).to_owned();
+        self.load_dirs.push(dir);
+    }
+
+    pub fn end_load(&mut self) {
+        self.load_dirs.pop();
+    }
+
+    /// Installs a handler for `raise` until the matching `pop_handler`.
+    pub fn push_handler(&mut self, handler: Value) {
+        self.handlers.push(handler);
+    }
+
+    pub fn pop_handler(&mut self) -> Option<Value> {
+        self.handlers.pop()
+    }
+
+    /// Swaps out the installed handlers, returning the previous ones.
+    pub fn replace_handlers(&mut self, handlers: Vec<Value>) -> Vec<Value> {
+        std::mem::replace(&mut self.handlers, handlers)
+    }
+
+    pub fn print_limit(&self) -> Option<usize> {
+        self.print_limit
+    }
+
+    pub fn set_print_limit(&mut self, limit: Option<usize>) {
+        self.print_limit = limit;
+    }
+
+    pub fn set_depth_limit(&mut self, limit: Option<usize>) {
+        self.depth_limit = limit;
+    }
+
+    /// Records that a call started, failing rather than letting runaway
+    /// recursion overflow the Rust stack. Every successful call must be
+    /// matched by `leave_call`.
+    pub fn enter_call(&mut self) -> Result<()> {
+        match self.depth_limit {
+            Some(limit) if self.depth >= limit => Err(Error::StackOverflow(limit)),
+            _ => {
+                self.depth += 1;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn leave_call(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Makes `set!` and `define` fail on names still bound to primitives,
+    /// rather than silently shadowing them.
+    pub fn set_safe_primitives(&mut self, safe: bool) {
+        self.safe_primitives = safe;
+    }
+
+    /// The port `read` uses when none is given, or `None` for stdin.
+    pub fn current_input_port(&self) -> Option<usize> {
+        self.current_input
+    }
+
+    /// The port `write` and `display` use when none is given, or `None` for
+    /// stdout.
+    pub fn current_output_port(&self) -> Option<usize> {
+        self.current_output
+    }
+
+    /// Redirects the current input port, returning the previous one.
+    pub fn replace_current_input_port(&mut self, port_id: Option<usize>) -> Option<usize> {
+        std::mem::replace(&mut self.current_input, port_id)
+    }
+
+    /// Redirects the current output port, returning the previous one.
+    pub fn replace_current_output_port(&mut self, port_id: Option<usize>) -> Option<usize> {
+        std::mem::replace(&mut self.current_output, port_id)
+    }
+
+    /// Whether the port is open and can be read from.
+    pub fn is_input_port(&self, port_id: &usize) -> bool {
+        matches!(
+            self.ports.get(port_id),
+            Some(Port::Read(_) | Port::BinaryRead(_))
+        )
+    }
+
+    /// What kind of port this is, and whether it's still open. Closing a
+    /// port forgets it, so a closed port can't say which way it went.
+    pub fn port_description(&self, port_id: &usize) -> &'static str {
+        match self.ports.get(port_id) {
+            Some(Port::Read(_)) => "input port, open",
+            Some(Port::Write(_)) => "output port, open",
+            Some(Port::BinaryRead(_)) => "binary input port, open",
+            Some(Port::BinaryWrite(_)) => "binary output port, open",
+            None => "port, closed",
+        }
+    }
+
+    /// Whether the port is open and can be written to.
+    pub fn is_output_port(&self, port_id: &usize) -> bool {
+        matches!(
+            self.ports.get(port_id),
+            Some(Port::Write(_) | Port::BinaryWrite(_))
+        )
+    }
+
+    pub fn get_read_port(&mut self, port_id: &usize) -> Result<&mut InputPort> {
+        if let Some(Port::Read(reader)) = self.ports.get_mut(port_id) {
             return Ok(reader);
         }
         Err(Error::Port(
@@ -111,7 +500,7 @@ impl Env {
     }
 
     pub fn get_write_port(&mut self, port_id: &usize) -> Result<&mut BufWriter<File>> {
-        if let Some(Port::WritePort(writer)) = self.ports.get_mut(port_id) {
+        if let Some(Port::Write(writer)) = self.ports.get_mut(port_id) {
             return Ok(writer);
         }
         Err(Error::Port(
@@ -119,12 +508,35 @@ impl Env {
         ))
     }
 
+    pub fn get_binary_read_port(&mut self, port_id: &usize) -> Result<&mut BinaryInputPort> {
+        if let Some(Port::BinaryRead(reader)) = self.ports.get_mut(port_id) {
+            return Ok(reader);
+        }
+        Err(Error::Port(
+            "Port was not opened, was closed or is not a binary read port".to_owned(),
+        ))
+    }
+
+    pub fn get_binary_write_port(&mut self, port_id: &usize) -> Result<&mut BufWriter<File>> {
+        if let Some(Port::BinaryWrite(writer)) = self.ports.get_mut(port_id) {
+            return Ok(writer);
+        }
+        Err(Error::Port(
+            "Port was not opened, was closed or is not a binary write port".to_owned(),
+        ))
+    }
+
     pub fn primitive_bindings() -> Self {
-        let mut env = Env::default();
+        let mut env = Env {
+            depth_limit: Some(DEPTH_LIMIT),
+            ..Env::default()
+        };
         fn define_primitive_func(env: &mut Env, name: &str, func: PrimitiveFunc) {
+            env.primitives.insert(env.vals.len());
             env.define_var(name.to_owned(), Value::PrimitiveFunc(func));
         }
         fn define_io_func(env: &mut Env, name: &str, func: IOFunc) {
+            env.primitives.insert(env.vals.len());
             env.define_var(name.to_owned(), Value::IOFunc(func));
         }
         define_primitive_func(&mut env, "+", PrimitiveFunc::Add);
@@ -132,8 +544,37 @@ impl Env {
         define_primitive_func(&mut env, "*", PrimitiveFunc::Mul);
         define_primitive_func(&mut env, "/", PrimitiveFunc::Div);
         define_primitive_func(&mut env, "mod", PrimitiveFunc::Rem);
-        define_primitive_func(&mut env, "quotient", PrimitiveFunc::Div);
+        define_primitive_func(&mut env, "modulo", PrimitiveFunc::FloorRemainder);
+        define_primitive_func(&mut env, "quotient", PrimitiveFunc::Quotient);
         define_primitive_func(&mut env, "remainder", PrimitiveFunc::Rem);
+        define_primitive_func(&mut env, "floor/", PrimitiveFunc::FloorDiv);
+        define_primitive_func(&mut env, "floor-quotient", PrimitiveFunc::FloorQuotient);
+        define_primitive_func(&mut env, "floor-remainder", PrimitiveFunc::FloorRemainder);
+        define_primitive_func(&mut env, "truncate/", PrimitiveFunc::TruncateDiv);
+        define_primitive_func(&mut env, "truncate-quotient", PrimitiveFunc::Quotient);
+        define_primitive_func(&mut env, "truncate-remainder", PrimitiveFunc::Rem);
+        define_primitive_func(
+            &mut env,
+            "exact-integer-sqrt",
+            PrimitiveFunc::ExactIntegerSqrt,
+        );
+        define_primitive_func(&mut env, "1+", PrimitiveFunc::Increment);
+        define_primitive_func(&mut env, "1-", PrimitiveFunc::Decrement);
+        define_primitive_func(&mut env, "boolean", PrimitiveFunc::Boolean);
+        define_primitive_func(&mut env, "integer?", PrimitiveFunc::IsInteger);
+        define_primitive_func(&mut env, "rational?", PrimitiveFunc::IsRational);
+        define_primitive_func(&mut env, "real?", PrimitiveFunc::IsReal);
+        define_primitive_func(&mut env, "exact?", PrimitiveFunc::IsExact);
+        define_primitive_func(&mut env, "inexact?", PrimitiveFunc::IsInexact);
+        define_primitive_func(&mut env, "exact-integer?", PrimitiveFunc::IsExactInteger);
+        define_primitive_func(
+            &mut env,
+            "exact-nonnegative-integer?",
+            PrimitiveFunc::IsExactNonnegativeInteger,
+        );
+        define_primitive_func(&mut env, "numerator", PrimitiveFunc::Numerator);
+        define_primitive_func(&mut env, "denominator", PrimitiveFunc::Denominator);
+        define_primitive_func(&mut env, "rationalize", PrimitiveFunc::Rationalize);
         define_primitive_func(&mut env, "=", PrimitiveFunc::Eq);
         define_primitive_func(&mut env, "<", PrimitiveFunc::Lt);
         define_primitive_func(&mut env, ">", PrimitiveFunc::Gt);
@@ -142,26 +583,180 @@ impl Env {
         define_primitive_func(&mut env, "<=", PrimitiveFunc::Le);
         define_primitive_func(&mut env, "&&", PrimitiveFunc::And);
         define_primitive_func(&mut env, "||", PrimitiveFunc::Or);
+        define_primitive_func(&mut env, "not", PrimitiveFunc::Not);
+        define_primitive_func(&mut env, "error-kind", PrimitiveFunc::ErrorKind);
+        define_primitive_func(&mut env, "error-message", PrimitiveFunc::ErrorMessage);
         define_primitive_func(&mut env, "string=?", PrimitiveFunc::StringEq);
         define_primitive_func(&mut env, "string<?", PrimitiveFunc::StringLt);
         define_primitive_func(&mut env, "string>?", PrimitiveFunc::StringGt);
         define_primitive_func(&mut env, "string<=?", PrimitiveFunc::StringLe);
         define_primitive_func(&mut env, "string>=?", PrimitiveFunc::StringGe);
+        define_primitive_func(&mut env, "string-ci=?", PrimitiveFunc::StringCiEq);
+        define_primitive_func(&mut env, "string-ci<?", PrimitiveFunc::StringCiLt);
+        define_primitive_func(&mut env, "string-ci>?", PrimitiveFunc::StringCiGt);
+        define_primitive_func(&mut env, "string-ci<=?", PrimitiveFunc::StringCiLe);
+        define_primitive_func(&mut env, "string-ci>=?", PrimitiveFunc::StringCiGe);
         define_primitive_func(&mut env, "car", PrimitiveFunc::Car);
         define_primitive_func(&mut env, "cdr", PrimitiveFunc::Cdr);
         define_primitive_func(&mut env, "cons", PrimitiveFunc::Cons);
         define_primitive_func(&mut env, "eq?", PrimitiveFunc::Eqv);
         define_primitive_func(&mut env, "eqv?", PrimitiveFunc::Eqv);
         define_primitive_func(&mut env, "equal?", PrimitiveFunc::Equal);
+        define_primitive_func(&mut env, "values", PrimitiveFunc::Values);
+        define_primitive_func(&mut env, "stream-car", PrimitiveFunc::Car);
+        define_primitive_func(&mut env, "char->integer", PrimitiveFunc::CharToInteger);
+        define_primitive_func(&mut env, "integer->char", PrimitiveFunc::IntegerToChar);
+        define_primitive_func(&mut env, "char-upcase", PrimitiveFunc::CharUpcase);
+        define_primitive_func(&mut env, "char-downcase", PrimitiveFunc::CharDowncase);
+        define_primitive_func(&mut env, "char-foldcase", PrimitiveFunc::CharFoldcase);
+        define_primitive_func(&mut env, "string-foldcase", PrimitiveFunc::StringFoldcase);
+        define_primitive_func(&mut env, "string-length", PrimitiveFunc::StringLength);
+        define_primitive_func(&mut env, "string-ref", PrimitiveFunc::StringRef);
+        define_primitive_func(&mut env, "substring", PrimitiveFunc::Substring);
+        define_primitive_func(&mut env, "bytevector", PrimitiveFunc::Bytevector);
+        define_primitive_func(&mut env, "string->vector", PrimitiveFunc::StringToVector);
+        define_primitive_func(&mut env, "vector->string", PrimitiveFunc::VectorToString);
+        define_primitive_func(&mut env, "string", PrimitiveFunc::String);
+        define_primitive_func(&mut env, "list->string", PrimitiveFunc::ListToString);
+        define_primitive_func(&mut env, "string-trim", PrimitiveFunc::StringTrim);
+        define_primitive_func(&mut env, "string-trim-left", PrimitiveFunc::StringTrimLeft);
+        define_primitive_func(
+            &mut env,
+            "string-trim-right",
+            PrimitiveFunc::StringTrimRight,
+        );
+        define_primitive_func(&mut env, "vector-append", PrimitiveFunc::VectorAppend);
+        define_primitive_func(&mut env, "gensym", PrimitiveFunc::Gensym);
+        define_primitive_func(&mut env, "vector-copy", PrimitiveFunc::VectorCopy);
+        define_primitive_func(&mut env, "subvector", PrimitiveFunc::VectorCopy);
+        define_primitive_func(&mut env, "make-bytevector", PrimitiveFunc::MakeBytevector);
+        define_primitive_func(
+            &mut env,
+            "bytevector-u8-ref",
+            PrimitiveFunc::BytevectorU8Ref,
+        );
+        define_primitive_func(
+            &mut env,
+            "bytevector-u8-set!",
+            PrimitiveFunc::BytevectorU8Set,
+        );
+        define_primitive_func(
+            &mut env,
+            "bytevector-length",
+            PrimitiveFunc::BytevectorLength,
+        );
+        define_primitive_func(&mut env, "make-hash-table", PrimitiveFunc::MakeHashTable);
+        define_primitive_func(&mut env, "hash-table?", PrimitiveFunc::IsHashTable);
+        define_primitive_func(&mut env, "hash-set!", PrimitiveFunc::HashSet);
+        define_primitive_func(&mut env, "hash-ref", PrimitiveFunc::HashRef);
+        define_primitive_func(&mut env, "nested-ref", PrimitiveFunc::NestedRef);
+        define_primitive_func(&mut env, "hash-count", PrimitiveFunc::HashCount);
+        define_primitive_func(&mut env, "equal-hash", PrimitiveFunc::EqualHash);
+        define_primitive_func(&mut env, "eqv-hash", PrimitiveFunc::EqualHash);
+        define_primitive_func(
+            &mut env,
+            "alist->hash-table",
+            PrimitiveFunc::AlistToHashTable,
+        );
+        define_primitive_func(
+            &mut env,
+            "hash-table->alist",
+            PrimitiveFunc::HashTableToAlist,
+        );
+        define_primitive_func(
+            &mut env,
+            "get-environment-variable",
+            PrimitiveFunc::GetEnvironmentVariable,
+        );
+        define_primitive_func(&mut env, "identity", PrimitiveFunc::Identity);
+        define_primitive_func(&mut env, "compose", PrimitiveFunc::Compose);
+        define_primitive_func(&mut env, "curry", PrimitiveFunc::Curry);
+        define_primitive_func(&mut env, "append", PrimitiveFunc::Append);
+        define_primitive_func(&mut env, "flatten", PrimitiveFunc::Flatten);
+        define_primitive_func(&mut env, "reverse", PrimitiveFunc::Reverse);
+        define_primitive_func(&mut env, "eof-object", PrimitiveFunc::EofObject);
+        define_primitive_func(&mut env, "eof-object?", PrimitiveFunc::IsEofObject);
+        define_primitive_func(&mut env, "port?", PrimitiveFunc::IsPort);
         define_io_func(&mut env, "apply", IOFunc::Apply);
         define_io_func(&mut env, "open-input-file", IOFunc::MakeReadPort);
         define_io_func(&mut env, "open-output-file", IOFunc::MakeWritePort);
+        define_io_func(&mut env, "open-input-string", IOFunc::MakeStringReadPort);
+        define_io_func(
+            &mut env,
+            "open-binary-input-file",
+            IOFunc::MakeBinaryReadPort,
+        );
+        define_io_func(
+            &mut env,
+            "open-binary-output-file",
+            IOFunc::MakeBinaryWritePort,
+        );
         define_io_func(&mut env, "close-input-port", IOFunc::ClosePort);
+        define_io_func(&mut env, "call-with-input-file", IOFunc::CallWithInputFile);
+        define_io_func(
+            &mut env,
+            "call-with-output-file",
+            IOFunc::CallWithOutputFile,
+        );
+        define_io_func(&mut env, "with-input-from-file", IOFunc::WithInputFromFile);
+        define_io_func(&mut env, "with-output-to-file", IOFunc::WithOutputToFile);
         define_io_func(&mut env, "close-output-port", IOFunc::ClosePort);
         define_io_func(&mut env, "read", IOFunc::Read);
+        define_io_func(&mut env, "read-line", IOFunc::ReadLine);
+        define_io_func(&mut env, "read-char", IOFunc::ReadChar);
+        define_io_func(&mut env, "peek-char", IOFunc::PeekChar);
+        define_io_func(&mut env, "char-ready?", IOFunc::CharReady);
+        define_io_func(&mut env, "input-port?", IOFunc::IsInputPort);
+        define_io_func(&mut env, "output-port?", IOFunc::IsOutputPort);
+        define_io_func(
+            &mut env,
+            "with-exception-handler",
+            IOFunc::WithExceptionHandler,
+        );
+        define_io_func(&mut env, "raise", IOFunc::Raise);
+        define_io_func(&mut env, "raise-continuable", IOFunc::RaiseContinuable);
+        define_io_func(&mut env, "write-char", IOFunc::WriteChar);
         define_io_func(&mut env, "write", IOFunc::Write);
+        define_io_func(&mut env, "display", IOFunc::Display);
+        define_io_func(&mut env, "describe", IOFunc::Describe);
+        // `write`, like `write-simple`, writes a list or vector in full
+        // wherever it appears; `write-shared` labels the ones that appear more
+        // than once.
+        define_io_func(&mut env, "write-simple", IOFunc::Write);
+        define_io_func(&mut env, "write-shared", IOFunc::WriteShared);
+        define_io_func(&mut env, "read-u8", IOFunc::ReadU8);
+        define_io_func(&mut env, "write-u8", IOFunc::WriteU8);
+        define_io_func(&mut env, "read-bytevector", IOFunc::ReadBytevector);
+        define_io_func(&mut env, "write-bytevector", IOFunc::WriteBytevector);
+        define_io_func(&mut env, "hash-update!", IOFunc::HashUpdate);
+        define_io_func(&mut env, "sort", IOFunc::Sort);
+        define_io_func(&mut env, "list-sort", IOFunc::ListSort);
+        define_io_func(&mut env, "map", IOFunc::Map);
+        define_io_func(&mut env, "for-each", IOFunc::ForEach);
+        define_io_func(&mut env, "filter", IOFunc::Filter);
+        define_io_func(&mut env, "remove", IOFunc::Remove);
+        define_io_func(&mut env, "partition", IOFunc::Partition);
+        define_io_func(&mut env, "count", IOFunc::Count);
+        define_io_func(&mut env, "any", IOFunc::Any);
+        define_io_func(&mut env, "every", IOFunc::Every);
+        define_io_func(&mut env, "find", IOFunc::Find);
+        define_io_func(&mut env, "fold", IOFunc::Fold);
+        define_io_func(&mut env, "fold-right", IOFunc::FoldRight);
+        define_io_func(&mut env, "delete-duplicates", IOFunc::DeleteDuplicates);
+        define_io_func(&mut env, "append-map", IOFunc::AppendMap);
+        define_io_func(&mut env, "alist-update", IOFunc::AlistUpdate);
+        define_io_func(&mut env, "alist-delete", IOFunc::AlistDelete);
+        define_io_func(&mut env, "merge", IOFunc::Merge);
         define_io_func(&mut env, "read-contents", IOFunc::ReadContents);
+        define_io_func(&mut env, "command-line", IOFunc::CommandLine);
+        define_io_func(&mut env, "add-to-load-path", IOFunc::AddToLoadPath);
         define_io_func(&mut env, "read-all", IOFunc::ReadAll);
+        define_io_func(&mut env, "format", IOFunc::Format);
+        define_io_func(&mut env, "bound?", IOFunc::Bound);
+        define_io_func(&mut env, "call-with-values", IOFunc::CallWithValues);
+        define_io_func(&mut env, "force", IOFunc::Force);
+        define_io_func(&mut env, "stream-cdr", IOFunc::StreamCdr);
+        define_io_func(&mut env, "stream-ref", IOFunc::StreamRef);
         env
     }
 }
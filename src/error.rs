@@ -26,28 +26,144 @@ impl Display for ParserError {
 #[derive(Debug)]
 pub enum Error {
     NumArgs(usize, Vec<Value>),
+    /// Like `NumArgs`, for a call to a procedure defined in Scheme, with its
+    /// name if it has one.
+    ProcedureNumArgs(Option<String>, Arity, Vec<Value>),
     TypeMismatch(String, Value),
     Parser(ParserError),
     BadSpecialForm(String, Value),
     NotFunction(Value),
     UnboundVar(String, String),
+    /// A `set!` of a name bound with `define-constant`.
+    Constant(String),
+    /// A `set!` or `define` of a primitive while primitives are protected.
+    Primitive(String),
+    /// Calls nested deeper than the limit, with the limit.
+    StackOverflow(usize),
     EmptyBody,
     IO(io::Error),
     Port(String),
+    Format(String),
+    DivByZero,
+    AssertionFailed(Value),
+    OutOfRange(usize, Value),
+    MissingKey(Value),
+    Unwritable(Value),
+    /// An object passed to `raise` that no handler dealt with.
+    Raised(Value),
+    /// An error that escaped one or more procedure calls, with the calls it
+    /// passed through, innermost first.
+    Traced(Box<Error>, Vec<Value>),
+}
+
+/// How many arguments a procedure accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    Between(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// The arity of a procedure with `required` parameters, then `optional`
+    /// ones, and a rest parameter if `rest`.
+    pub fn new(required: usize, optional: usize, rest: bool) -> Self {
+        match (optional, rest) {
+            (_, true) => Arity::AtLeast(required),
+            (0, false) => Arity::Exactly(required),
+            (n, false) => Arity::Between(required, required + n),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
+/// How many calls a traced error keeps; deeper ones are dropped, since the
+/// innermost frames are the ones that explain the error.
+const MAX_FRAMES: usize = 10;
+
+impl Error {
+    /// The category of the error, which `guard` handlers can dispatch on.
+    pub fn kind(&self) -> &'static str {
+        match self.untraced() {
+            Error::NumArgs(_, _) | Error::ProcedureNumArgs(_, _, _) => "arity",
+            Error::TypeMismatch(_, _) | Error::NotFunction(_) | Error::Unwritable(_) => "type",
+            Error::Parser(_) | Error::BadSpecialForm(_, _) | Error::EmptyBody => "syntax",
+            Error::UnboundVar(_, _) => "unbound",
+            Error::Constant(_) | Error::Primitive(_) => "constant",
+            Error::IO(_) | Error::Port(_) => "io",
+            Error::Format(_) => "format",
+            Error::DivByZero => "arithmetic",
+            Error::AssertionFailed(_) => "assertion",
+            Error::OutOfRange(_, _) | Error::MissingKey(_) => "range",
+            Error::Raised(_) => "raise",
+            Error::StackOverflow(_) => "recursion",
+            Error::Traced(err, _) => err.kind(),
+        }
+    }
+
+    /// The error without the calls it was raised in.
+    pub fn untraced(&self) -> &Error {
+        match self {
+            Error::Traced(err, _) => err,
+            err => err,
+        }
+    }
+
+    /// Records that the error escaped the call `frame`.
+    pub fn in_frame(self, frame: Value) -> Error {
+        match self {
+            Error::Traced(err, mut frames) => {
+                if frames.len() < MAX_FRAMES {
+                    frames.push(frame);
+                }
+                Error::Traced(err, frames)
+            }
+            err => Error::Traced(Box::new(err), vec![frame]),
+        }
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::UnboundVar(msg, name) => write!(f, "{}: {}", msg, name),
+            Error::Constant(name) => write!(f, "Cannot assign to constant: {}", name),
+            Error::Primitive(name) => write!(f, "Cannot redefine primitive: {}", name),
             Error::BadSpecialForm(msg, form) => write!(f, "{}: {}", msg, form),
-            Error::NotFunction(form) => write!(f, "Not a function: {}", form),
+            Error::NotFunction(val) => write!(
+                f,
+                "Attempted to apply non-procedure {}: {}",
+                val.type_name(),
+                val
+            ),
             Error::NumArgs(expected, found) => write!(
                 f,
                 "Expected {} args; found values {}",
                 expected,
                 intersperse(found)
             ),
+            Error::ProcedureNumArgs(Some(name), expected, found) => write!(
+                f,
+                "{}: expected {} args; found values {}",
+                name,
+                expected,
+                intersperse(found)
+            ),
+            Error::ProcedureNumArgs(None, expected, found) => write!(
+                f,
+                "Expected {} args; found values {}",
+                expected,
+                intersperse(found)
+            ),
             Error::TypeMismatch(expected, found) => {
                 write!(f, "Invalid type: expected {}, found {}", expected, found)
             }
@@ -55,22 +171,55 @@ impl Display for Error {
             Error::EmptyBody => write!(f, "Function has empty body"),
             Error::IO(e) => write!(f, "IO error: {}", e),
             Error::Port(msg) => write!(f, "Port error: {}", msg),
+            Error::Format(msg) => write!(f, "Format error: {}", msg),
+            Error::DivByZero => write!(f, "Division by zero"),
+            Error::AssertionFailed(form) => write!(f, "Assertion failed: {}", form),
+            Error::OutOfRange(index, val) => write!(f, "Index {} out of range for {}", index, val),
+            Error::MissingKey(key) => write!(f, "No value for key: {}", key),
+            Error::Unwritable(val) => {
+                write!(f, "Cannot write {} as source: {}", val.type_name(), val)
+            }
+            Error::Raised(obj) => write!(f, "Uncaught exception: {}", obj),
+            Error::StackOverflow(limit) => {
+                write!(f, "Stack overflow: calls nested deeper than {}", limit)
+            }
+            Error::Traced(err, frames) => {
+                write!(f, "{}", err)?;
+                for frame in frames {
+                    write!(f, "\n  in {}", frame)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+/// Errors compare by what went wrong, not by the calls they escaped.
 impl PartialEq for Error {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
+        let (this, other) = (self.untraced(), other.untraced());
+        match (this, other) {
             (Self::NumArgs(l0, l1), Self::NumArgs(r0, r1)) => l0 == r0 && l1 == r1,
+            (Self::ProcedureNumArgs(l0, l1, l2), Self::ProcedureNumArgs(r0, r1, r2)) => {
+                l0 == r0 && l1 == r1 && l2 == r2
+            }
             (Self::TypeMismatch(l0, l1), Self::TypeMismatch(r0, r1)) => l0 == r0 && l1 == r1,
             (Self::Parser(l0), Self::Parser(r0)) => l0 == r0,
             (Self::BadSpecialForm(l0, l1), Self::BadSpecialForm(r0, r1)) => l0 == r0 && l1 == r1,
             (Self::NotFunction(l0), Self::NotFunction(r0)) => l0 == r0,
             (Self::UnboundVar(l0, l1), Self::UnboundVar(r0, r1)) => l0 == r0 && l1 == r1,
+            (Self::Constant(l0), Self::Constant(r0)) => l0 == r0,
+            (Self::Primitive(l0), Self::Primitive(r0)) => l0 == r0,
             (Self::IO(l0), Self::IO(r0)) => l0.kind() == r0.kind(),
             (Self::Port(l0), Self::Port(r0)) => l0 == r0,
-            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+            (Self::Format(l0), Self::Format(r0)) => l0 == r0,
+            (Self::AssertionFailed(l0), Self::AssertionFailed(r0)) => l0 == r0,
+            (Self::OutOfRange(l0, l1), Self::OutOfRange(r0, r1)) => l0 == r0 && l1 == r1,
+            (Self::MissingKey(l0), Self::MissingKey(r0)) => l0 == r0,
+            (Self::Unwritable(l0), Self::Unwritable(r0)) => l0 == r0,
+            (Self::Raised(l0), Self::Raised(r0)) => l0 == r0,
+            (Self::StackOverflow(l0), Self::StackOverflow(r0)) => l0 == r0,
+            _ => core::mem::discriminant(this) == core::mem::discriminant(other),
         }
     }
 }
@@ -1,238 +1,1426 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
 use crate::{
+    bigint::BigInt,
     env::Env,
-    error::Error,
-    primitive::{self, load},
-    value::{IOFunc, PrimitiveFunc, Value, QUOTE},
+    error::{Arity, Error},
+    hash::ValueKey,
+    number::Number,
+    primitive::{self, fold_case, load},
+    symbol::Symbol,
+    syntax::SyntaxRules,
+    value::{IOFunc, NativeClosure, PrimitiveFunc, PromiseState, Value, QUOTE},
 };
 
 type Result<T> = std::result::Result<T, Error>;
 
-pub fn apply(env: &mut Env, val: &Value, args: &[Value]) -> Result<Value> {
+/// Names handled by `eval` itself rather than by applying a procedure, with
+/// the shape each expects for reporting malformed uses.
+static SPECIAL_FORMS: &[(&str, &str)] = &[
+    (QUOTE, "(quote datum)"),
+    ("delay", "(delay expr)"),
+    ("cons-stream", "(cons-stream head tail)"),
+    ("if", "(if test conseq [alt])"),
+    ("set!", "(set! var expr)"),
+    (
+        "define-memoized",
+        "(define-memoized (name params ...) body ...)",
+    ),
+    (
+        "define",
+        "(define var expr) or (define (name params ...) body ...)",
+    ),
+    ("define-constant", "(define-constant var expr)"),
+    (
+        "define-syntax",
+        "(define-syntax name (syntax-rules (literal ...) (pattern template) ...))",
+    ),
+    ("lambda", "(lambda (params ...) body ...)"),
+    ("load", "(load \"path\" [\"prefix\"])"),
+    ("assert", "(assert expr)"),
+    ("letrec*", "(letrec* ((var init) ...) body ...)"),
+    ("fluid-let", "(fluid-let ((var expr) ...) body ...)"),
+    (
+        "do",
+        "(do ((var init [step]) ...) (test expr ...) body ...)",
+    ),
+    ("and", "(and expr ...)"),
+    ("or", "(or expr ...)"),
+    ("when", "(when test expr ...)"),
+    ("unless", "(unless test expr ...)"),
+    ("cond", "(cond (test expr ...) ... [(else expr ...)])"),
+    ("guard", "(guard (var clause ...) body ...)"),
+    (
+        "define-library",
+        "(define-library (name ...) (export var ...) ... (begin body ...) ...)",
+    ),
+    ("import", "(import (name ...) ...)"),
+    ("cut", "(cut slot-or-expr ... [<...>])"),
+    ("cute", "(cute slot-or-expr ... [<...>])"),
+];
+
+pub fn is_special_form(name: &str) -> bool {
+    SPECIAL_FORMS.iter().any(|(form, _)| *form == name)
+}
+
+fn bad_special_form(name: &str, form: &Value) -> Error {
+    let (_, syntax) = SPECIAL_FORMS
+        .iter()
+        .find(|(special_form, _)| *special_form == name)
+        .expect("only special forms are reported as malformed");
+    Error::BadSpecialForm(format!("{}: expected {}", name, syntax), form.clone())
+}
+
+fn define_syntax(env: &mut Env, form: &Value, name: Symbol, spec: &Value) -> Result<Value> {
+    let rules = SyntaxRules::parse(spec).ok_or_else(|| bad_special_form("define-syntax", form))?;
+    env.define_macro(name.to_string(), rules)?;
+    Ok(Value::Unspecified)
+}
+
+fn define_memoized(env: &mut Env, form: &Value, head: &Value, body: &[Value]) -> Result<Value> {
+    let (name_params, vararg) = match head {
+        Value::List(name_params) => (name_params, None),
+        Value::DottedList(name_params, vararg) => (name_params, Some(vararg.as_ref())),
+        _ => return Err(bad_special_form("define-memoized", form)),
+    };
+    let [Value::Atom(name), params @ ..] = &name_params[..] else {
+        return Err(bad_special_form("define-memoized", form));
+    };
+    let func = make_func(env, "define-memoized", form, params, vararg, body)?;
+    let cache = Rc::new(RefCell::new(HashMap::new()));
+    let memoized = NativeClosure::Memoize(Rc::new(named(func, *name)), cache);
+    env.define_checked(name.to_string(), Value::NativeClosure(memoized))
+}
+
+fn define_constant(env: &mut Env, var: Symbol, form: &Value) -> Result<Value> {
+    let val = named(eval(env, form)?, var);
+    env.define_constant(var.to_string(), val)
+}
+
+/// Expands a use of the macro `name` and evaluates the result in its place.
+fn eval_macro_use(env: &mut Env, name: &str, form: &Rc<[Value]>) -> Result<Tail> {
+    let rules = env.get_macro(name).expect("only macros are expanded");
+    let expanded = match env.expansion(form, &rules) {
+        Some(expanded) => expanded,
+        None => {
+            let expanded = rules.expand(env, &Value::List(form.clone()))?;
+            env.cache_expansion(form.clone(), rules, expanded.clone());
+            expanded
+        }
+    };
+    eval_tail(env, &expanded)
+}
+
+/// The outcome of evaluating a form in tail position: either its value or a
+/// call left for the enclosing `apply` to make without growing the stack.
+enum Tail {
+    Return(Value),
+    Call(Value, Vec<Value>),
+}
+
+fn eval_tail(env: &mut Env, val: &Value) -> Result<Tail> {
     match val {
-        Value::PrimitiveFunc(func) => match func {
-            PrimitiveFunc::Add => primitive::numeric_binop(args, |acc, val| acc + val),
-            PrimitiveFunc::Sub => primitive::numeric_binop(args, |acc, val| acc - val),
-            PrimitiveFunc::Mul => primitive::numeric_binop(args, |acc, val| acc * val),
-            PrimitiveFunc::Div => primitive::numeric_binop(args, |acc, val| acc / val),
-            PrimitiveFunc::Rem => primitive::numeric_binop(args, |acc, val| acc % val),
-            PrimitiveFunc::Eq => primitive::numeric_bool_binop(args, |lhs, rhs| lhs == rhs),
-            PrimitiveFunc::Lt => primitive::numeric_bool_binop(args, |lhs, rhs| lhs < rhs),
-            PrimitiveFunc::Gt => primitive::numeric_bool_binop(args, |lhs, rhs| lhs > rhs),
-            PrimitiveFunc::Ne => primitive::numeric_bool_binop(args, |lhs, rhs| lhs != rhs),
-            PrimitiveFunc::Ge => primitive::numeric_bool_binop(args, |lhs, rhs| lhs >= rhs),
-            PrimitiveFunc::Le => primitive::numeric_bool_binop(args, |lhs, rhs| lhs <= rhs),
-            PrimitiveFunc::And => primitive::bool_bool_binop(args, |lhs, rhs| lhs && rhs),
-            PrimitiveFunc::Or => primitive::bool_bool_binop(args, |lhs, rhs| lhs || rhs),
-            PrimitiveFunc::StringEq => primitive::string_bool_binop(args, |lhs, rhs| lhs == rhs),
-            PrimitiveFunc::StringLt => primitive::string_bool_binop(args, |lhs, rhs| lhs < rhs),
-            PrimitiveFunc::StringGt => primitive::string_bool_binop(args, |lhs, rhs| lhs > rhs),
-            PrimitiveFunc::StringLe => primitive::string_bool_binop(args, |lhs, rhs| lhs <= rhs),
-            PrimitiveFunc::StringGe => primitive::string_bool_binop(args, |lhs, rhs| lhs >= rhs),
-            PrimitiveFunc::Car => primitive::car(args),
-            PrimitiveFunc::Cdr => primitive::cdr(args),
-            PrimitiveFunc::Cons => primitive::cons(args),
-            PrimitiveFunc::Eqv => primitive::eqv(args),
-            PrimitiveFunc::Equal => primitive::equal(args),
+        Value::List(vals) => match &vals[..] {
+            [Value::Atom(atom), pred, conseq, alt] if atom == "if" => {
+                match eval(env, pred)?.is_truthy() {
+                    true => eval_tail(env, conseq),
+                    false => eval_tail(env, alt),
+                }
+            }
+            [Value::Atom(atom), pred, conseq] if atom == "if" => {
+                match eval(env, pred)?.is_truthy() {
+                    true => eval_tail(env, conseq),
+                    false => Ok(Tail::Return(Value::Unspecified)),
+                }
+            }
+            [Value::Atom(atom), args @ ..] if atom == "and" => match args.split_last() {
+                Some((last, init)) => {
+                    for arg in init {
+                        let val = eval(env, arg)?;
+                        if !val.is_truthy() {
+                            return Ok(Tail::Return(val));
+                        }
+                    }
+                    eval_tail(env, last)
+                }
+                None => Ok(Tail::Return(Value::Bool(true))),
+            },
+            [Value::Atom(atom), args @ ..] if atom == "or" => match args.split_last() {
+                Some((last, init)) => {
+                    for arg in init {
+                        let val = eval(env, arg)?;
+                        if val.is_truthy() {
+                            return Ok(Tail::Return(val));
+                        }
+                    }
+                    eval_tail(env, last)
+                }
+                None => Ok(Tail::Return(Value::Bool(false))),
+            },
+            [Value::Atom(atom), args @ ..] if atom == "when" || atom == "unless" => {
+                let (test, body) = args
+                    .split_first()
+                    .ok_or_else(|| bad_special_form(atom.as_str(), val))?;
+                match eval(env, test)?.is_truthy() == (atom == "when") {
+                    true => eval_sequence(env, body),
+                    false => Ok(Tail::Return(Value::Unspecified)),
+                }
+            }
+            [Value::Atom(atom), clauses @ ..] if atom == "cond" => {
+                let tail = eval_cond(env, "cond", val, clauses)?;
+                Ok(tail.unwrap_or(Tail::Return(Value::Unspecified)))
+            }
+            // The bindings are left in place for the rest of the call; the
+            // caller restores its scope once the call returns.
+            [Value::Atom(atom), Value::List(bindings), body @ ..]
+                if atom == "letrec*" && !body.is_empty() =>
+            {
+                bind_letrec(env, val, bindings)?;
+                eval_body(env, body)
+            }
+            [Value::Atom(atom), ..] if is_special_form(atom.as_str()) => {
+                eval(env, val).map(Tail::Return)
+            }
+            [Value::Atom(atom), ..] if env.is_macro(atom.as_str()) => {
+                eval_macro_use(env, atom.as_str(), vals)
+            }
+            [func, args @ ..] => {
+                let func = eval(env, func)?;
+                let args = args
+                    .iter()
+                    .map(|arg| eval(env, arg))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Tail::Call(func, args))
+            }
+            [] => eval(env, val).map(Tail::Return),
         },
+        _ => eval(env, val).map(Tail::Return),
+    }
+}
+
+/// Calls a primitive. These never call back into `apply`, so keeping them out
+/// of it keeps the frame of every interpreted call small.
+fn apply_primitive(func: &PrimitiveFunc, args: &[Value]) -> Result<Value> {
+    match func {
+        PrimitiveFunc::Add => primitive::numeric_fold(args, 0.into(), |acc, val| Ok(acc + val)),
+        PrimitiveFunc::Sub => primitive::sub(args),
+        PrimitiveFunc::Mul => primitive::numeric_fold(args, 1.into(), |acc, val| Ok(acc * val)),
+        PrimitiveFunc::Div => primitive::div(args),
+        PrimitiveFunc::Quotient => primitive::integer_div_binop(args, |acc, val| acc / val),
+        PrimitiveFunc::Rem => primitive::integer_div_binop(args, |acc, val| acc % val),
+        PrimitiveFunc::FloorDiv => primitive::integer_div_values(args, BigInt::div_rem_floor),
+        PrimitiveFunc::FloorQuotient => {
+            primitive::integer_div_binop(args, |acc, val| acc.div_rem_floor(val).0)
+        }
+        PrimitiveFunc::FloorRemainder => {
+            primitive::integer_div_binop(args, |acc, val| acc.div_rem_floor(val).1)
+        }
+        PrimitiveFunc::TruncateDiv => primitive::integer_div_values(args, BigInt::div_rem),
+        PrimitiveFunc::ExactIntegerSqrt => primitive::exact_integer_sqrt(args),
+        PrimitiveFunc::Increment => primitive::step(args, 1),
+        PrimitiveFunc::Decrement => primitive::step(args, -1),
+        PrimitiveFunc::Boolean => primitive::boolean(args),
+        PrimitiveFunc::IsExact => primitive::exact_number_is(args, true),
+        PrimitiveFunc::IsInexact => primitive::exact_number_is(args, false),
+        PrimitiveFunc::IsRational | PrimitiveFunc::IsReal => {
+            primitive::is_number_where(args, |_| true)
+        }
+        PrimitiveFunc::IsInteger | PrimitiveFunc::IsExactInteger => {
+            primitive::is_number_where(args, |n| matches!(n, Number::Integer(_)))
+        }
+        PrimitiveFunc::IsExactNonnegativeInteger => primitive::is_number_where(
+            args,
+            |n| matches!(n, Number::Integer(n) if !n.is_negative()),
+        ),
+        PrimitiveFunc::Numerator => primitive::numerator(args),
+        PrimitiveFunc::Denominator => primitive::denominator(args),
+        PrimitiveFunc::Rationalize => primitive::rationalize(args),
+        PrimitiveFunc::Eq => primitive::numeric_bool_binop(args, |lhs, rhs| lhs == rhs),
+        PrimitiveFunc::Lt => primitive::numeric_bool_binop(args, |lhs, rhs| lhs < rhs),
+        PrimitiveFunc::Gt => primitive::numeric_bool_binop(args, |lhs, rhs| lhs > rhs),
+        PrimitiveFunc::Ne => primitive::numeric_bool_binop(args, |lhs, rhs| lhs != rhs),
+        PrimitiveFunc::Ge => primitive::numeric_bool_binop(args, |lhs, rhs| lhs >= rhs),
+        PrimitiveFunc::Le => primitive::numeric_bool_binop(args, |lhs, rhs| lhs <= rhs),
+        PrimitiveFunc::And => primitive::bool_bool_binop(args, |lhs, rhs| lhs && rhs),
+        PrimitiveFunc::Or => primitive::bool_bool_binop(args, |lhs, rhs| lhs || rhs),
+        PrimitiveFunc::Not => primitive::not(args),
+        PrimitiveFunc::ErrorKind => primitive::error_kind(args),
+        PrimitiveFunc::ErrorMessage => primitive::error_message(args),
+        PrimitiveFunc::StringEq => primitive::string_bool_binop(args, |lhs, rhs| lhs == rhs),
+        PrimitiveFunc::StringLt => primitive::string_bool_binop(args, |lhs, rhs| lhs < rhs),
+        PrimitiveFunc::StringGt => primitive::string_bool_binop(args, |lhs, rhs| lhs > rhs),
+        PrimitiveFunc::StringLe => primitive::string_bool_binop(args, |lhs, rhs| lhs <= rhs),
+        PrimitiveFunc::StringGe => primitive::string_bool_binop(args, |lhs, rhs| lhs >= rhs),
+        PrimitiveFunc::StringCiEq => {
+            primitive::string_bool_binop(args, |lhs, rhs| fold_case(lhs) == fold_case(rhs))
+        }
+        PrimitiveFunc::StringCiLt => {
+            primitive::string_bool_binop(args, |lhs, rhs| fold_case(lhs) < fold_case(rhs))
+        }
+        PrimitiveFunc::StringCiGt => {
+            primitive::string_bool_binop(args, |lhs, rhs| fold_case(lhs) > fold_case(rhs))
+        }
+        PrimitiveFunc::StringCiLe => {
+            primitive::string_bool_binop(args, |lhs, rhs| fold_case(lhs) <= fold_case(rhs))
+        }
+        PrimitiveFunc::StringCiGe => {
+            primitive::string_bool_binop(args, |lhs, rhs| fold_case(lhs) >= fold_case(rhs))
+        }
+        PrimitiveFunc::Car => primitive::car(args),
+        PrimitiveFunc::Cdr => primitive::cdr(args),
+        PrimitiveFunc::Cons => primitive::cons(args),
+        PrimitiveFunc::Eqv => primitive::eqv(args),
+        PrimitiveFunc::Equal => primitive::equal(args),
+        PrimitiveFunc::Values => primitive::values(args),
+        PrimitiveFunc::CharToInteger => primitive::char_to_integer(args),
+        PrimitiveFunc::IntegerToChar => primitive::integer_to_char(args),
+        PrimitiveFunc::CharUpcase => primitive::char_unop(args, char::to_uppercase),
+        PrimitiveFunc::CharDowncase => primitive::char_unop(args, char::to_lowercase),
+        PrimitiveFunc::CharFoldcase => primitive::char_unop(args, char::to_lowercase),
+        PrimitiveFunc::StringFoldcase => primitive::string_foldcase(args),
+        PrimitiveFunc::StringLength => primitive::string_length(args),
+        PrimitiveFunc::StringRef => primitive::string_ref(args),
+        PrimitiveFunc::Substring => primitive::substring(args),
+        PrimitiveFunc::Bytevector => primitive::bytevector(args),
+        PrimitiveFunc::MakeBytevector => primitive::make_bytevector(args),
+        PrimitiveFunc::BytevectorU8Ref => primitive::bytevector_u8_ref(args),
+        PrimitiveFunc::BytevectorU8Set => primitive::bytevector_u8_set(args),
+        PrimitiveFunc::BytevectorLength => primitive::bytevector_length(args),
+        PrimitiveFunc::StringToVector => primitive::string_to_vector(args),
+        PrimitiveFunc::VectorToString => primitive::vector_to_string(args),
+        PrimitiveFunc::String => primitive::string(args),
+        PrimitiveFunc::ListToString => primitive::list_to_string(args),
+        PrimitiveFunc::StringTrim => primitive::string_trim(args, true, true),
+        PrimitiveFunc::StringTrimLeft => primitive::string_trim(args, true, false),
+        PrimitiveFunc::StringTrimRight => primitive::string_trim(args, false, true),
+        PrimitiveFunc::VectorAppend => primitive::vector_append(args),
+        PrimitiveFunc::Gensym => primitive::gensym(args),
+        PrimitiveFunc::VectorCopy => primitive::vector_copy(args),
+        PrimitiveFunc::MakeHashTable => primitive::make_hash_table(args),
+        PrimitiveFunc::IsHashTable => primitive::is_hash_table(args),
+        PrimitiveFunc::HashSet => primitive::hash_set(args),
+        PrimitiveFunc::HashRef => primitive::hash_ref(args),
+        PrimitiveFunc::NestedRef => primitive::nested_ref(args),
+        PrimitiveFunc::HashCount => primitive::hash_count(args),
+        PrimitiveFunc::EqualHash => primitive::equal_hash(args),
+        PrimitiveFunc::AlistToHashTable => primitive::alist_to_hash_table(args),
+        PrimitiveFunc::HashTableToAlist => primitive::hash_table_to_alist(args),
+        PrimitiveFunc::GetEnvironmentVariable => primitive::get_environment_variable(args),
+        PrimitiveFunc::Identity => primitive::identity(args),
+        PrimitiveFunc::Compose => primitive::compose(args),
+        PrimitiveFunc::Curry => primitive::curry(args),
+        PrimitiveFunc::Append => primitive::append(args),
+        PrimitiveFunc::Flatten => primitive::flatten(args),
+        PrimitiveFunc::Reverse => primitive::reverse(args),
+        PrimitiveFunc::EofObject => primitive::eof_object(args),
+        PrimitiveFunc::IsEofObject => primitive::is_eof_object(args),
+        PrimitiveFunc::IsPort => primitive::is_port(args),
+    }
+}
+
+pub fn apply(env: &mut Env, val: &Value, args: &[Value]) -> Result<Value> {
+    env.enter_call()?;
+    let result = apply_call(env, val, args);
+    env.leave_call();
+    result
+}
+
+fn apply_call(env: &mut Env, val: &Value, args: &[Value]) -> Result<Value> {
+    match val {
+        Value::PrimitiveFunc(func) => apply_primitive(func, args),
         Value::IOFunc(func) => match func {
             IOFunc::Apply => primitive::apply_proc(env, args),
             IOFunc::MakeReadPort => primitive::make_read_port(env, args),
             IOFunc::MakeWritePort => primitive::make_write_port(env, args),
+            IOFunc::MakeStringReadPort => primitive::make_string_read_port(env, args),
+            IOFunc::MakeBinaryReadPort => primitive::make_binary_read_port(env, args),
+            IOFunc::MakeBinaryWritePort => primitive::make_binary_write_port(env, args),
             IOFunc::ClosePort => primitive::close_port(env, args),
+            IOFunc::CallWithInputFile => primitive::call_with_input_file(env, args),
+            IOFunc::CallWithOutputFile => primitive::call_with_output_file(env, args),
+            IOFunc::WithInputFromFile => primitive::with_input_from_file(env, args),
+            IOFunc::WithOutputToFile => primitive::with_output_to_file(env, args),
             IOFunc::Read => primitive::read_proc(env, args),
+            IOFunc::ReadLine => primitive::read_line(env, args),
+            IOFunc::ReadChar => primitive::read_char(env, args, true),
+            IOFunc::PeekChar => primitive::read_char(env, args, false),
+            IOFunc::CharReady => primitive::char_ready(env, args),
+            IOFunc::IsInputPort => primitive::is_input_port(env, args),
+            IOFunc::IsOutputPort => primitive::is_output_port(env, args),
+            IOFunc::WithExceptionHandler => primitive::with_exception_handler(env, args),
+            IOFunc::Raise => primitive::raise(env, args, false),
+            IOFunc::RaiseContinuable => primitive::raise(env, args, true),
+            IOFunc::WriteChar => primitive::write_char(env, args),
             IOFunc::Write => primitive::write_proc(env, args),
+            IOFunc::WriteShared => primitive::write_shared(env, args),
+            IOFunc::Display => primitive::display(env, args),
+            IOFunc::Describe => primitive::describe(env, args),
+            IOFunc::ReadU8 => primitive::read_u8(env, args),
+            IOFunc::WriteU8 => primitive::write_u8(env, args),
+            IOFunc::ReadBytevector => primitive::read_bytevector(env, args),
+            IOFunc::WriteBytevector => primitive::write_bytevector(env, args),
+            IOFunc::CommandLine => primitive::command_line(env, args),
+            IOFunc::AddToLoadPath => primitive::add_to_load_path(env, args),
+            IOFunc::HashUpdate => primitive::hash_update(env, args),
+            IOFunc::Sort => primitive::sort(env, args),
+            IOFunc::ListSort => primitive::list_sort(env, args),
+            IOFunc::Map => primitive::map(env, args),
+            IOFunc::ForEach => primitive::for_each(env, args),
+            IOFunc::Filter => primitive::filter(env, args, true),
+            IOFunc::Remove => primitive::filter(env, args, false),
+            IOFunc::Partition => primitive::partition(env, args),
+            IOFunc::Count => primitive::count(env, args),
+            IOFunc::Any => primitive::any(env, args),
+            IOFunc::Every => primitive::every(env, args),
+            IOFunc::Find => primitive::find(env, args),
+            IOFunc::Fold => primitive::fold(env, args, false),
+            IOFunc::FoldRight => primitive::fold(env, args, true),
+            IOFunc::DeleteDuplicates => primitive::delete_duplicates(env, args),
+            IOFunc::AppendMap => primitive::append_map(env, args),
+            IOFunc::AlistUpdate => primitive::alist_update(env, args),
+            IOFunc::AlistDelete => primitive::alist_delete(env, args),
+            IOFunc::Merge => primitive::merge(env, args),
             IOFunc::ReadContents => primitive::read_contents(args),
             IOFunc::ReadAll => primitive::read_all(args),
+            IOFunc::Format => primitive::format(env, args),
+            IOFunc::Bound => primitive::bound(env, args),
+            IOFunc::CallWithValues => primitive::call_with_values(env, args),
+            IOFunc::Force => primitive::force(env, args),
+            IOFunc::StreamCdr => primitive::stream_cdr(env, args),
+            IOFunc::StreamRef => primitive::stream_ref(env, args),
         },
-        Value::Func {
-            params,
-            vararg,
-            body,
-            closure,
-        } => {
-            if params.len() != args.len() && vararg == &None {
-                return Err(Error::NumArgs(params.len(), args.to_vec()));
-            }
-            env.with_closure(closure);
-            let mut last = 0;
-            for i in 0..params.len() {
-                last = i;
-                let param = &params[i];
-                let arg = &args[i];
-                env.define_var(param.to_owned(), arg.clone());
+        Value::Func { .. } => {
+            let mut func = val.clone();
+            let mut args = args.to_vec();
+            // A call in tail position rebinds the parameters and loops here
+            // rather than recursing, so tail calls run in constant stack.
+            while let Value::Func {
+                name,
+                params,
+                optionals,
+                vararg,
+                body,
+                closure,
+            } = &func
+            {
+                let max = params.len() + optionals.len();
+                if args.len() < params.len() || (args.len() > max && vararg.is_none()) {
+                    let arity = Arity::new(params.len(), optionals.len(), vararg.is_some());
+                    let name = name.map(|name| name.to_string());
+                    return Err(Error::ProcedureNumArgs(name, arity, args));
+                }
+                env.with_closure(closure);
+                for (param, arg) in params.iter().zip(&args) {
+                    env.define_var(param.to_owned(), arg.clone());
+                }
+                // Defaults are evaluated in the callee's scope, so they can
+                // refer to the parameters before them.
+                for (i, (param, default)) in optionals.iter().enumerate() {
+                    let arg = match args.get(params.len() + i) {
+                        Some(arg) => arg.clone(),
+                        None => eval(env, default)?,
+                    };
+                    env.define_var(param.to_owned(), arg);
+                }
+                if let Some(vararg) = vararg {
+                    let rest = Value::List(args[max.min(args.len())..].into());
+                    env.define_var(vararg.to_owned(), rest);
+                }
+                let tail =
+                    eval_body(env, body).map_err(|err| err.in_frame(call_form(&func, &args)))?;
+                match tail {
+                    Tail::Return(val) => return Ok(val),
+                    Tail::Call(next, next_args) => {
+                        func = next;
+                        args = next_args;
+                    }
+                }
             }
-            if let Some(vararg) = vararg {
-                env.define_var(vararg.to_owned(), Value::List(args[last..].to_vec()));
+            apply(env, &func, &args)
+        }
+        Value::NativeClosure(NativeClosure::Compose(funcs)) => match funcs.split_last() {
+            Some((first, rest)) => {
+                let mut val = apply(env, first, args)?;
+                for func in rest.iter().rev() {
+                    val = match val {
+                        Value::Values(vals) => apply(env, func, &vals)?,
+                        val => apply(env, func, &[val])?,
+                    };
+                }
+                Ok(val)
             }
-            let mut ret = None;
-            for val in body {
-                ret = Some(eval(env, val)?);
+            None => primitive::identity(args),
+        },
+        Value::NativeClosure(NativeClosure::Curry(func, curried)) => {
+            let args: Vec<Value> = curried.iter().chain(args).cloned().collect();
+            apply(env, func, &args)
+        }
+        // Calls with arguments that can't be hashed, such as procedures, are
+        // passed through uncached.
+        Value::NativeClosure(NativeClosure::Memoize(func, cache)) => {
+            let Ok(key) = ValueKey::try_from(&Value::List(args.into())) else {
+                return apply(env, func, args);
+            };
+            if let Some(val) = cache.borrow().get(&key) {
+                return Ok(val.clone());
             }
-            ret.ok_or(Error::EmptyBody)
+            let val = apply(env, func, args)?;
+            cache.borrow_mut().insert(key, val.clone());
+            Ok(val)
         }
         _ => Err(Error::NotFunction(val.clone())),
     }
 }
 
-pub fn eval(env: &mut Env, val: &Value) -> Result<Value> {
-    match val {
-        Value::String(_) => Ok(val.clone()),
-        Value::Number(_) => Ok(val.clone()),
-        Value::Bool(_) => Ok(val.clone()),
-        Value::Atom(id) => env.get_var(id).cloned(),
-        Value::List(vals) => match &vals[..] {
-            [Value::Atom(atom), val] if atom == QUOTE => Ok(val.clone()),
-            [Value::Atom(atom), pred, conseq, alt] if atom == "if" => {
-                let result = eval(env, pred)?;
-                match result {
-                    Value::Bool(false) => eval(env, alt),
-                    _ => eval(env, conseq),
+/// Finishes a tail call left by `eval_tail` when the caller can't loop on it,
+/// restoring the caller's scope afterwards.
+fn resolve(env: &mut Env, tail: Tail) -> Result<Value> {
+    match tail {
+        Tail::Return(val) => Ok(val),
+        Tail::Call(func, args) => {
+            let closure = env.make_closure();
+            let ret = apply(env, &func, &args);
+            env.load_closure(closure);
+            ret
+        }
+    }
+}
+
+/// Evaluates forms in order, leaving the last in tail position.
+fn eval_sequence(env: &mut Env, forms: &[Value]) -> Result<Tail> {
+    match forms.split_last() {
+        Some((last, init)) => {
+            for form in init {
+                eval(env, form)?;
+            }
+            eval_tail(env, last)
+        }
+        None => Ok(Tail::Return(Value::Unspecified)),
+    }
+}
+
+/// Picks the first `cond` clause whose test holds, or `None` if none does. A
+/// clause with no body yields the test's value and `=>` passes it to a
+/// procedure. `name` is the form the clauses belong to, for error reports.
+fn eval_cond(env: &mut Env, name: &str, form: &Value, clauses: &[Value]) -> Result<Option<Tail>> {
+    for clause in clauses {
+        let Value::List(clause) = clause else {
+            return Err(bad_special_form(name, form));
+        };
+        match &clause[..] {
+            [Value::Atom(atom), body @ ..] if atom == "else" => {
+                return eval_sequence(env, body).map(Some)
+            }
+            [test, Value::Atom(arrow), func] if arrow == "=>" => {
+                let val = eval(env, test)?;
+                if val.is_truthy() {
+                    return Ok(Some(Tail::Call(eval(env, func)?, vec![val])));
                 }
             }
-            [Value::Atom(atom), Value::Atom(var), form] if atom == "set!" => {
-                let val = eval(env, form)?;
-                env.set_var(var, val)
+            [test] => {
+                let val = eval(env, test)?;
+                if val.is_truthy() {
+                    return Ok(Some(Tail::Return(val)));
+                }
             }
-            [Value::Atom(atom), Value::Atom(var), form] if atom == "define" => {
-                let val = eval(env, form)?;
-                Ok(env.define_var(var.clone(), val))
-            }
-            [Value::Atom(atom), Value::List(name_args), body @ ..] if atom == "define" => {
-                let (name, args) = match &name_args[..] {
-                    [Value::Atom(name), args @ ..] => (name.clone(), args.to_vec()),
-                    _ => {
-                        return Err(Error::BadSpecialForm(
-                            "unrecognized special form".to_owned(),
-                            val.clone(),
-                        ));
+            [test, body @ ..] => {
+                if eval(env, test)?.is_truthy() {
+                    return eval_sequence(env, body).map(Some);
+                }
+            }
+            [] => return Err(bad_special_form(name, form)),
+        }
+    }
+    Ok(None)
+}
+
+/// Evaluates a `guard` body. If it fails, the error is bound to `var` as a
+/// condition, or as the raised object for `raise`, and the handler clauses
+/// are tried like `cond` clauses; an error no clause handles is returned
+/// unchanged.
+fn eval_guard(
+    env: &mut Env,
+    form: &Value,
+    var: Symbol,
+    clauses: &[Value],
+    body: &[Value],
+) -> Result<Value> {
+    let closure = env.make_closure();
+    // The guard handles what is raised in its body before any handler
+    // installed around it, so those are hidden until the body is done.
+    let handlers = env.replace_handlers(Vec::new());
+    let ret = eval_body(env, body).and_then(|tail| resolve(env, tail));
+    env.replace_handlers(handlers);
+    env.load_closure(closure);
+    let err = match ret {
+        Ok(val) => return Ok(val),
+        Err(err) => err,
+    };
+    let closure = env.make_closure();
+    let condition = match err.untraced() {
+        Error::Raised(obj) => obj.clone(),
+        err => Value::Condition {
+            kind: err.kind(),
+            message: err.to_string(),
+        },
+    };
+    env.define_var(var.to_string(), condition);
+    let ret = match eval_cond(env, "guard", form, clauses) {
+        Ok(Some(tail)) => resolve(env, tail).map(Some),
+        Ok(None) => Ok(None),
+        Err(err) => Err(err),
+    };
+    env.load_closure(closure);
+    ret?.ok_or(err)
+}
+
+/// Evaluates a body, leaving its last form in tail position. Internal defines
+/// are bound up front, like `letrec*`, so the procedures they create can see
+/// every name in the body.
+fn eval_body(env: &mut Env, body: &[Value]) -> Result<Tail> {
+    let defines: Vec<_> = body.iter().map(internal_define).collect();
+    for (name, _) in defines.iter().flatten() {
+        env.define_var(name.to_string(), Value::Unspecified);
+    }
+    let (last, init) = body.split_last().ok_or(Error::EmptyBody)?;
+    for (val, define) in init.iter().zip(&defines) {
+        match define {
+            Some((name, expr)) => {
+                let val = named(eval(env, expr)?, *name);
+                env.set_var(name.as_str(), val)?;
+            }
+            None => {
+                eval(env, val)?;
+            }
+        }
+    }
+    match &defines[init.len()] {
+        Some((name, expr)) => {
+            let val = named(eval(env, expr)?, *name);
+            Ok(Tail::Return(env.set_var(name.as_str(), val)?))
+        }
+        None => eval_tail(env, last),
+    }
+}
+
+/// Evaluates a `define-library` form. This is a subset of R7RS libraries:
+/// declarations are `export` of plain names (no `rename`), `import` and
+/// `begin`. The bodies see the bindings around the form and are evaluated
+/// together like a body, so the library's definitions can refer to each
+/// other in any order, but only the exported ones outlive the form.
+fn define_library(env: &mut Env, form: &Value, name: &Value, decls: &[Value]) -> Result<Value> {
+    let mut exports = Vec::new();
+    let mut imports = Vec::new();
+    let mut body = Vec::new();
+    for decl in decls {
+        let Value::List(decl) = decl else {
+            return Err(bad_special_form("define-library", form));
+        };
+        match &decl[..] {
+            [Value::Atom(atom), names @ ..] if atom == "export" => {
+                for name in names {
+                    match name {
+                        Value::Atom(name) => exports.push(*name),
+                        _ => return Err(bad_special_form("define-library", form)),
                     }
-                };
-                let closure = env.make_closure();
-                let params = args.into_iter().map(|arg| arg.to_string()).collect();
-                let vararg = None;
-                let body = body.to_vec();
-                let func = Value::Func {
-                    params,
-                    vararg,
-                    body,
-                    closure,
-                };
-                Ok(env.define_var(name, func))
+                }
+            }
+            [Value::Atom(atom), ..] if atom == "import" => {
+                imports.push(Value::List(decl.clone()));
+            }
+            [Value::Atom(atom), forms @ ..] if atom == "begin" => body.extend_from_slice(forms),
+            _ => return Err(bad_special_form("define-library", form)),
+        }
+    }
+    let closure = env.make_closure();
+    let ret = imports
+        .iter()
+        .try_for_each(|import| eval(env, import).map(|_| ()))
+        .and_then(|_| match body.is_empty() {
+            true => Ok(Value::Unspecified),
+            false => eval_body(env, &body).and_then(|tail| resolve(env, tail)),
+        })
+        .and_then(|_| env.define_library(name.to_string(), &exports));
+    env.load_closure(closure);
+    ret.map(|_| Value::Unspecified)
+}
+
+/// Binds the exports of each library named by an `import` form. Libraries
+/// named `(scheme ...)` are built in and always bound, so importing them
+/// does nothing.
+fn import(env: &mut Env, form: &Value, names: &[Value]) -> Result<Value> {
+    for name in names {
+        let Value::List(parts) = name else {
+            return Err(bad_special_form("import", form));
+        };
+        match &parts[..] {
+            [Value::Atom(first), ..] if first == "scheme" => {}
+            [_, ..] if parts.iter().all(|part| matches!(part, Value::Atom(_))) => {
+                env.import_library(&name.to_string())?
+            }
+            _ => return Err(bad_special_form("import", form)),
+        }
+    }
+    Ok(Value::Unspecified)
+}
+
+/// Binds `letrec*` variables in order, each initializer seeing the variables
+/// bound before it as well as the ones still to come.
+fn bind_letrec(env: &mut Env, form: &Value, bindings: &[Value]) -> Result<()> {
+    let bindings = bindings
+        .iter()
+        .map(|binding| match binding {
+            Value::List(binding) => match &binding[..] {
+                [Value::Atom(var), init] => Ok((*var, init)),
+                _ => Err(bad_special_form("letrec*", form)),
+            },
+            _ => Err(bad_special_form("letrec*", form)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (var, _) in &bindings {
+        env.define_var(var.to_string(), Value::Unspecified);
+    }
+    for (var, init) in bindings {
+        let val = eval(env, init)?;
+        env.set_var(var.as_str(), val)?;
+    }
+    Ok(())
+}
+
+/// Evaluates a `fluid-let` body with existing variables set to new values,
+/// putting the old values back afterwards whether or not the body fails.
+fn eval_fluid_let(
+    env: &mut Env,
+    form: &Value,
+    bindings: &[Value],
+    body: &[Value],
+) -> Result<Value> {
+    let bindings = bindings
+        .iter()
+        .map(|binding| match binding {
+            Value::List(binding) => match &binding[..] {
+                [Value::Atom(var), expr] => Ok((*var, eval(env, expr)?)),
+                _ => Err(bad_special_form("fluid-let", form)),
+            },
+            _ => Err(bad_special_form("fluid-let", form)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let saved = bindings
+        .iter()
+        .map(|(var, _)| Ok((*var, env.get_var(var.as_str())?.clone())))
+        .collect::<Result<Vec<_>>>()?;
+    for (var, val) in bindings {
+        env.set_var(var.as_str(), val)?;
+    }
+    let closure = env.make_closure();
+    let ret = eval_body(env, body).and_then(|tail| resolve(env, tail));
+    env.load_closure(closure);
+    for (var, old) in saved {
+        env.set_var(var.as_str(), old)?;
+    }
+    ret
+}
+
+/// Runs a `do` loop, stepping its variables until the test holds and then
+/// evaluating the result expressions.
+fn eval_do(
+    env: &mut Env,
+    form: &Value,
+    specs: &[Value],
+    exit: &[Value],
+    body: &[Value],
+) -> Result<Value> {
+    let specs = specs
+        .iter()
+        .map(|spec| match spec {
+            Value::List(spec) => match &spec[..] {
+                [Value::Atom(var), init] => Ok((*var, init, None)),
+                [Value::Atom(var), init, step] => Ok((*var, init, Some(step))),
+                _ => Err(bad_special_form("do", form)),
+            },
+            _ => Err(bad_special_form("do", form)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (test, results) = exit
+        .split_first()
+        .ok_or_else(|| bad_special_form("do", form))?;
+    let inits = specs
+        .iter()
+        .map(|(_, init, _)| eval(env, init))
+        .collect::<Result<Vec<_>>>()?;
+    for ((var, _, _), val) in specs.iter().zip(inits) {
+        env.define_var(var.to_string(), val);
+    }
+    loop {
+        if eval(env, test)?.is_truthy() {
+            let mut ret = Value::Unspecified;
+            for result in results {
+                ret = eval(env, result)?;
+            }
+            return Ok(ret);
+        }
+        for val in body {
+            eval(env, val)?;
+        }
+        let steps = specs
+            .iter()
+            .filter_map(|(var, _, step)| step.map(|step| (var, step)))
+            .map(|(var, step)| Ok((var, eval(env, step)?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (var, val) in steps {
+            env.set_var(var.as_str(), val)?;
+        }
+    }
+}
+
+/// Expands SRFI-26 `cut` into a `lambda` taking one parameter per `<>` slot,
+/// plus a rest parameter for a trailing `<...>`. `cute` evaluates the other
+/// expressions once, up front, and captures their values instead.
+fn eval_cut(env: &mut Env, form: &Value, parts: &[Value], evaluate_now: bool) -> Result<Value> {
+    let (parts, rest) = match parts.split_last() {
+        Some((Value::Atom(atom), init)) if atom == "<...>" => (init, true),
+        _ => (parts, false),
+    };
+    if parts.is_empty() {
+        return Err(bad_special_form(
+            if evaluate_now { "cute" } else { "cut" },
+            form,
+        ));
+    }
+    let mut params = Vec::new();
+    let mut call = Vec::new();
+    for part in parts {
+        match part {
+            Value::Atom(atom) if atom == "<>" => {
+                let param = Value::Atom(Symbol::new(&format!("<>{}", params.len())));
+                params.push(param.clone());
+                call.push(param);
+            }
+            part if evaluate_now => {
+                let val = eval(env, part)?;
+                call.push(Value::List(Rc::new([Value::Atom(QUOTE.into()), val])));
+            }
+            part => call.push(part.clone()),
+        }
+    }
+    let params = match rest {
+        true => {
+            let apply = Value::IOFunc(IOFunc::Apply);
+            call.insert(0, Value::List(Rc::new([Value::Atom(QUOTE.into()), apply])));
+            call.push(Value::Atom("<...>".into()));
+            Value::DottedList(params.into(), Rc::new(Value::Atom("<...>".into())))
+        }
+        false => Value::List(params.into()),
+    };
+    eval(env, &lambda(params, &[Value::List(call.into())]))
+}
+
+/// Splits a `define` in a body into the name it binds and the expression for
+/// its value, turning the procedure shorthand into a `lambda`.
+fn internal_define(form: &Value) -> Option<(Symbol, Value)> {
+    let Value::List(vals) = form else {
+        return None;
+    };
+    match &vals[..] {
+        [Value::Atom(atom), Value::Atom(name), expr] if atom == "define" => {
+            Some((*name, expr.clone()))
+        }
+        [Value::Atom(atom), Value::List(name_params), body @ ..]
+            if atom == "define" && !body.is_empty() =>
+        {
+            let (Value::Atom(name), params) = name_params.split_first()? else {
+                return None;
+            };
+            Some((*name, lambda(Value::List(params.into()), body)))
+        }
+        [Value::Atom(atom), Value::DottedList(name_params, vararg), body @ ..]
+            if atom == "define" && !body.is_empty() =>
+        {
+            let (Value::Atom(name), params) = name_params.split_first()? else {
+                return None;
+            };
+            let params = match params {
+                [] => vararg.as_ref().clone(),
+                _ => Value::DottedList(params.into(), vararg.clone()),
+            };
+            Some((*name, lambda(params, body)))
+        }
+        _ => None,
+    }
+}
+
+/// Prefixes the names defined at the top level of loaded forms, so files
+/// loaded with different prefixes can define the same names without
+/// clobbering each other. Every use of those names in the forms is renamed
+/// to match, except inside quoted data and where a local binding shadows
+/// the name.
+fn prefix_defines(forms: &[Value], prefix: &str) -> Vec<Value> {
+    let renames: HashMap<Symbol, Symbol> = forms
+        .iter()
+        .filter_map(internal_define)
+        .map(|(name, _)| (name, Symbol::new(&format!("{}{}", prefix, name))))
+        .collect();
+    forms.iter().map(|form| rename(form, &renames)).collect()
+}
+
+fn rename(val: &Value, renames: &HashMap<Symbol, Symbol>) -> Value {
+    if renames.is_empty() {
+        return val.clone();
+    }
+    match val {
+        Value::Atom(atom) => Value::Atom(*renames.get(atom).unwrap_or(atom)),
+        Value::List(vals) => match &vals[..] {
+            [Value::Atom(atom), _] if atom == QUOTE => val.clone(),
+            [Value::Atom(atom), datum] if atom == "quasiquote" => {
+                Value::List(Rc::new([vals[0].clone(), rename_quasi(datum, renames, 1)]))
+            }
+            [Value::Atom(atom), params, body @ ..] if atom == "lambda" => {
+                let inner = shadow(renames, param_names(params).chain(defined_names(body)));
+                let mut vals = vec![vals[0].clone(), rename_params(params, &inner)];
+                vals.extend(body.iter().map(|val| rename(val, &inner)));
+                Value::List(vals.into())
             }
-            [Value::Atom(atom), Value::DottedList(name_args, vararg), body @ ..]
-                if atom == "define" =>
+            [Value::Atom(atom), signature @ (Value::List(_) | Value::DottedList(_, _)), body @ ..]
+                if atom == "define" || atom == "define-memoized" =>
             {
-                let (name, args) = match &name_args[..] {
-                    [Value::Atom(name), args @ ..] => (name.clone(), args.to_vec()),
-                    _ => {
-                        return Err(Error::BadSpecialForm(
-                            "unrecognized special form".to_owned(),
-                            val.clone(),
-                        ));
+                let (name, params) = match signature {
+                    Value::List(vals) => (&vals[0], Value::List(vals[1..].into())),
+                    Value::DottedList(vals, tail) if vals.len() == 1 => {
+                        (&vals[0], (**tail).clone())
                     }
+                    Value::DottedList(vals, tail) => {
+                        (&vals[0], Value::DottedList(vals[1..].into(), tail.clone()))
+                    }
+                    _ => unreachable!(),
                 };
-                let closure = env.make_closure();
-                let params = args.into_iter().map(|arg| arg.to_string()).collect();
-                let vararg = Some(vararg.clone().to_string());
-                let body = body.to_vec();
-                let func = Value::Func {
-                    params,
-                    vararg,
-                    body,
-                    closure,
+                let inner = shadow(renames, param_names(&params).chain(defined_names(body)));
+                let signature = match rename_params(&params, &inner) {
+                    Value::List(params) => Value::List(
+                        std::iter::once(rename(name, renames))
+                            .chain(params.iter().cloned())
+                            .collect(),
+                    ),
+                    Value::DottedList(params, tail) => Value::DottedList(
+                        std::iter::once(rename(name, renames))
+                            .chain(params.iter().cloned())
+                            .collect(),
+                        tail,
+                    ),
+                    tail => Value::DottedList(Rc::new([rename(name, renames)]), Rc::new(tail)),
                 };
-                Ok(env.define_var(name, func))
+                let mut vals = vec![vals[0].clone(), signature];
+                vals.extend(body.iter().map(|val| rename(val, &inner)));
+                Value::List(vals.into())
             }
-            [Value::Atom(atom), Value::List(params), body @ ..] if atom == "lambda" => {
-                let closure = env.make_closure();
-                let params = params.iter().map(|param| param.to_string()).collect();
-                let vararg = None;
-                let body = body.to_vec();
-                Ok(Value::Func {
-                    params,
-                    vararg,
-                    body,
-                    closure,
-                })
+            [Value::Atom(atom), Value::List(bindings), body @ ..] if atom == "letrec*" => {
+                let names = bindings.iter().filter_map(|binding| match binding {
+                    Value::List(binding) => binding.first().and_then(bound_name),
+                    _ => None,
+                });
+                let inner = shadow(renames, names.chain(defined_names(body)));
+                let mut vals = vec![vals[0].clone(), rename_bindings(bindings, &inner, &inner)];
+                vals.extend(body.iter().map(|val| rename(val, &inner)));
+                Value::List(vals.into())
             }
-            [Value::Atom(atom), Value::DottedList(params, vararg), body @ ..]
-                if atom == "lambda" =>
-            {
-                let closure = env.make_closure();
-                let params = params.iter().map(|param| param.to_string()).collect();
-                let vararg = Some(vararg.clone().to_string());
-                let body = body.to_vec();
-                Ok(Value::Func {
-                    params,
-                    vararg,
-                    body,
-                    closure,
-                })
-            }
-            [Value::Atom(atom), Value::Atom(vararg), body @ ..] if atom == "lambda" => {
-                let closure = env.make_closure();
-                let params = Vec::new();
-                let vararg = Some(vararg.clone());
-                let body = body.to_vec();
-                Ok(Value::Func {
-                    params,
-                    vararg,
-                    body,
-                    closure,
-                })
-            }
-            [Value::Atom(atom), Value::String(path)] if atom == "load" => {
-                let vals = load(path)?;
-                let mut ret = None;
-                for val in vals {
-                    ret = Some(eval(env, &val)?);
-                }
-                ret.ok_or(Error::EmptyBody)
+            [Value::Atom(atom), Value::List(specs), rest @ ..] if atom == "do" => {
+                let names = specs.iter().filter_map(|spec| match spec {
+                    Value::List(spec) => spec.first().and_then(bound_name),
+                    _ => None,
+                });
+                let inner = shadow(renames, names);
+                let mut vals = vec![vals[0].clone(), rename_bindings(specs, renames, &inner)];
+                vals.extend(rest.iter().map(|val| rename(val, &inner)));
+                Value::List(vals.into())
             }
-            [func, args @ ..] => {
-                let func = eval(env, func)?;
-                let args = args
-                    .iter()
-                    .map(|arg| eval(env, arg))
-                    .collect::<Result<Vec<_>>>()?;
-                let closure = env.make_closure();
-                let ret = apply(env, &func, &args);
-                env.load_closure(closure);
-                ret
+            _ => Value::List(vals.iter().map(|val| rename(val, renames)).collect()),
+        },
+        Value::DottedList(vals, tail) => Value::DottedList(
+            vals.iter().map(|val| rename(val, renames)).collect(),
+            Rc::new(rename(tail, renames)),
+        ),
+        _ => val.clone(),
+    }
+}
+
+/// Renames inside a quasiquoted template, where only the unquoted parts at
+/// the outermost `depth` of nesting are expressions.
+fn rename_quasi(val: &Value, renames: &HashMap<Symbol, Symbol>, depth: usize) -> Value {
+    match val {
+        Value::List(vals) => match &vals[..] {
+            [Value::Atom(atom), expr] if atom == "unquote" || atom == "unquote-splicing" => {
+                let expr = match depth {
+                    1 => rename(expr, renames),
+                    _ => rename_quasi(expr, renames, depth - 1),
+                };
+                Value::List(Rc::new([vals[0].clone(), expr]))
+            }
+            [Value::Atom(atom), datum] if atom == "quasiquote" => Value::List(Rc::new([
+                vals[0].clone(),
+                rename_quasi(datum, renames, depth + 1),
+            ])),
+            _ => Value::List(
+                vals.iter()
+                    .map(|val| rename_quasi(val, renames, depth))
+                    .collect(),
+            ),
+        },
+        Value::DottedList(vals, tail) => Value::DottedList(
+            vals.iter()
+                .map(|val| rename_quasi(val, renames, depth))
+                .collect(),
+            Rc::new(rename_quasi(tail, renames, depth)),
+        ),
+        _ => val.clone(),
+    }
+}
+
+/// The renames that still apply where `names` are bound locally.
+fn shadow(
+    renames: &HashMap<Symbol, Symbol>,
+    names: impl Iterator<Item = Symbol>,
+) -> HashMap<Symbol, Symbol> {
+    let mut renames = renames.clone();
+    for name in names {
+        renames.remove(&name);
+    }
+    renames
+}
+
+/// The names a lambda list binds, including its optional and rest
+/// parameters.
+fn param_names(params: &Value) -> impl Iterator<Item = Symbol> + '_ {
+    let (params, rest): (&[Value], Option<&Value>) = match params {
+        Value::List(params) => (params, None),
+        Value::DottedList(params, rest) => (params, Some(rest)),
+        rest => (&[], Some(rest)),
+    };
+    params.iter().chain(rest).filter_map(|param| match param {
+        Value::List(optional) => optional.first().and_then(bound_name),
+        param => bound_name(param),
+    })
+}
+
+fn bound_name(val: &Value) -> Option<Symbol> {
+    match val {
+        Value::Atom(name) => Some(*name),
+        _ => None,
+    }
+}
+
+/// The names the internal definitions of a body bind.
+fn defined_names(body: &[Value]) -> impl Iterator<Item = Symbol> + '_ {
+    body.iter()
+        .filter_map(internal_define)
+        .map(|(name, _)| name)
+}
+
+/// Renames the defaults of a lambda list's optional parameters.
+fn rename_params(params: &Value, renames: &HashMap<Symbol, Symbol>) -> Value {
+    let rename_param = |param: &Value| match param {
+        Value::List(optional) => match &optional[..] {
+            [name, default] => Value::List(Rc::new([name.clone(), rename(default, renames)])),
+            _ => param.clone(),
+        },
+        _ => param.clone(),
+    };
+    match params {
+        Value::List(params) => Value::List(params.iter().map(rename_param).collect()),
+        Value::DottedList(params, rest) => {
+            Value::DottedList(params.iter().map(rename_param).collect(), rest.clone())
+        }
+        _ => params.clone(),
+    }
+}
+
+/// Renames the `(var init [step])` bindings of `letrec*` or `do`, whose
+/// inits see `renames` and whose steps see `inner`.
+fn rename_bindings(
+    bindings: &[Value],
+    renames: &HashMap<Symbol, Symbol>,
+    inner: &HashMap<Symbol, Symbol>,
+) -> Value {
+    let bindings = bindings.iter().map(|binding| match binding {
+        Value::List(binding) => match &binding[..] {
+            [var, init, steps @ ..] => {
+                let mut vals = vec![var.clone(), rename(init, renames)];
+                vals.extend(steps.iter().map(|step| rename(step, inner)));
+                Value::List(vals.into())
             }
+            _ => Value::List(binding.clone()),
+        },
+        _ => binding.clone(),
+    });
+    Value::List(bindings.collect())
+}
+
+/// Builds a procedure closing over the current scope for a `lambda` or
+/// `define` form, checking that its parameters are symbols and distinct.
+/// Parameters after `#!optional` are written `(name default)` and may be
+/// left out by callers.
+fn make_func(
+    env: &mut Env,
+    name: &str,
+    form: &Value,
+    params: &[Value],
+    vararg: Option<&Value>,
+    body: &[Value],
+) -> Result<Value> {
+    let mut seen: Vec<Symbol> = Vec::new();
+    let mut bind = |param: &Value| {
+        let Value::Atom(param) = param else {
+            return Err(Error::BadSpecialForm(
+                format!("{}: parameter is not a symbol: {}", name, param),
+                form.clone(),
+            ));
+        };
+        if seen.contains(param) {
+            return Err(Error::BadSpecialForm(
+                format!("{}: duplicate parameter: {}", name, param),
+                form.clone(),
+            ));
+        }
+        seen.push(*param);
+        Ok(param.to_string())
+    };
+    let optional = params
+        .iter()
+        .position(|param| matches!(param, Value::Atom(atom) if atom == "#!optional"));
+    let (required, optional) = match optional {
+        Some(i) => (&params[..i], &params[i + 1..]),
+        None => (params, &[][..]),
+    };
+    let params = required.iter().map(&mut bind).collect::<Result<_>>()?;
+    let optionals = optional
+        .iter()
+        .map(|param| match param {
+            Value::List(param) if param.len() == 2 => Ok((bind(&param[0])?, param[1].clone())),
             _ => Err(Error::BadSpecialForm(
-                "unrecognized special form".to_owned(),
-                val.clone(),
+                format!(
+                    "{}: expected (name default) for optional parameter: {}",
+                    name, param
+                ),
+                form.clone(),
             )),
+        })
+        .collect::<Result<_>>()?;
+    let vararg = vararg.map(bind).transpose()?;
+    Ok(Value::Func {
+        name: None,
+        params,
+        optionals,
+        vararg,
+        body: body.into(),
+        closure: env.make_closure(),
+    })
+}
+
+/// A call to a procedure as it appears in an error trace: the procedure's
+/// name, or the procedure itself if it has none, applied to the arguments.
+fn call_form(func: &Value, args: &[Value]) -> Value {
+    let head = match func {
+        Value::Func {
+            name: Some(name), ..
+        } => Value::Atom(*name),
+        func => func.clone(),
+    };
+    Value::List(std::iter::once(head).chain(args.iter().cloned()).collect())
+}
+
+/// Names a procedure after the variable it is first defined as, leaving
+/// other values and procedures that already have a name alone.
+fn named(val: Value, var: Symbol) -> Value {
+    match val {
+        Value::Func {
+            name: None,
+            params,
+            optionals,
+            vararg,
+            body,
+            closure,
+        } => Value::Func {
+            name: Some(var),
+            params,
+            optionals,
+            vararg,
+            body,
+            closure,
         },
-        _ => todo!(),
+        val => val,
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{eval::Env, parser::parse_expr, value::Value};
-
-    use super::Error;
+fn lambda(params: Value, body: &[Value]) -> Value {
+    let mut vals = vec![Value::Atom("lambda".into()), params];
+    vals.extend_from_slice(body);
+    Value::List(vals.into())
+}
 
-    #[test]
-    fn eval() {
-        let cases = vec![
-            ("'atom", Ok("atom")),
-            ("2", Ok("2")),
-            ("\"a string\"", Ok("\"a string\"")),
-            ("(+ 2 2)", Ok("4")),
-            ("(+ 2 (- 4 1))", Ok("5")),
-            ("(- (+ 4 6 3) 3 5 2)", Ok("3")),
-            ("(< 2 3)", Ok("#t")),
-            ("(> 2 3)", Ok("#f")),
-            ("(>= 3 3)", Ok("#t")),
-            ("(string=? \"test\" \"test\")", Ok("#t")),
+pub fn eval(env: &mut Env, val: &Value) -> Result<Value> {
+    match val {
+        Value::String(_) => Ok(val.clone()),
+        Value::Number(_) => Ok(val.clone()),
+        Value::Bool(_) => Ok(val.clone()),
+        Value::Char(_) => Ok(val.clone()),
+        Value::Bytevector(_) => Ok(val.clone()),
+        Value::Vector(_) => Ok(val.clone()),
+        Value::Atom(id) => env.get_var(id.as_str()).cloned(),
+        Value::List(vals) => match &vals[..] {
+            [Value::Atom(atom), val] if atom == QUOTE => Ok(val.clone()),
+            [Value::Atom(atom), expr] if atom == "delay" => {
+                let closure = env.make_closure();
+                let expr = expr.clone();
+                let state = PromiseState::Delayed { expr, closure };
+                Ok(Value::Promise(Rc::new(RefCell::new(state))))
+            }
+            [Value::Atom(atom), head, tail] if atom == "cons-stream" => {
+                let head = eval(env, head)?;
+                let closure = env.make_closure();
+                let expr = tail.clone();
+                let state = PromiseState::Delayed { expr, closure };
+                let tail = Value::Promise(Rc::new(RefCell::new(state)));
+                primitive::cons(&[head, tail])
+            }
+            [Value::Atom(atom), pred, conseq, alt] if atom == "if" => {
+                match eval(env, pred)?.is_truthy() {
+                    true => eval(env, conseq),
+                    false => eval(env, alt),
+                }
+            }
+            [Value::Atom(atom), pred, conseq] if atom == "if" => {
+                match eval(env, pred)?.is_truthy() {
+                    true => eval(env, conseq),
+                    false => Ok(Value::Unspecified),
+                }
+            }
+            [Value::Atom(atom), ..]
+                if ["and", "or", "when", "unless", "cond"].contains(&atom.as_str()) =>
+            {
+                let tail = eval_tail(env, val)?;
+                resolve(env, tail)
+            }
+            [Value::Atom(atom), Value::List(bindings), body @ ..]
+                if atom == "letrec*" && !body.is_empty() =>
+            {
+                let closure = env.make_closure();
+                let ret =
+                    bind_letrec(env, val, bindings).and_then(|_| match eval_body(env, body)? {
+                        Tail::Return(val) => Ok(val),
+                        Tail::Call(func, args) => apply(env, &func, &args),
+                    });
+                env.load_closure(closure);
+                ret
+            }
+            [Value::Atom(atom), Value::List(bindings), body @ ..]
+                if atom == "fluid-let" && !body.is_empty() =>
+            {
+                eval_fluid_let(env, val, bindings, body)
+            }
+            [Value::Atom(atom), Value::List(specs), Value::List(exit), body @ ..]
+                if atom == "do" =>
+            {
+                let closure = env.make_closure();
+                let ret = eval_do(env, val, specs, exit, body);
+                env.load_closure(closure);
+                ret
+            }
+            [Value::Atom(atom), Value::List(spec), body @ ..]
+                if atom == "guard" && !body.is_empty() =>
+            {
+                match &spec[..] {
+                    [Value::Atom(var), clauses @ ..] => eval_guard(env, val, *var, clauses, body),
+                    _ => Err(bad_special_form("guard", val)),
+                }
+            }
+            [Value::Atom(atom), name @ Value::List(_), decls @ ..] if atom == "define-library" => {
+                define_library(env, val, name, decls)
+            }
+            [Value::Atom(atom), names @ ..] if atom == "import" => import(env, val, names),
+            [Value::Atom(atom), parts @ ..] if atom == "cut" => eval_cut(env, val, parts, false),
+            [Value::Atom(atom), parts @ ..] if atom == "cute" => eval_cut(env, val, parts, true),
+            [Value::Atom(atom), form] if atom == "assert" => match eval(env, form)?.is_truthy() {
+                true => Ok(Value::Unspecified),
+                false => Err(Error::AssertionFailed(form.clone())),
+            },
+            [Value::Atom(atom), Value::Atom(var), form] if atom == "set!" => {
+                let val = eval(env, form)?;
+                env.set_var(var.as_str(), val)?;
+                Ok(Value::Unspecified)
+            }
+            [Value::Atom(atom), Value::Atom(var), form] if atom == "define" => {
+                let val = named(eval(env, form)?, *var);
+                env.define_checked(var.to_string(), val)
+            }
+            [Value::Atom(atom), Value::List(name_params), body @ ..]
+                if atom == "define" && !body.is_empty() =>
+            {
+                let [Value::Atom(name), params @ ..] = &name_params[..] else {
+                    return Err(bad_special_form("define", val));
+                };
+                let func = make_func(env, "define", val, params, None, body)?;
+                env.define_checked(name.to_string(), named(func, *name))
+            }
+            [Value::Atom(atom), Value::DottedList(name_params, vararg), body @ ..]
+                if atom == "define" && !body.is_empty() =>
+            {
+                let [Value::Atom(name), params @ ..] = &name_params[..] else {
+                    return Err(bad_special_form("define", val));
+                };
+                let func = make_func(env, "define", val, params, Some(vararg), body)?;
+                env.define_checked(name.to_string(), named(func, *name))
+            }
+            [Value::Atom(atom), head, body @ ..]
+                if atom == "define-memoized" && !body.is_empty() =>
+            {
+                define_memoized(env, val, head, body)
+            }
+            [Value::Atom(atom), Value::Atom(var), form] if atom == "define-constant" => {
+                define_constant(env, *var, form)
+            }
+            [Value::Atom(atom), Value::Atom(name), spec] if atom == "define-syntax" => {
+                define_syntax(env, val, *name, spec)
+            }
+            [Value::Atom(atom), Value::List(params), body @ ..]
+                if atom == "lambda" && !body.is_empty() =>
+            {
+                make_func(env, "lambda", val, params, None, body)
+            }
+            [Value::Atom(atom), Value::DottedList(params, vararg), body @ ..]
+                if atom == "lambda" && !body.is_empty() =>
+            {
+                make_func(env, "lambda", val, params, Some(vararg), body)
+            }
+            [Value::Atom(atom), vararg @ Value::Atom(_), body @ ..]
+                if atom == "lambda" && !body.is_empty() =>
+            {
+                make_func(env, "lambda", val, &[], Some(vararg), body)
+            }
+            [Value::Atom(atom), Value::String(path), prefix @ ..]
+                if atom == "load" && matches!(prefix, [] | [Value::String(_)]) =>
+            {
+                let path = env.find_load_path(path)?;
+                let mut vals = load(&path)?;
+                if let [Value::String(prefix)] = prefix {
+                    vals = prefix_defines(&vals, prefix);
+                }
+                env.begin_load(&path);
+                let ret = vals
+                    .iter()
+                    .map(|val| eval(env, val))
+                    .try_fold(None, |_, val| val.map(Some));
+                env.end_load();
+                ret?.ok_or(Error::EmptyBody)
+            }
+            [Value::Atom(atom), ..] if is_special_form(atom.as_str()) => {
+                Err(bad_special_form(atom.as_str(), val))
+            }
+            [Value::Atom(atom), ..] if env.is_macro(atom.as_str()) => {
+                let tail = eval_tail(env, val)?;
+                resolve(env, tail)
+            }
+            [func, args @ ..] => {
+                let func = eval(env, func)?;
+                let args = args
+                    .iter()
+                    .map(|arg| eval(env, arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let closure = env.make_closure();
+                let ret = apply(env, &func, &args);
+                env.load_closure(closure);
+                ret
+            }
+            _ => Err(Error::BadSpecialForm(
+                "unrecognized special form".to_owned(),
+                val.clone(),
+            )),
+        },
+        _ => todo!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        eval::Env,
+        number::Number,
+        parser::{parse_expr, parse_exprs},
+        value::{PrimitiveFunc, Value},
+    };
+
+    use std::rc::Rc;
+
+    use super::{Arity, Error};
+
+    fn check(env: &mut Env, cases: Vec<(&str, Result<&str, Error>)>) {
+        for (input, expected) in cases {
+            let val = parse_expr(input).unwrap();
+            let actual = super::eval(env, &val).map(|val| val.to_string());
+            let expected = expected.map(|str| str.to_owned());
+            assert_eq!(expected, actual, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval() {
+        let cases = vec![
+            ("'atom", Ok("atom")),
+            ("2", Ok("2")),
+            ("\"a string\"", Ok("\"a string\"")),
+            ("(+ 2 2)", Ok("4")),
+            ("(+ 2 (- 4 1))", Ok("5")),
+            ("(- (+ 4 6 3) 3 5 2)", Ok("3")),
+            ("(+)", Ok("0")),
+            ("(*)", Ok("1")),
+            ("(+ 5)", Ok("5")),
+            ("(* 5)", Ok("5")),
+            ("(- 5)", Ok("-5")),
+            ("(- -1/2)", Ok("1/2")),
+            ("(/ 2)", Ok("1/2")),
+            ("(/ -3/4)", Ok("-4/3")),
+            ("(/ 1/5)", Ok("5")),
+            ("(/ 0)", Err(Error::DivByZero)),
+            ("(/)", Err(Error::NumArgs(2, vec![]))),
+            ("(1+ 5)", Ok("6")),
+            ("(1- 5)", Ok("4")),
+            ("(1+ -1/2)", Ok("1/2")),
+            ("(map 1+ '(1 2 3))", Ok("(2 3 4)")),
+            (
+                "(1+ \"5\")",
+                Err(Error::TypeMismatch(
+                    "number".to_owned(),
+                    Value::String("5".to_owned()),
+                )),
+            ),
+            (
+                "(1- 1 2)",
+                Err(Error::NumArgs(
+                    1,
+                    vec![Value::Number(1.into()), Value::Number(2.into())],
+                )),
+            ),
+            ("(boolean 0)", Ok("#t")),
+            ("(boolean '())", Ok("#t")),
+            ("(boolean #f)", Ok("#f")),
+            ("(apply + '())", Ok("0")),
+            ("(apply * '(2 3 4))", Ok("24")),
+            ("(-)", Err(Error::NumArgs(2, vec![]))),
+            (
+                "(+ 'a)",
+                Err(Error::TypeMismatch(
+                    "number".to_owned(),
+                    Value::Atom("a".into()),
+                )),
+            ),
+            ("(< 2 3)", Ok("#t")),
+            ("(> 2 3)", Ok("#f")),
+            ("(>= 3 3)", Ok("#t")),
+            ("(string=? \"test\" \"test\")", Ok("#t")),
             ("(string<? \"abc\" \"bba\")", Ok("#t")),
             ("(if (> 2 3) \"no\" \"yes\")", Ok("\"yes\"")),
             ("(if (= 3 3) (+ 2 3 (- 5 1)) \"unequal\")", Ok("9")),
             ("(cdr '(a simple test))", Ok("(simple test)")),
             ("(car (cdr '(a simple test)))", Ok("simple")),
+            ("(cdr '(a . b))", Ok("b")),
+            ("(cdr '(a b . c))", Ok("(b . c)")),
             ("(car '((this is) a test))", Ok("(this is)")),
             ("(cons '(this is) 'test)", Ok("((this is) . test)")),
             ("(cons '(this is) '())", Ok("((this is))")),
@@ -263,17 +1451,30 @@ mod tests {
             ("(f 1 2)", Ok("3")),
             (
                 "(f 1 2 3)",
-                Err(Error::NumArgs(
-                    2,
-                    vec![Value::Number(1), Value::Number(2), Value::Number(3)],
+                Err(Error::ProcedureNumArgs(
+                    Some("f".to_owned()),
+                    Arity::Exactly(2),
+                    vec![
+                        Value::Number(1.into()),
+                        Value::Number(2.into()),
+                        Value::Number(3.into()),
+                    ],
+                )),
+            ),
+            (
+                "(f 1)",
+                Err(Error::ProcedureNumArgs(
+                    Some("f".to_owned()),
+                    Arity::Exactly(2),
+                    vec![Value::Number(1.into())],
                 )),
             ),
-            ("(f 1)", Err(Error::NumArgs(2, vec![Value::Number(1)]))),
             (
                 "(define (factorial x) (if (= x 1) 1 (* x (factorial (- x 1)))))",
                 Ok("(lambda (x) ...)"),
             ),
             ("(factorial 10)", Ok("3628800")),
+            ("(factorial 30)", Ok("265252859812191058636308480000000")),
             (
                 "(define (counter inc) (lambda (x) (set! inc (+ x inc)) inc))",
                 Ok("(lambda (inc) ...)"),
@@ -284,16 +1485,2628 @@ mod tests {
             ("(my-count 5)", Ok("19")),
             // For some reason, for me it's not a DottedList
             // ("(load \"stdlib.scm\")", Ok("(lambda (pred . lst) ...)")),
-            ("(load \"stdlib.scm\")", Ok("(lambda (pred lst) ...)")),
+            ("(load \"stdlib.scm\")", Ok("(lambda (obj lst) ...)")),
             ("(map (curry + 2) '(1 2 3 4))", Ok("(3 4 5 6)")),
             ("(filter even? '(1 2 3 4))", Ok("(2 4)")),
         ];
         let mut env = Env::primitive_bindings();
-        for (input, expected) in cases {
-            let val = parse_expr(input).unwrap();
-            let actual = super::eval(&mut env, &val).map(|val| val.to_string());
-            let expected = expected.map(|str| str.to_owned());
-            assert_eq!(expected, actual,);
-        }
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn read_port() {
+        let cases = vec![
+            (
+                "(define p (open-input-string \"(a (b c)) 'd\"))",
+                Ok("<IO port>"),
+            ),
+            ("(read p)", Ok("(a (b c))")),
+            ("(read p)", Ok("'d")),
+            ("(read p)", Ok("#<eof>")),
+            (
+                "(define f (open-input-file \"stdlib.scm\"))",
+                Ok("<IO port>"),
+            ),
+            (
+                "(read f)",
+                Ok("(define (null? obj) (if (eqv? obj '()) #t #f))"),
+            ),
+            ("(read f)", Ok("(define (list . objs) objs)")),
+            (
+                "(read 5)",
+                Err(Error::TypeMismatch(
+                    "port".to_owned(),
+                    Value::Number(5.into()),
+                )),
+            ),
+            (
+                "(read p f)",
+                Err(Error::NumArgs(1, vec![Value::Port(0), Value::Port(1)])),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn read_quote_shorthand() {
+        let cases = vec![
+            (
+                "(define p (open-input-string \"'(a b) `(1 ,x) `(0 ,@xs)\"))",
+                Ok("<IO port>"),
+            ),
+            ("(define quoted (read p))", Ok("'(a b)")),
+            ("(car quoted)", Ok("quote")),
+            ("(cdr quoted)", Ok("((a b))")),
+            ("(define quasi (read p))", Ok("`(1 ,x)")),
+            ("(car quasi)", Ok("quasiquote")),
+            ("(car (car (cdr (car (cdr quasi)))))", Ok("unquote")),
+            ("(cdr (car (cdr (car (cdr quasi)))))", Ok("(x)")),
+            ("(define spliced (read p))", Ok("`(0 ,@xs)")),
+            (
+                "(car (car (cdr (car (cdr spliced)))))",
+                Ok("unquote-splicing"),
+            ),
+            ("(read p)", Ok("#<eof>")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn format() {
+        let path = std::env::temp_dir().join("scheme-rs-format.scm");
+        let path = path.to_str().unwrap();
+        let write_file = format!(
+            "(with-output-to-file \"{}\" (lambda () (format #t \"(~a ~a)\" 'x 1)))",
+            path
+        );
+        let read_contents = format!("(read-contents \"{}\")", path);
+        let cases = vec![
+            ("(format #f \"~a + ~a = ~a\" 1 2 3)", Ok("\"1 + 2 = 3\"")),
+            (
+                "(format \"~a and ~s\" \"x\" \"y\")",
+                Ok("\"x and \\\"y\\\"\""),
+            ),
+            ("(format #f \"~a~%~~\" '(1 \"two\"))", Ok("\"(1 two)\\n~\"")),
+            (
+                "(format #f \"~a ~a\" 1)",
+                Err(Error::Format("not enough arguments".to_owned())),
+            ),
+            (
+                "(format #f \"~x\" 1)",
+                Err(Error::Format("unknown directive ~x".to_owned())),
+            ),
+            (
+                "(format 1 \"~a\" 2)",
+                Err(Error::TypeMismatch(
+                    "port or boolean".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(format #t 1)",
+                Err(Error::TypeMismatch(
+                    "string".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (write_file.as_str(), Ok("#t")),
+            (read_contents.as_str(), Ok("\"(x 1)\"")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn bound() {
+        let cases = vec![
+            ("(bound? 'car)", Ok("#t")),
+            ("(bound? 'nope)", Ok("#f")),
+            ("(define nope 1)", Ok("1")),
+            ("(bound? 'nope)", Ok("#t")),
+            (
+                "(bound? \"car\")",
+                Err(Error::TypeMismatch(
+                    "symbol".to_owned(),
+                    Value::String("car".to_owned()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn apply() {
+        let cases = vec![
+            ("(apply + '(1 2 3))", Ok("6")),
+            ("(apply + 1 2 '(3 4))", Ok("10")),
+            ("(apply car '((1 2 3)))", Ok("1")),
+            ("(apply format '(#f \"~a-~a\" 1 2))", Ok("\"1-2\"")),
+            ("(define (f x y) (- x y))", Ok("(lambda (x y) ...)")),
+            ("(apply f '(5 3))", Ok("2")),
+            ("(apply f 5 '(3))", Ok("2")),
+            (
+                "(apply f 5 3)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::Number(3.into()),
+                )),
+            ),
+            (
+                "(apply car)",
+                Err(Error::NumArgs(
+                    2,
+                    vec![Value::PrimitiveFunc(PrimitiveFunc::Car)],
+                )),
+            ),
+            (
+                "(apply 1 '(2 3))",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn values() {
+        let cases = vec![
+            ("(values 1 2)", Ok("1 2")),
+            ("(values 1)", Ok("1")),
+            ("(call-with-values (lambda () (values 1 2)) +)", Ok("3")),
+            (
+                "(call-with-values (lambda () 5) (lambda (x) (* x x)))",
+                Ok("25"),
+            ),
+            (
+                "(call-with-values (lambda () (values 1)) (lambda (x) x))",
+                Ok("1"),
+            ),
+            (
+                "(call-with-values (lambda () (values 1 2 3)) (lambda (a b) a))",
+                Err(Error::ProcedureNumArgs(
+                    None,
+                    Arity::Exactly(2),
+                    vec![
+                        Value::Number(1.into()),
+                        Value::Number(2.into()),
+                        Value::Number(3.into()),
+                    ],
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn delay_force() {
+        let cases = vec![
+            ("(define count 0)", Ok("0")),
+            (
+                "(define p (delay ((lambda () (set! count (+ count 1)) (* count 10)))))",
+                Ok("#<promise>"),
+            ),
+            ("count", Ok("0")),
+            ("(force p)", Ok("10")),
+            ("(force p)", Ok("10")),
+            ("count", Ok("1")),
+            ("(force 5)", Ok("5")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn streams() {
+        let cases = vec![
+            (
+                "(define (integers-from n) (cons-stream n (integers-from (+ n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            (
+                "(define naturals (integers-from 1))",
+                Ok("(1 . #<promise>)"),
+            ),
+            ("(stream-car naturals)", Ok("1")),
+            ("(stream-car (stream-cdr naturals))", Ok("2")),
+            ("(stream-ref naturals 4)", Ok("5")),
+            ("(stream-ref naturals 0)", Ok("1")),
+            (
+                "(stream-ref naturals -1)",
+                Err(Error::TypeMismatch(
+                    "index".to_owned(),
+                    Value::Number((-1).into()),
+                )),
+            ),
+            (
+                "(stream-ref naturals 'x)",
+                Err(Error::TypeMismatch(
+                    "index".to_owned(),
+                    Value::Atom("x".into()),
+                )),
+            ),
+            (
+                "(stream-cdr 1)",
+                Err(Error::TypeMismatch(
+                    "stream".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn chars() {
+        let cases = vec![
+            ("#\\a", Ok("#\\a")),
+            ("#\\space", Ok("#\\space")),
+            ("(char->integer #\\a)", Ok("97")),
+            ("(integer->char 97)", Ok("#\\a")),
+            ("(integer->char 10)", Ok("#\\newline")),
+            ("(integer->char 1)", Ok("#\\x1")),
+            ("(char->integer #\\λ)", Ok("955")),
+            ("(integer->char 955)", Ok("#\\λ")),
+            ("(integer->char (char->integer #\\x1f600))", Ok("#\\😀")),
+            ("(eqv? #\\a (integer->char 97))", Ok("#t")),
+            (
+                "(integer->char 55296)",
+                Err(Error::TypeMismatch(
+                    "unicode scalar value".to_owned(),
+                    Value::Number(55296.into()),
+                )),
+            ),
+            (
+                "(integer->char 1114112)",
+                Err(Error::TypeMismatch(
+                    "unicode scalar value".to_owned(),
+                    Value::Number(1114112.into()),
+                )),
+            ),
+            (
+                "(char->integer 97)",
+                Err(Error::TypeMismatch(
+                    "char".to_owned(),
+                    Value::Number(97.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn rationals() {
+        let cases = vec![
+            ("(/ 1 3)", Ok("1/3")),
+            ("(/ 2 4)", Ok("1/2")),
+            ("(/ 4 2)", Ok("2")),
+            ("6/10", Ok("3/5")),
+            ("(+ 1/2 1/3)", Ok("5/6")),
+            ("(+ 1/2 1/2)", Ok("1")),
+            ("(* 2 1/4)", Ok("1/2")),
+            ("(- 1 1/3)", Ok("2/3")),
+            ("(< 1/3 1/2)", Ok("#t")),
+            ("(= 2/4 1/2)", Ok("#t")),
+            ("(quotient 7 2)", Ok("3")),
+            ("(remainder 7 2)", Ok("1")),
+            ("(modulo 7 3)", Ok("1")),
+            ("(modulo -7 3)", Ok("2")),
+            ("(remainder -7 3)", Ok("-1")),
+            ("(modulo 14/2 3)", Ok("1")),
+            (
+                "(modulo 15/2 3)",
+                Err(Error::TypeMismatch(
+                    "integer".to_owned(),
+                    Value::Number(Number::rational(15.into(), 2.into()).unwrap()),
+                )),
+            ),
+            ("(/ 1 0)", Err(Error::DivByZero)),
+            ("(quotient 1 0)", Err(Error::DivByZero)),
+            (
+                "(remainder 1/2 2)",
+                Err(Error::TypeMismatch(
+                    "integer".to_owned(),
+                    Value::Number(Number::rational(1.into(), 2.into()).unwrap()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn tail_calls() {
+        let cases = vec![
+            (
+                "(define (my-even? n) (if (= n 0) #t (my-odd? (- n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            (
+                "(define (my-odd? n) (if (= n 0) #f (my-even? (- n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(my-even? 100000)", Ok("#t")),
+            ("(my-odd? 7)", Ok("#t")),
+            (
+                "(define (count-down n acc) (if (= n 0) acc (count-down (- n 1) (+ acc 1))))",
+                Ok("(lambda (n acc) ...)"),
+            ),
+            ("(count-down 10000 0)", Ok("10000")),
+            ("(define (last-car l) (car l))", Ok("(lambda (l) ...)")),
+            ("(last-car '(1 2))", Ok("1")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let cases = vec![
+            ("(char-upcase #\\a)", Ok("#\\A")),
+            ("(char-downcase #\\A)", Ok("#\\a")),
+            ("(char-upcase #\\1)", Ok("#\\1")),
+            ("(char-upcase #\\λ)", Ok("#\\Λ")),
+            ("(char-upcase #\\ß)", Ok("#\\ß")),
+            ("(string-ci=? \"ABC\" \"abc\")", Ok("#t")),
+            ("(string=? \"ABC\" \"abc\")", Ok("#f")),
+            ("(string-ci<? \"apple\" \"BANANA\")", Ok("#t")),
+            ("(string<? \"apple\" \"BANANA\")", Ok("#f")),
+            ("(string-ci>=? \"b\" \"A\")", Ok("#t")),
+            (
+                "(char-upcase \"a\")",
+                Err(Error::TypeMismatch(
+                    "char".to_owned(),
+                    Value::String("a".to_owned()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn assert() {
+        let cases = vec![
+            ("(assert (= 1 1))", Ok("#<unspecified>")),
+            ("(assert 0)", Ok("#<unspecified>")),
+            (
+                "(assert (= 1 2))",
+                Err(Error::AssertionFailed(parse_expr("(= 1 2)").unwrap())),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        let err = super::eval(&mut env, &parse_expr("(assert (= 1 2))").unwrap()).unwrap_err();
+        assert_eq!("Assertion failed: (= 1 2)", err.to_string());
+    }
+
+    #[test]
+    fn strings_from_chars() {
+        let cases = vec![
+            ("(string #\\h #\\i)", Ok("\"hi\"")),
+            ("(string)", Ok("\"\"")),
+            ("(list->string '(#\\o #\\k))", Ok("\"ok\"")),
+            ("(list->string '())", Ok("\"\"")),
+            (
+                "(string #\\a 1)",
+                Err(Error::TypeMismatch(
+                    "char".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(list->string '(#\\a 2))",
+                Err(Error::TypeMismatch(
+                    "char".to_owned(),
+                    Value::Number(2.into()),
+                )),
+            ),
+            (
+                "(list->string \"ab\")",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::String("ab".to_owned()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn string_trim() {
+        let cases = vec![
+            ("(string-trim \"  hi there \")", Ok("\"hi there\"")),
+            ("(string-trim-left \"  hi \")", Ok("\"hi \"")),
+            ("(string-trim-right \"  hi \")", Ok("\"  hi\"")),
+            ("(string-trim \"   \")", Ok("\"\"")),
+            ("(string-trim \"xyhixyx\" \"xy\")", Ok("\"hi\"")),
+            ("(string-trim-left \"xyhixy\" \"xy\")", Ok("\"hixy\"")),
+            ("(string-trim-right \"xyhixy\" \"yx\")", Ok("\"xyhi\"")),
+            (
+                "(string-trim 'hi)",
+                Err(Error::TypeMismatch(
+                    "string".to_owned(),
+                    Value::Atom("hi".into()),
+                )),
+            ),
+            (
+                "(string-trim \"hi\" #\\h)",
+                Err(Error::TypeMismatch("string".to_owned(), Value::Char('h'))),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn vectors() {
+        let cases = vec![
+            ("#(1 \"a\" #\\b)", Ok("#(1 \"a\" #\\b)")),
+            ("(string->vector \"hi\")", Ok("#(#\\h #\\i)")),
+            ("(vector->string (string->vector \"hi\"))", Ok("\"hi\"")),
+            ("(vector->string #())", Ok("\"\"")),
+            ("(equal? #(1 2) #(1 2))", Ok("#t")),
+            ("(vector-append #(1 2) #(3) #())", Ok("#(1 2 3)")),
+            ("(vector-append)", Ok("#()")),
+            ("(vector-copy #(a b c d) 1 3)", Ok("#(b c)")),
+            ("(subvector #(a b c d) 1 3)", Ok("#(b c)")),
+            ("(vector-copy #(a b c d) 2)", Ok("#(c d)")),
+            ("(vector-copy #(a b))", Ok("#(a b)")),
+            ("(vector-copy #(a b) 2 2)", Ok("#()")),
+            (
+                "(vector-copy #(a b) 1 3)",
+                Err(Error::OutOfRange(3, parse_expr("#(a b)").unwrap())),
+            ),
+            (
+                "(subvector #(a b) 2 1)",
+                Err(Error::OutOfRange(2, parse_expr("#(a b)").unwrap())),
+            ),
+            (
+                "(vector-append #(1) '(2))",
+                Err(Error::TypeMismatch(
+                    "vector".to_owned(),
+                    parse_expr("(2)").unwrap(),
+                )),
+            ),
+            (
+                "(vector->string #(#\\a 1))",
+                Err(Error::TypeMismatch(
+                    "char".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(string->vector 'hi)",
+                Err(Error::TypeMismatch(
+                    "string".to_owned(),
+                    Value::Atom("hi".into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn bytevectors() {
+        let cases = vec![
+            ("#u8(1 2 3)", Ok("#u8(1 2 3)")),
+            ("(bytevector 1 2 3)", Ok("#u8(1 2 3)")),
+            ("(bytevector)", Ok("#u8()")),
+            ("(make-bytevector 3 7)", Ok("#u8(7 7 7)")),
+            ("(make-bytevector 2)", Ok("#u8(0 0)")),
+            ("(define bv (bytevector 1 2 3))", Ok("#u8(1 2 3)")),
+            ("(bytevector-length bv)", Ok("3")),
+            ("(bytevector-u8-ref bv 1)", Ok("2")),
+            ("(bytevector-u8-set! bv 1 255)", Ok("#t")),
+            ("bv", Ok("#u8(1 255 3)")),
+            ("(equal? bv #u8(1 255 3))", Ok("#t")),
+            ("(eqv? bv #u8(1 255 3))", Ok("#f")),
+            ("(eqv? bv bv)", Ok("#t")),
+            (
+                "(bytevector-u8-ref bv 3)",
+                Err(Error::OutOfRange(
+                    3,
+                    Value::Bytevector(Rc::new(vec![1, 255, 3].into())),
+                )),
+            ),
+            (
+                "(bytevector-u8-set! bv 0 256)",
+                Err(Error::TypeMismatch(
+                    "byte".to_owned(),
+                    Value::Number(256.into()),
+                )),
+            ),
+            (
+                "(bytevector 1 (- 0 1))",
+                Err(Error::TypeMismatch(
+                    "byte".to_owned(),
+                    Value::Number((-1).into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn binary_ports() {
+        let path = std::env::temp_dir().join("scheme-rs-binary-ports.bin");
+        let path = path.to_str().unwrap();
+        let open_output = format!("(define out (open-binary-output-file \"{}\"))", path);
+        let open_input = format!("(define in (open-binary-input-file \"{}\"))", path);
+        let cases = vec![
+            (open_output.as_str(), Ok("<IO port>")),
+            ("(write-u8 1 out)", Ok("#t")),
+            ("(write-bytevector #u8(2 3 255) out)", Ok("#t")),
+            ("(close-output-port out)", Ok("#t")),
+            (open_input.as_str(), Ok("<IO port>")),
+            ("(read-u8 in)", Ok("1")),
+            ("(read-bytevector 2 in)", Ok("#u8(2 3)")),
+            ("(read-bytevector 5 in)", Ok("#u8(255)")),
+            ("(read-u8 in)", Ok("#<eof>")),
+            ("(read-bytevector 1 in)", Ok("#<eof>")),
+            (
+                "(read in)",
+                Err(Error::Port(
+                    "Port was not opened, was closed or is not a read port".to_owned(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn call_with_file() {
+        let path = std::env::temp_dir().join("scheme-rs-call-with-file.scm");
+        let path = path.to_str().unwrap();
+        let write_file = format!(
+            "(call-with-output-file \"{}\" (lambda (port) (set! p port) (write 'hello port)))",
+            path
+        );
+        let read_file = format!("(call-with-input-file \"{}\" read)", path);
+        let read_error = format!(
+            "(call-with-input-file \"{}\" (lambda (port) (set! p port) (car '())))",
+            path
+        );
+        let cases = vec![
+            ("(define p #f)", Ok("#f")),
+            (write_file.as_str(), Ok("#t")),
+            (
+                "(write 'again p)",
+                Err(Error::Port(
+                    "Port was not opened, was closed or is not a write port".to_owned(),
+                )),
+            ),
+            (read_file.as_str(), Ok("hello")),
+            (
+                read_error.as_str(),
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::List(Rc::new([])),
+                )),
+            ),
+            (
+                "(read p)",
+                Err(Error::Port(
+                    "Port was not opened, was closed or is not a read port".to_owned(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn equal_hash() {
+        let cases = vec![
+            (
+                "(= (equal-hash '(1 \"two\" (3 . x))) (equal-hash (cons 1 (cons \"two\" (cons (cons 3 'x) '())))))",
+                Ok("#t"),
+            ),
+            ("(= (eqv-hash 'abc) (eqv-hash 'abc))", Ok("#t")),
+            ("(= (equal-hash 1/2) (equal-hash 2/4))", Ok("#t")),
+            ("(= (equal-hash '(1 2)) (equal-hash '(2 1)))", Ok("#f")),
+            ("(= (equal-hash \"a\") (equal-hash 'a))", Ok("#f")),
+            (
+                "(equal-hash car)",
+                Err(Error::TypeMismatch(
+                    "hashable value".to_owned(),
+                    Value::PrimitiveFunc(PrimitiveFunc::Car),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn describe() {
+        let path = std::env::temp_dir().join("scheme-rs-describe.txt");
+        let path = path.to_str().unwrap();
+        let describe_all = format!(
+            "(with-output-to-file \"{}\" (lambda () (describe (lambda (x y) x)) (describe add) (describe (lambda (x #!optional (y 1)) x)) (describe (lambda args args)) (describe '(1 2 3)) (describe '()) (describe \"héllo\") (describe car) (describe 42) (describe (open-input-string \"\")) (describe #(1)) (describe #(1 2)) (describe one) (describe two)))",
+            path
+        );
+        let read_contents = format!("(read-contents \"{}\")", path);
+        let cases = vec![
+            ("(define (add a b) (+ a b))", Ok("(lambda (a b) ...)")),
+            ("(define one (make-hash-table))", Ok("#<hash-table>")),
+            ("(hash-set! one 'a 1)", Ok("#t")),
+            ("(define two (make-hash-table))", Ok("#<hash-table>")),
+            ("(hash-set! two 'a 1)", Ok("#t")),
+            ("(hash-set! two 'b 2)", Ok("#t")),
+            (describe_all.as_str(), Ok("#<unspecified>")),
+            (
+                read_contents.as_str(),
+                Ok("\"procedure (x y), takes 2 args\\n\
+                    procedure add (a b), takes 2 args\\n\
+                    procedure (x #!optional (y 1)), takes 1 to 2 args\\n\
+                    procedure args, takes at least 0 args\\n\
+                    list of 3 elements\\n\
+                    empty list\\n\
+                    string of 5 chars\\n\
+                    primitive procedure\\n\
+                    number 42\\n\
+                    input port, open\\n\
+                    vector of 1 element\\n\
+                    vector of 2 elements\\n\
+                    hash table of 1 entry\\n\
+                    hash table of 2 entries\\n\""),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn current_ports() {
+        let path = std::env::temp_dir().join("scheme-rs-current-ports.scm");
+        let path = path.to_str().unwrap();
+        let write_file = format!(
+            "(with-output-to-file \"{}\" (lambda () (display \"(a\") (write 'b) (display #\\))))",
+            path
+        );
+        let read_contents = format!("(read-contents \"{}\")", path);
+        let read_file = format!("(with-input-from-file \"{}\" read)", path);
+        let cases = vec![
+            (write_file.as_str(), Ok("#t")),
+            (read_contents.as_str(), Ok("\"(ab)\"")),
+            (read_file.as_str(), Ok("(ab)")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        assert_eq!(None, env.current_input_port());
+        assert_eq!(None, env.current_output_port());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_shared() {
+        let path = std::env::temp_dir().join("scheme-rs-write-shared.scm");
+        let path = path.to_str().unwrap();
+        let write_file = format!(
+            "(with-output-to-file \"{}\" (lambda () (write-shared shared) (write-simple shared)))",
+            path
+        );
+        let read_contents = format!("(read-contents \"{}\")", path);
+        let cases = vec![
+            ("(define v #(a))", Ok("#(a)")),
+            ("(define l '(b))", Ok("(b)")),
+            (
+                "(define shared (cons v (cons l (cons v (cons l '())))))",
+                Ok("(#(a) (b) #(a) (b))"),
+            ),
+            (write_file.as_str(), Ok("#t")),
+            (
+                read_contents.as_str(),
+                Ok("\"(#0=#(a) #1=(b) #0# #1#)(#(a) (b) #(a) (b))\""),
+            ),
+            (
+                "(write-shared v 1)",
+                Err(Error::TypeMismatch(
+                    "port".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            ("(write-shared)", Err(Error::NumArgs(1, vec![]))),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn process_environment() {
+        std::env::set_var("SCHEME_RS_TEST_VAR", "hello");
+        let cases = vec![
+            (
+                "(get-environment-variable \"SCHEME_RS_TEST_VAR\")",
+                Ok("\"hello\""),
+            ),
+            (
+                "(get-environment-variable \"SCHEME_RS_UNSET_VAR\")",
+                Ok("#f"),
+            ),
+            (
+                "(get-environment-variable 'home)",
+                Err(Error::TypeMismatch(
+                    "string".to_owned(),
+                    Value::Atom("home".into()),
+                )),
+            ),
+            ("(command-line)", Ok("(\"scheme-rs\" \"script.scm\")")),
+        ];
+        let mut env = Env::primitive_bindings();
+        env.set_command_line(vec!["scheme-rs".to_owned(), "script.scm".to_owned()]);
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn symbols() {
+        let cases = vec![
+            ("(eqv? 'lambda 'lambda)", Ok("#t")),
+            ("(eqv? 'lambda 'define)", Ok("#f")),
+            ("(eqv? (car '(x y)) 'x)", Ok("#t")),
+            ("(eqv? (car (cons 'a '())) 'a)", Ok("#t")),
+            ("(define sym 'hello)", Ok("hello")),
+            ("(eqv? sym 'hello)", Ok("#t")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn shared_structure() {
+        let items: Vec<String> = (0..10000).map(|i| i.to_string()).collect();
+        let define = parse_expr(&format!("(define big '({}))", items.join(" "))).unwrap();
+        let literal = match &define {
+            Value::List(vals) => match &vals[2] {
+                Value::List(quote) => quote[1].clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let mut env = Env::primitive_bindings();
+        super::eval(&mut env, &define).unwrap();
+        let big = super::eval(&mut env, &parse_expr("big").unwrap()).unwrap();
+        let id = super::eval(&mut env, &parse_expr("((lambda (x) x) big)").unwrap()).unwrap();
+        match (&literal, &big, &id) {
+            (Value::List(literal), Value::List(big), Value::List(id)) => {
+                assert!(Rc::ptr_eq(literal, big));
+                assert!(Rc::ptr_eq(literal, id));
+            }
+            _ => panic!("expected lists"),
+        }
+    }
+
+    #[test]
+    fn malformed_special_forms() {
+        let form = |input| parse_expr(input).unwrap();
+        let cases = vec![
+            (
+                "(if #t)",
+                Err(Error::BadSpecialForm(
+                    "if: expected (if test conseq [alt])".to_owned(),
+                    form("(if #t)"),
+                )),
+            ),
+            (
+                "(if #t 1 2 3)",
+                Err(Error::BadSpecialForm(
+                    "if: expected (if test conseq [alt])".to_owned(),
+                    form("(if #t 1 2 3)"),
+                )),
+            ),
+            (
+                "(define)",
+                Err(Error::BadSpecialForm(
+                    "define: expected (define var expr) or (define (name params ...) body ...)"
+                        .to_owned(),
+                    form("(define)"),
+                )),
+            ),
+            (
+                "(define (f x))",
+                Err(Error::BadSpecialForm(
+                    "define: expected (define var expr) or (define (name params ...) body ...)"
+                        .to_owned(),
+                    form("(define (f x))"),
+                )),
+            ),
+            (
+                "(define (1 x) x)",
+                Err(Error::BadSpecialForm(
+                    "define: expected (define var expr) or (define (name params ...) body ...)"
+                        .to_owned(),
+                    form("(define (1 x) x)"),
+                )),
+            ),
+            (
+                "(set! x)",
+                Err(Error::BadSpecialForm(
+                    "set!: expected (set! var expr)".to_owned(),
+                    form("(set! x)"),
+                )),
+            ),
+            (
+                "(set! 1 2)",
+                Err(Error::BadSpecialForm(
+                    "set!: expected (set! var expr)".to_owned(),
+                    form("(set! 1 2)"),
+                )),
+            ),
+            (
+                "(lambda (x))",
+                Err(Error::BadSpecialForm(
+                    "lambda: expected (lambda (params ...) body ...)".to_owned(),
+                    form("(lambda (x))"),
+                )),
+            ),
+            (
+                "(lambda)",
+                Err(Error::BadSpecialForm(
+                    "lambda: expected (lambda (params ...) body ...)".to_owned(),
+                    form("(lambda)"),
+                )),
+            ),
+            (
+                "((lambda () (if #t)))",
+                Err(Error::BadSpecialForm(
+                    "if: expected (if test conseq [alt])".to_owned(),
+                    form("(if #t)"),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn one_armed_if() {
+        let cases = vec![
+            ("(if #f 1)", Ok("#<unspecified>")),
+            ("(if #t 1)", Ok("1")),
+            ("(define (f x) (if (> x 0) x))", Ok("(lambda (x) ...)")),
+            ("(f 1)", Ok("1")),
+            ("(f 0)", Ok("#<unspecified>")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn top_level_forward_references() {
+        let cases = vec![
+            (
+                "(define (even? n) (if (= n 0) #t (odd? (- n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            (
+                "(define (odd? n) (if (= n 0) #f (even? (- n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(even? 10)", Ok("#t")),
+            ("(odd? 7)", Ok("#t")),
+            ("(define (make) (lambda () (later)))", Ok("(lambda () ...)")),
+            ("(define thunk (make))", Ok("(lambda () ...)")),
+            ("(define (later) 1)", Ok("(lambda () ...)")),
+            ("(thunk)", Ok("1")),
+            ("(define (later) 2)", Ok("(lambda () ...)")),
+            ("(thunk)", Ok("2")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn internal_defines() {
+        let cases = vec![
+            (
+                "(define (f x) (define (double y) (* y 2)) (define z (double x)) (+ x z))",
+                Ok("(lambda (x) ...)"),
+            ),
+            ("(f 3)", Ok("9")),
+            ("(bound? 'double)", Ok("#f")),
+            (
+                "(define (make-counter) (define count 0) (define (next) (set! count (+ count 1)) count) next)",
+                Ok("(lambda () ...)"),
+            ),
+            ("(define counter (make-counter))", Ok("(lambda () ...)")),
+            ("(counter)", Ok("1")),
+            ("(counter)", Ok("2")),
+            (
+                "(define (parity) (define (ev? n) (if (= n 0) #t (od? (- n 1)))) (define (od? n) (if (= n 0) #f (ev? (- n 1)))) ev?)",
+                Ok("(lambda () ...)"),
+            ),
+            ("((parity) 10)", Ok("#t")),
+            ("((parity) 7)", Ok("#f")),
+            ("(define y 5)", Ok("5")),
+            ("(define (g) (define y 1) y)", Ok("(lambda () ...)")),
+            ("(g)", Ok("1")),
+            ("y", Ok("5")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn letrec_star() {
+        let cases = vec![
+            ("(letrec* ((a 1) (b (+ a 1))) (* a b))", Ok("2")),
+            (
+                "(letrec* ((ev? (lambda (n) (if (= n 0) #t (od? (- n 1))))) (od? (lambda (n) (if (= n 0) #f (ev? (- n 1)))))) (ev? 100))",
+                Ok("#t"),
+            ),
+            ("(bound? 'a)", Ok("#f")),
+            ("(define x 10)", Ok("10")),
+            ("(letrec* ((x 1) (y (* x 2))) (define z (+ x y)) z)", Ok("3")),
+            ("x", Ok("10")),
+            (
+                "(define (count-down n) (letrec* ((next (- n 1))) (if (= n 0) 'done (count-down next))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(count-down 10000)", Ok("done")),
+            (
+                "(letrec* (x) x)",
+                Err(Error::BadSpecialForm(
+                    "letrec*: expected (letrec* ((var init) ...) body ...)".to_owned(),
+                    parse_expr("(letrec* (x) x)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn define_memoized() {
+        let cases = vec![
+            ("(define calls 0)", Ok("0")),
+            (
+                "(define-memoized (square x) (set! calls (+ calls 1)) (* x x))",
+                Ok("(lambda (x) ...)"),
+            ),
+            ("(square 4)", Ok("16")),
+            ("(square 4)", Ok("16")),
+            ("calls", Ok("1")),
+            ("(square 5)", Ok("25")),
+            ("calls", Ok("2")),
+            (
+                "(define-memoized (fib n) (set! calls (+ calls 1)) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(set! calls 0)", Ok("#<unspecified>")),
+            ("(fib 30)", Ok("832040")),
+            ("calls", Ok("31")),
+            ("(define-memoized (ident f) (set! calls (+ calls 1)) f)", Ok("(lambda (f) ...)")),
+            ("(set! calls 0)", Ok("#<unspecified>")),
+            ("(ident car)", Ok("<primitive>")),
+            ("(ident car)", Ok("<primitive>")),
+            ("calls", Ok("2")),
+            (
+                "(define-memoized square 1)",
+                Err(Error::BadSpecialForm(
+                    "define-memoized: expected (define-memoized (name params ...) body ...)"
+                        .to_owned(),
+                    parse_expr("(define-memoized square 1)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn syntax_rules() {
+        let cases = vec![
+            (
+                "(define-syntax swap! (syntax-rules () ((_ a b) ((lambda (tmp) (set! a b) (set! b tmp)) a))))",
+                Ok("#<unspecified>"),
+            ),
+            ("(define tmp 1)", Ok("1")),
+            ("(define other 2)", Ok("2")),
+            ("(swap! tmp other)", Ok("#<unspecified>")),
+            ("(cons tmp other)", Ok("(2 . 1)")),
+            (
+                "((lambda (tmp y) (swap! tmp y) (cons tmp y)) 'a 'b)",
+                Ok("(b . a)"),
+            ),
+            (
+                "(define-syntax my-or (syntax-rules () ((_) #f) ((_ e) e) ((_ e r ...) ((lambda (t) (if t t (my-or r ...))) e))))",
+                Ok("#<unspecified>"),
+            ),
+            ("(define t 5)", Ok("5")),
+            ("(my-or #f t)", Ok("5")),
+            ("(my-or)", Ok("#f")),
+            (
+                "(define-syntax while (syntax-rules () ((_ test body ...) (do () ((not test)) body ...))))",
+                Ok("#<unspecified>"),
+            ),
+            ("(define n 0)", Ok("0")),
+            ("(while (< n 3) (set! n (+ n 1)))", Ok("#<unspecified>")),
+            ("n", Ok("3")),
+            (
+                "(swap! tmp)",
+                Err(Error::BadSpecialForm(
+                    "no syntax rule matches".to_owned(),
+                    parse_expr("(swap! tmp)").unwrap(),
+                )),
+            ),
+            (
+                "(define-syntax broken (lambda (x) x))",
+                Err(Error::BadSpecialForm(
+                    "define-syntax: expected (define-syntax name (syntax-rules (literal ...) (pattern template) ...))".to_owned(),
+                    parse_expr("(define-syntax broken (lambda (x) x))").unwrap(),
+                )),
+            ),
+            (
+                "(gensym 1)",
+                Err(Error::TypeMismatch(
+                    "symbol or string".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            ("(eq? (gensym 'tmp) 'tmp)", Ok("#f")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn macro_use_in_loop() {
+        let mut env = Env::primitive_bindings();
+        let cases = vec![
+            (
+                "(define-syntax swap! (syntax-rules () ((_ a b) ((lambda (tmp) (set! a b) (set! b tmp)) a))))",
+                Ok("#<unspecified>"),
+            ),
+            (
+                "(define (shuffle n a b) (if (= n 0) (cons a b) ((lambda () (swap! a b) (shuffle (- n 1) a b)))))",
+                Ok("(lambda (n a b) ...)"),
+            ),
+            ("(shuffle 1 'x 'y)", Ok("(y . x)")),
+        ];
+        check(&mut env, cases);
+        let aliases = env.alias_count();
+        check(&mut env, vec![("(shuffle 101 'x 'y)", Ok("(y . x)"))]);
+        assert_eq!(aliases, env.alias_count());
+        let cases = vec![
+            (
+                "(define-syntax swap! (syntax-rules () ((_ a b) (set! a b))))",
+                Ok("#<unspecified>"),
+            ),
+            ("(shuffle 1 'x 'y)", Ok("(y . y)")),
+        ];
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn fluid_let() {
+        let cases = vec![
+            ("(define depth 0)", Ok("0")),
+            ("(define (get-depth) depth)", Ok("(lambda () ...)")),
+            ("(fluid-let ((depth 1)) (get-depth))", Ok("1")),
+            ("depth", Ok("0")),
+            (
+                "(fluid-let ((depth 2)) (set! depth (+ depth 1)) (get-depth))",
+                Ok("3"),
+            ),
+            ("depth", Ok("0")),
+            (
+                "(fluid-let ((depth 5)) (car depth))",
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::Number(5.into()),
+                )),
+            ),
+            ("depth", Ok("0")),
+            (
+                "(fluid-let ((depth 5) (undefined 1)) depth)",
+                Err(Error::UnboundVar(
+                    "Getting an unbound variable".to_owned(),
+                    "undefined".to_owned(),
+                )),
+            ),
+            ("depth", Ok("0")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn do_loop() {
+        let cases = vec![
+            (
+                "(do ((i 1 (+ i 1)) (acc 1 (* acc i))) ((> i 5) acc))",
+                Ok("120"),
+            ),
+            ("(bound? 'i)", Ok("#f")),
+            ("(define total 0)", Ok("0")),
+            (
+                "(do ((i 0 (+ i 1)) (step 2)) ((= i 3) total) (set! total (+ total step)))",
+                Ok("6"),
+            ),
+            ("(do ((i 0 (+ i 1))) ((= i 3)))", Ok("#<unspecified>")),
+            ("(do ((a 1 b) (b 2 a)) ((= a 2) (cons a b)))", Ok("(2 . 1)")),
+            (
+                "(do ((i 0 (+ i 1))) ())",
+                Err(Error::BadSpecialForm(
+                    "do: expected (do ((var init [step]) ...) (test expr ...) body ...)".to_owned(),
+                    parse_expr("(do ((i 0 (+ i 1))) ())").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn not_function() {
+        let cases = vec![
+            (
+                "('(1 2 3) 1)",
+                "Attempted to apply non-procedure list: (1 2 3)",
+            ),
+            ("(1 2)", "Attempted to apply non-procedure number: 1"),
+            ("(\"f\")", "Attempted to apply non-procedure string: \"f\""),
+            (
+                "(apply 'car '((1)))",
+                "Invalid type: expected procedure, found car",
+            ),
+            (
+                "((open-input-string \"x\") 1)",
+                "Attempted to apply non-procedure port: <IO port>",
+            ),
+            (
+                "((make-hash-table))",
+                "Attempted to apply non-procedure hash-table: #<hash-table>",
+            ),
+            (
+                "((guard (e (#t e)) (car 1)))",
+                "Attempted to apply non-procedure condition: #<condition type: Invalid type: expected pair, found 1>",
+            ),
+            ("(#(1 2) 0)", "Attempted to apply non-procedure vector: #(1 2)"),
+        ];
+        let mut env = Env::primitive_bindings();
+        for (input, expected) in cases {
+            let val = parse_expr(input).unwrap();
+            let actual = super::eval(&mut env, &val).map_err(|e| e.to_string());
+            assert_eq!(Err(expected.to_owned()), actual.map(|val| val.to_string()));
+        }
+    }
+
+    #[test]
+    fn call_trace() {
+        let mut env = Env::primitive_bindings();
+        for input in [
+            "(define (inner x) (+ x missing))",
+            "(define (outer x) (* 2 (inner (+ x 1))))",
+        ] {
+            super::eval(&mut env, &parse_expr(input).unwrap()).unwrap();
+        }
+        let err = super::eval(&mut env, &parse_expr("(outer 1)").unwrap()).unwrap_err();
+        assert_eq!(
+            Error::UnboundVar(
+                "Getting an unbound variable".to_owned(),
+                "missing".to_owned()
+            ),
+            err
+        );
+        assert_eq!(
+            "Getting an unbound variable: missing\n  in (inner 2)\n  in (outer 1)",
+            err.to_string()
+        );
+        let err =
+            super::eval(&mut env, &parse_expr("((lambda (y) (inner y)) 5)").unwrap()).unwrap_err();
+        assert_eq!(
+            "Getting an unbound variable: missing\n  in (inner 5)",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn eof_object() {
+        let cases = vec![
+            ("(eof-object)", Ok("#<eof>")),
+            ("(eof-object? (eof-object))", Ok("#t")),
+            ("(eof-object? 1)", Ok("#f")),
+            (
+                "(define p (open-input-string \"hello\nworld\"))",
+                Ok("<IO port>"),
+            ),
+            ("(read-line p)", Ok("\"hello\"")),
+            ("(read-line p)", Ok("\"world\"")),
+            ("(eof-object? (read-line p))", Ok("#t")),
+            ("(define q (open-input-string \"done\"))", Ok("<IO port>")),
+            ("(read q)", Ok("done")),
+            ("(eof-object? (read q))", Ok("#t")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn char_io() {
+        let path = std::env::temp_dir().join("scheme-rs-char-io.txt");
+        let path = path.to_str().unwrap();
+        let write_file = format!(
+            "(call-with-output-file \"{}\" (lambda (port) (write-char #\\λ port) (write-char #\\x port)))",
+            path
+        );
+        let read_contents = format!("(read-contents \"{}\")", path);
+        let cases = vec![
+            ("(define p (open-input-string \"aλ\"))", Ok("<IO port>")),
+            ("(char-ready? p)", Ok("#t")),
+            ("(peek-char p)", Ok("#\\a")),
+            ("(peek-char p)", Ok("#\\a")),
+            ("(read-char p)", Ok("#\\a")),
+            ("(char-ready? p)", Ok("#t")),
+            ("(read-char p)", Ok("#\\λ")),
+            ("(char-ready? p)", Ok("#t")),
+            ("(eof-object? (peek-char p))", Ok("#t")),
+            ("(eof-object? (read-char p))", Ok("#t")),
+            (write_file.as_str(), Ok("#t")),
+            (read_contents.as_str(), Ok("\"λx\"")),
+            (
+                "(char-ready? 1)",
+                Err(Error::TypeMismatch(
+                    "port".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(write-char \"a\")",
+                Err(Error::TypeMismatch(
+                    "char".to_owned(),
+                    Value::String("a".to_owned()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn exception_handlers() {
+        let cases = vec![
+            (
+                "(with-exception-handler (lambda (c) 42) (lambda () (+ (raise-continuable 'oops) 1)))",
+                Ok("43"),
+            ),
+            (
+                "(with-exception-handler (lambda (c) (* c 10)) (lambda () (+ (raise-continuable 1) (raise-continuable 2))))",
+                Ok("30"),
+            ),
+            (
+                "(with-exception-handler (lambda (c) (+ c 1)) (lambda () (with-exception-handler (lambda (c) (raise-continuable (* c 2))) (lambda () (raise-continuable 5)))))",
+                Ok("11"),
+            ),
+            ("(with-exception-handler (lambda (c) c) (lambda () 7))", Ok("7")),
+            ("(guard (e (#t (cons 'caught e))) (raise 'boom))", Ok("(caught . boom)")),
+            (
+                "(with-exception-handler (lambda (c) 'outer) (lambda () (guard (e (#t 'inner)) (raise-continuable 1))))",
+                Ok("inner"),
+            ),
+            (
+                "(with-exception-handler (lambda (c) 0) (lambda () (raise 'boom)))",
+                Err(Error::Raised(parse_expr("boom").unwrap())),
+            ),
+            (
+                "(raise-continuable 'boom)",
+                Err(Error::Raised(parse_expr("boom").unwrap())),
+            ),
+            (
+                "(with-exception-handler 1 (lambda () 0))",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn port_predicates() {
+        let path = std::env::temp_dir().join("scheme-rs-port-predicates.txt");
+        let open_output = format!("(define out (open-output-file \"{}\"))", path.display());
+        let cases = vec![
+            ("(define in (open-input-string \"abc\"))", Ok("<IO port>")),
+            (open_output.as_str(), Ok("<IO port>")),
+            ("(port? in)", Ok("#t")),
+            ("(port? out)", Ok("#t")),
+            ("(port? \"abc\")", Ok("#f")),
+            ("(input-port? in)", Ok("#t")),
+            ("(input-port? out)", Ok("#f")),
+            ("(output-port? out)", Ok("#t")),
+            ("(output-port? in)", Ok("#f")),
+            ("(output-port? 1)", Ok("#f")),
+            ("(close-input-port in)", Ok("#t")),
+            ("(close-output-port out)", Ok("#t")),
+            ("(port? in)", Ok("#t")),
+            ("(input-port? in)", Ok("#f")),
+            ("(output-port? out)", Ok("#f")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compose() {
+        let cases = vec![
+            ("(identity 3)", Ok("3")),
+            ("(compose)", Ok("<composed procedure>")),
+            ("((compose) 3)", Ok("3")),
+            ("(load \"stdlib.scm\")", Ok("(lambda (obj lst) ...)")),
+            ("((compose (curry + 1) (curry * 2)) 3)", Ok("7")),
+            ("((compose car cdr cdr) '(1 2 3))", Ok("3")),
+            ("((compose (lambda (a b) (- a b)) values) 5 2)", Ok("3")),
+            ("((compose identity) 'x)", Ok("x")),
+            (
+                "(compose 1)",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn curry_and_cut() {
+        let cases = vec![
+            ("((curry + 2) 3)", Ok("5")),
+            ("((curry - 10 2) 3)", Ok("5")),
+            ("((curry cons) 1 2)", Ok("(1 . 2)")),
+            ("((cut - 10 <> 2) 3)", Ok("5")),
+            ("((cut <> 1 2) +)", Ok("3")),
+            ("((cut cons <> <>) 1 2)", Ok("(1 . 2)")),
+            ("((cut + 1 <...>) 2 3 4)", Ok("10")),
+            ("(define n 1)", Ok("1")),
+            ("(define add-n (cut + n <>))", Ok("(lambda (<>0) ...)")),
+            ("(define add-1 (cute + n <>))", Ok("(lambda (<>0) ...)")),
+            ("(set! n 10)", Ok("#<unspecified>")),
+            ("(add-n 1)", Ok("11")),
+            ("(add-1 1)", Ok("2")),
+            (
+                "(cut)",
+                Err(Error::BadSpecialForm(
+                    "cut: expected (cut slot-or-expr ... [<...>])".to_owned(),
+                    parse_expr("(cut)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn append_reverse() {
+        let items: Vec<String> = (0..100000).map(|i| i.to_string()).collect();
+        let define = format!("(define big '({}))", items.join(" "));
+        let cases = vec![
+            ("(append)", Ok("()")),
+            ("(append '(1 2))", Ok("(1 2)")),
+            ("(append 3)", Ok("3")),
+            ("(append '(1) '(2 3) '() '(4))", Ok("(1 2 3 4)")),
+            ("(append '(1) '(2 . 3))", Ok("(1 2 . 3)")),
+            ("(append '(1) 2)", Ok("(1 . 2)")),
+            ("(append '() 2)", Ok("2")),
+            (
+                "(append '(1 . 2) '(3))",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    parse_expr("(1 . 2)").unwrap(),
+                )),
+            ),
+            (
+                "(append-map (lambda (x) (cons x (cons x '()))) '(1 2))",
+                Ok("(1 1 2 2)"),
+            ),
+            ("(append-map cons '(1 2) '((a) (b c)))", Ok("(1 a 2 b c)")),
+            ("(append-map car '())", Ok("()")),
+            ("(flatten '(1 (2 (3)) 4))", Ok("(1 2 3 4)")),
+            ("(flatten '((()) (a ((b) c)) () d))", Ok("(a b c d)")),
+            ("(flatten '(1 (2 . 3) . 4))", Ok("(1 2 3 4)")),
+            (
+                "(flatten 5)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::Number(5.into()),
+                )),
+            ),
+            ("(reverse '())", Ok("()")),
+            ("(reverse '(1 2 3))", Ok("(3 2 1)")),
+            (define.as_str(), Ok(&define[13..define.len() - 1])),
+            ("(car (reverse big))", Ok("99999")),
+            ("(car (cdr (append '(a) big big)))", Ok("0")),
+            ("(car (reverse (append big '(a))))", Ok("a")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn write_abbreviations() {
+        let cases = vec![
+            ("'`(1 ,x ,@y)", Ok("`(1 ,x ,@y)")),
+            ("'(quasiquote (1 (unquote x)))", Ok("`(1 ,x)")),
+            ("''a", Ok("'a")),
+            ("'(a 'b)", Ok("(a 'b)")),
+            ("(car ''a)", Ok("quote")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn quote_shorthand() {
+        let cases = vec![
+            ("'''a", Ok("''a")),
+            ("(cons 'quote (cons 'a '()))", Ok("'a")),
+            ("'(quote)", Ok("(quote)")),
+            ("'(quote a b)", Ok("(quote a b)")),
+            ("'(quote . a)", Ok("(quote . a)")),
+            ("'(a quote b)", Ok("(a quote b)")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn sort() {
+        let cases = vec![
+            ("(sort '(3 1 2) <)", Ok("(1 2 3)")),
+            ("(list-sort > '(3 1 2))", Ok("(3 2 1)")),
+            ("(list-sort < '())", Ok("()")),
+            (
+                "(list-sort (lambda (a b) (< (car a) (car b))) '((1 . a) (0 . b) (1 . c) (0 . d)))",
+                Ok("((0 . b) (0 . d) (1 . a) (1 . c))"),
+            ),
+            ("(merge < '(1 3 5) '(2 4 6))", Ok("(1 2 3 4 5 6)")),
+            (
+                "(merge (lambda (a b) (< (car a) (car b))) '((0 . a) (1 . b)) '((0 . c) (2 . d)))",
+                Ok("((0 . a) (0 . c) (1 . b) (2 . d))"),
+            ),
+            (
+                "(list-sort 1 '(2 1))",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(list-sort + '(2 1))",
+                Err(Error::TypeMismatch(
+                    "boolean".to_owned(),
+                    Value::Number(3.into()),
+                )),
+            ),
+            (
+                "(list-sort (lambda (a b) (car a)) '(2 1))",
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn hash_tables() {
+        let cases = vec![
+            ("(define counts (make-hash-table))", Ok("#<hash-table>")),
+            ("(hash-table? counts)", Ok("#t")),
+            ("(hash-table? '())", Ok("#f")),
+            ("(hash-count counts)", Ok("0")),
+            (
+                "(do ((words '(a b a c a b) (cdr words))) ((eqv? words '()) (hash-count counts)) (hash-update! counts (car words) (lambda (n) (+ n 1)) 0))",
+                Ok("3"),
+            ),
+            ("(hash-ref counts 'a)", Ok("3")),
+            ("(hash-ref counts 'b)", Ok("2")),
+            ("(hash-ref counts 'c)", Ok("1")),
+            ("(hash-ref counts 'd 0)", Ok("0")),
+            (
+                "(hash-ref counts 'd)",
+                Err(Error::MissingKey(Value::Atom("d".into()))),
+            ),
+            (
+                "(hash-update! counts 'd (lambda (n) (+ n 1)))",
+                Err(Error::MissingKey(Value::Atom("d".into()))),
+            ),
+            ("(hash-set! counts \"d\" 'x)", Ok("#t")),
+            ("(hash-ref counts \"d\")", Ok("x")),
+            ("(hash-set! counts '(1 2) 'y)", Ok("#t")),
+            ("(hash-ref counts '(1 2))", Ok("y")),
+            ("(hash-ref counts (cons 1 '(2)))", Ok("y")),
+            ("(hash-set! counts 2/4 'half)", Ok("#t")),
+            ("(hash-ref counts 1/2)", Ok("half")),
+            ("(hash-count counts)", Ok("6")),
+            (
+                "(hash-set! counts car 1)",
+                Err(Error::TypeMismatch(
+                    "hashable value".to_owned(),
+                    Value::PrimitiveFunc(PrimitiveFunc::Car),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn nested_ref() {
+        let cases = vec![
+            (
+                "(define config '((server (host . \"example.org\") (port . 8080)) (\"debug\" . #t) ((1 2) . pair-key)))",
+                Ok("((server (host . \"example.org\") (port . 8080)) (\"debug\" . #t) ((1 2) . pair-key))"),
+            ),
+            ("(nested-ref config '(server port))", Ok("8080")),
+            ("(nested-ref config '(server host))", Ok("\"example.org\"")),
+            ("(nested-ref config '(\"debug\"))", Ok("#t")),
+            ("(nested-ref config '((1 2)))", Ok("pair-key")),
+            ("(nested-ref config '())", Ok("((server (host . \"example.org\") (port . 8080)) (\"debug\" . #t) ((1 2) . pair-key))")),
+            ("(nested-ref config '(client port))", Ok("#f")),
+            ("(nested-ref config '(client port) 80)", Ok("80")),
+            ("(nested-ref config '(server port number) 0)", Ok("0")),
+            ("(nested-ref '(1 2 3) '(1) 'none)", Ok("none")),
+            ("(define table (alist->hash-table (cons (cons 'db config) '())))", Ok("#<hash-table>")),
+            ("(nested-ref table '(db server port))", Ok("8080")),
+            ("(nested-ref table '(cache server port) 'none)", Ok("none")),
+            (
+                "(nested-ref config 'server)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::Atom("server".into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn alist_update_delete() {
+        let cases = vec![
+            (
+                "(define alist '((a . 1) (b . 2) (c . 3)))",
+                Ok("((a . 1) (b . 2) (c . 3))"),
+            ),
+            (
+                "(alist-update 'b 20 alist)",
+                Ok("((a . 1) (b . 20) (c . 3))"),
+            ),
+            (
+                "(alist-update 'd 4 alist)",
+                Ok("((a . 1) (b . 2) (c . 3) (d . 4))"),
+            ),
+            ("alist", Ok("((a . 1) (b . 2) (c . 3))")),
+            (
+                "(alist-update \"k\" 'v '((\"K\" . 1)) string-ci=?)",
+                Ok("((\"k\" . v))"),
+            ),
+            ("(alist-delete 'b alist)", Ok("((a . 1) (c . 3))")),
+            ("(alist-delete 'z alist)", Ok("((a . 1) (b . 2) (c . 3))")),
+            (
+                "(alist-delete '(1) '(((1) . x) (2 . y) ((1) . z)))",
+                Ok("((2 . y))"),
+            ),
+            ("(alist-delete 2 '((1 . a) (3 . b)) <)", Ok("((1 . a))")),
+            (
+                "(alist-delete 'a '((a . 1) b))",
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::Atom("b".into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn alist_hash_table() {
+        let cases = vec![
+            (
+                "(define table (alist->hash-table '((a . 1) (b 2 3) (a . 4))))",
+                Ok("#<hash-table>"),
+            ),
+            ("(hash-count table)", Ok("2")),
+            ("(hash-ref table 'a)", Ok("4")),
+            ("(hash-ref table 'b)", Ok("(2 3)")),
+            ("(hash-table->alist table)", Ok("((a . 4) (b 2 3))")),
+            (
+                "(hash-table->alist (alist->hash-table (hash-table->alist table)))",
+                Ok("((a . 4) (b 2 3))"),
+            ),
+            ("(hash-table->alist (alist->hash-table '()))", Ok("()")),
+            (
+                "(alist->hash-table '((a . 1) b))",
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::Atom("b".into()),
+                )),
+            ),
+            (
+                "(alist->hash-table '((a . 1) ()))",
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::List(Rc::new([])),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn truthiness() {
+        let cases = vec![
+            ("(if '() 'yes 'no)", Ok("yes")),
+            ("(if 0 'yes 'no)", Ok("yes")),
+            ("(not 0)", Ok("#f")),
+            ("(not #f)", Ok("#t")),
+            ("(&& 1 '())", Ok("#t")),
+            ("(|| #f 0)", Ok("#t")),
+            ("(and)", Ok("#t")),
+            ("(and 1 2)", Ok("2")),
+            ("(and 1 #f (car '()))", Ok("#f")),
+            ("(or)", Ok("#f")),
+            ("(or #f 3 (car '()))", Ok("3")),
+            ("(when 0 'a 'b)", Ok("b")),
+            ("(when #f 'a)", Ok("#<unspecified>")),
+            ("(unless #f 'a)", Ok("a")),
+            ("(unless '() 'a)", Ok("#<unspecified>")),
+            ("(cond (#f 1) ('() 2) (else 3))", Ok("2")),
+            ("(cond (#f 1) (else 2 3))", Ok("3")),
+            ("(cond (#f 1))", Ok("#<unspecified>")),
+            ("(cond (5))", Ok("5")),
+            ("(cond ((car '(1 2)) => (lambda (x) (+ x 1))))", Ok("2")),
+            (
+                "(define (count-down n) (cond ((= n 0) 'done) (else (and #t (or #f (when #t (count-down (- n 1))))))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(count-down 10000)", Ok("done")),
+            (
+                "(when)",
+                Err(Error::BadSpecialForm(
+                    "when: expected (when test expr ...)".to_owned(),
+                    parse_expr("(when)").unwrap(),
+                )),
+            ),
+            (
+                "(cond 1)",
+                Err(Error::BadSpecialForm(
+                    "cond: expected (cond (test expr ...) ... [(else expr ...)])".to_owned(),
+                    parse_expr("(cond 1)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn guard() {
+        let cases = vec![
+            ("(guard (e (#t (error-kind e))) (car 1))", Ok("\"type\"")),
+            ("(guard (e (#t (error-kind e))) (car 1 2))", Ok("\"arity\"")),
+            (
+                "(guard (e (#t (error-kind e))) undefined-var)",
+                Ok("\"unbound\""),
+            ),
+            (
+                "(guard (e (#t (error-kind e))) (/ 1 0))",
+                Ok("\"arithmetic\""),
+            ),
+            (
+                "(guard (e (#t (error-message e))) (car 1))",
+                Ok("\"Invalid type: expected pair, found 1\""),
+            ),
+            (
+                "(guard (e (#t e)) (car 1))",
+                Ok("#<condition type: Invalid type: expected pair, found 1>"),
+            ),
+            ("(guard (e (#t 'caught)) 1 2)", Ok("2")),
+            (
+                "(guard (e ((string=? (error-kind e) \"arity\") 'arity) (else 'other)) (car 1))",
+                Ok("other"),
+            ),
+            (
+                "(guard (e ((error-kind e) => (lambda (k) (string=? k \"type\")))) (car 1))",
+                Ok("#t"),
+            ),
+            (
+                "(guard (e (#f 'never)) (car 1))",
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    parse_expr("1").unwrap(),
+                )),
+            ),
+            ("(define (fails) (car 1))", Ok("(lambda () ...)")),
+            ("(define x 1)", Ok("1")),
+            ("(guard (x (#t (error-kind x))) (fails))", Ok("\"type\"")),
+            ("x", Ok("1")),
+            (
+                "(error-kind 1)",
+                Err(Error::TypeMismatch(
+                    "condition".to_owned(),
+                    parse_expr("1").unwrap(),
+                )),
+            ),
+            (
+                "(guard e (car 1))",
+                Err(Error::BadSpecialForm(
+                    "guard: expected (guard (var clause ...) body ...)".to_owned(),
+                    parse_expr("(guard e (car 1))").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn map_for_each() {
+        let cases = vec![
+            ("(map (lambda (x) (* x x)) '(1 2 3))", Ok("(1 4 9)")),
+            ("(map cons '(1 2 3) '(a b))", Ok("((1 . a) (2 . b))")),
+            ("(map + '(1 2) '(10 20 30) '(100 200))", Ok("(111 222)")),
+            ("(map car '())", Ok("()")),
+            ("(define total 0)", Ok("0")),
+            (
+                "(for-each (lambda (x y) (set! total (+ total (* x y)))) '(1 2 3) '(4 5))",
+                Ok("#<unspecified>"),
+            ),
+            ("total", Ok("14")),
+            (
+                "(map car 1)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    parse_expr("1").unwrap(),
+                )),
+            ),
+            (
+                "(map car)",
+                Err(Error::NumArgs(
+                    2,
+                    vec![Value::PrimitiveFunc(PrimitiveFunc::Car)],
+                )),
+            ),
+            (
+                "(map 1 '(1 2))",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(for-each 1 '())",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn filter_remove_partition() {
+        let cases = vec![
+            (
+                "(define (even n) (= (modulo n 2) 0))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(filter even '(1 2 3 4 5 6))", Ok("(2 4 6)")),
+            ("(remove even '(1 2 3 4 5 6))", Ok("(1 3 5)")),
+            ("(filter even '())", Ok("()")),
+            (
+                "(call-with-values (lambda () (partition even '(1 2 3 4 5))) cons)",
+                Ok("((2 4) 1 3 5)"),
+            ),
+            (
+                "(filter even 1)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+            (
+                "(partition 1 '(1))",
+                Err(Error::TypeMismatch(
+                    "procedure".to_owned(),
+                    Value::Number(1.into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn count_any_every_find() {
+        let cases = vec![
+            (
+                "(define (odd n) (= (modulo n 2) 1))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(count odd '(1 2 3 5))", Ok("3")),
+            ("(count < '(1 5 2) '(2 3 4 0))", Ok("2")),
+            ("(any odd '(2 4 5))", Ok("#t")),
+            (
+                "(any (lambda (n) (and (odd n) (* n 10))) '(2 3 5))",
+                Ok("30"),
+            ),
+            ("(any odd '(2 4))", Ok("#f")),
+            ("(every odd '(1 3 4))", Ok("#f")),
+            ("(every (lambda (n) (and (odd n) n)) '(1 3 5))", Ok("5")),
+            ("(every odd '())", Ok("#t")),
+            ("(define tested '())", Ok("()")),
+            (
+                "(define (odd-logged n) (set! tested (cons n tested)) (odd n))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(any odd-logged '(2 3 4 5))", Ok("#t")),
+            ("tested", Ok("(3 2)")),
+            ("(set! tested '())", Ok("#<unspecified>")),
+            ("(every odd-logged '(1 2 3))", Ok("#f")),
+            ("tested", Ok("(2 1)")),
+            ("(find odd '(2 4 5 7))", Ok("5")),
+            ("(find odd '(2 4))", Ok("#f")),
+            (
+                "(find odd 'x)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::Atom("x".into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn fold() {
+        let cases = vec![
+            ("(fold + 0 '(1 2 3) '(10 20 30))", Ok("66")),
+            ("(fold + 0 '(1 2 3) '(10 20))", Ok("33")),
+            ("(fold cons '() '(1 2 3))", Ok("(3 2 1)")),
+            ("(fold-right cons '() '(1 2 3))", Ok("(1 2 3)")),
+            (
+                "(fold-right (lambda (a b acc) (cons (cons a b) acc)) '() '(1 2 3) '(x y))",
+                Ok("((1 . x) (2 . y))"),
+            ),
+            ("(fold + 7 '())", Ok("7")),
+            (
+                "(fold + 0)",
+                Err(Error::NumArgs(
+                    3,
+                    vec![
+                        Value::PrimitiveFunc(PrimitiveFunc::Add),
+                        Value::Number(0.into()),
+                    ],
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn delete_duplicates() {
+        let cases = vec![
+            ("(delete-duplicates '(1 2 1 3 2))", Ok("(1 2 3)")),
+            (
+                "(delete-duplicates '(a (1 2) a \"x\" (1 2) \"x\"))",
+                Ok("(a (1 2) \"x\")"),
+            ),
+            ("(delete-duplicates '())", Ok("()")),
+            (
+                "(delete-duplicates '(\"Apple\" \"pear\" \"apple\" \"PEAR\") string-ci=?)",
+                Ok("(\"Apple\" \"pear\")"),
+            ),
+            (
+                "(delete-duplicates '(1 2 3 4 5) (lambda (a b) (= (modulo a 2) (modulo b 2))))",
+                Ok("(1 2)"),
+            ),
+            (
+                "(delete-duplicates 'x)",
+                Err(Error::TypeMismatch(
+                    "list".to_owned(),
+                    Value::Atom("x".into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn define_constant() {
+        let cases = vec![
+            ("(define-constant limit 10)", Ok("10")),
+            ("(define counter 0)", Ok("0")),
+            ("(set! counter (+ counter limit))", Ok("#<unspecified>")),
+            ("counter", Ok("10")),
+            ("(set! limit 20)", Err(Error::Constant("limit".to_owned()))),
+            ("limit", Ok("10")),
+            (
+                "((lambda () (set! limit 30)))",
+                Err(Error::Constant("limit".to_owned())),
+            ),
+            (
+                "(define limit 20)",
+                Err(Error::Constant("limit".to_owned())),
+            ),
+            (
+                "(define-constant limit 20)",
+                Err(Error::Constant("limit".to_owned())),
+            ),
+            ("(set! limit 30)", Err(Error::Constant("limit".to_owned()))),
+            ("limit", Ok("10")),
+            ("((lambda (limit) (set! limit 5) limit) 1)", Ok("5")),
+            (
+                "(define-constant (bad) 1)",
+                Err(Error::BadSpecialForm(
+                    "define-constant: expected (define-constant var expr)".to_owned(),
+                    parse_expr("(define-constant (bad) 1)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn depth_limit() {
+        let cases = vec![
+            (
+                "(define (count-up n) (+ 1 (count-up (+ n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(count-up 0)", Err(Error::StackOverflow(20))),
+            (
+                "(define (depth n) (if (= n 0) 0 (+ 1 (depth (- n 1)))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(depth 10)", Ok("10")),
+            (
+                "(define (loop n) (if (= n 0) 'done (loop (- n 1))))",
+                Ok("(lambda (n) ...)"),
+            ),
+            ("(loop 1000)", Ok("done")),
+        ];
+        let mut env = Env::primitive_bindings();
+        env.set_depth_limit(Some(20));
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn safe_primitives() {
+        let cases = vec![
+            ("(define (car x) x)", Ok("(lambda (x) ...)")),
+            ("(car 1)", Ok("1")),
+            ("(set! + -)", Ok("#<unspecified>")),
+            ("(+ 3 1)", Ok("2")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+
+        let cases = vec![
+            (
+                "(define (car x) x)",
+                Err(Error::Primitive("car".to_owned())),
+            ),
+            ("(car '(1 2))", Ok("1")),
+            ("(set! + -)", Err(Error::Primitive("+".to_owned()))),
+            (
+                "(define-constant cdr car)",
+                Err(Error::Primitive("cdr".to_owned())),
+            ),
+            (
+                "(define-syntax car (syntax-rules () ((_ x) 'hijacked)))",
+                Err(Error::Primitive("car".to_owned())),
+            ),
+            ("(car '(1 2))", Ok("1")),
+            ("(define x 1)", Ok("1")),
+            ("(set! x 2)", Ok("#<unspecified>")),
+            ("((lambda (car) (set! car 5) car) 1)", Ok("5")),
+        ];
+        let mut env = Env::primitive_bindings();
+        env.set_safe_primitives(true);
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn unicode_strings() {
+        let cases = vec![
+            ("(string-length \"héllo\")", Ok("5")),
+            ("(string-length \"a😀b\")", Ok("3")),
+            ("(string-length \"\")", Ok("0")),
+            ("(string-length \"a\\nb\")", Ok("3")),
+            ("(string-length \"\\\"\\\\\")", Ok("2")),
+            ("(substring \"a\\tb\" 1 2)", Ok("\"\\t\"")),
+            ("(string-ref \"a\\x3bb;\" 1)", Ok("#\\λ")),
+            ("(string-ref \"a\\\"\" 1)", Ok("#\\\"")),
+            ("(string-ref \"a😀b\" 1)", Ok("#\\😀")),
+            ("(string-ref \"héllo\" 4)", Ok("#\\o")),
+            ("(substring \"a😀bé\" 1 3)", Ok("\"😀b\"")),
+            ("(substring \"a😀bé\" 2)", Ok("\"bé\"")),
+            ("(substring \"héllo\" 1 1)", Ok("\"\"")),
+            ("(char-foldcase #\\É)", Ok("#\\é")),
+            ("(string-foldcase \"ÉCOLE\")", Ok("\"école\"")),
+            (
+                "(string-ref \"a😀b\" 3)",
+                Err(Error::OutOfRange(3, Value::String("a😀b".to_owned()))),
+            ),
+            (
+                "(substring \"a😀b\" 2 4)",
+                Err(Error::OutOfRange(4, Value::String("a😀b".to_owned()))),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn numeric_predicates() {
+        let cases = vec![
+            ("(integer? 7)", Ok("#t")),
+            ("(integer? -7)", Ok("#t")),
+            ("(integer? 1/2)", Ok("#f")),
+            ("(integer? \"7\")", Ok("#f")),
+            ("(rational? 7)", Ok("#t")),
+            ("(rational? -7)", Ok("#t")),
+            ("(rational? 1/2)", Ok("#t")),
+            ("(rational? \"7\")", Ok("#f")),
+            ("(real? 7)", Ok("#t")),
+            ("(real? -7)", Ok("#t")),
+            ("(real? 1/2)", Ok("#t")),
+            ("(real? \"7\")", Ok("#f")),
+            ("(exact? 7)", Ok("#t")),
+            ("(exact? -7)", Ok("#t")),
+            ("(exact? 1/2)", Ok("#t")),
+            (
+                "(exact? \"7\")",
+                Err(Error::TypeMismatch(
+                    "number".to_owned(),
+                    Value::String("7".to_owned()),
+                )),
+            ),
+            ("(inexact? 7)", Ok("#f")),
+            ("(inexact? -7)", Ok("#f")),
+            ("(inexact? 1/2)", Ok("#f")),
+            (
+                "(inexact? \"7\")",
+                Err(Error::TypeMismatch(
+                    "number".to_owned(),
+                    Value::String("7".to_owned()),
+                )),
+            ),
+            ("(exact-integer? 7)", Ok("#t")),
+            ("(exact-integer? -7)", Ok("#t")),
+            ("(exact-integer? 1/2)", Ok("#f")),
+            ("(exact-integer? \"7\")", Ok("#f")),
+            ("(exact-nonnegative-integer? 7)", Ok("#t")),
+            ("(exact-nonnegative-integer? -7)", Ok("#f")),
+            ("(exact-nonnegative-integer? 1/2)", Ok("#f")),
+            ("(exact-nonnegative-integer? \"7\")", Ok("#f")),
+            ("(exact-nonnegative-integer? 0)", Ok("#t")),
+            ("(integer? 8/2)", Ok("#t")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn floor_truncate() {
+        let cases = vec![
+            ("(floor-quotient -7 2)", Ok("-4")),
+            ("(truncate-quotient -7 2)", Ok("-3")),
+            ("(floor-remainder -7 2)", Ok("1")),
+            ("(truncate-remainder -7 2)", Ok("-1")),
+            ("(floor-quotient 7 -2)", Ok("-4")),
+            ("(floor-remainder 7 -2)", Ok("-1")),
+            ("(floor-remainder -7 -2)", Ok("-1")),
+            ("(floor-quotient 6 -2)", Ok("-3")),
+            ("(floor/ -7 2)", Ok("-4 1")),
+            ("(truncate/ -7 2)", Ok("-3 -1")),
+            ("(call-with-values (lambda () (floor/ 7 -2)) +)", Ok("-5")),
+            ("(floor/ 1 0)", Err(Error::DivByZero)),
+            (
+                "(truncate/ 1/2 1)",
+                Err(Error::TypeMismatch(
+                    "integer".to_owned(),
+                    parse_expr("1/2").unwrap(),
+                )),
+            ),
+            (
+                "(floor/ 1)",
+                Err(Error::NumArgs(2, vec![Value::Number(1.into())])),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn exact_integer_sqrt() {
+        let cases = vec![
+            ("(exact-integer-sqrt 17)", Ok("4 1")),
+            ("(exact-integer-sqrt 16)", Ok("4 0")),
+            ("(exact-integer-sqrt 0)", Ok("0 0")),
+            ("(exact-integer-sqrt 1)", Ok("1 0")),
+            ("(exact-integer-sqrt 99)", Ok("9 18")),
+            (
+                "(exact-integer-sqrt 1000000000000000000000000000000000000000)",
+                Ok("31622776601683793319 62545769258890964239"),
+            ),
+            (
+                "(exact-integer-sqrt -4)",
+                Err(Error::TypeMismatch(
+                    "non-negative integer".to_owned(),
+                    parse_expr("-4").unwrap(),
+                )),
+            ),
+            (
+                "(exact-integer-sqrt 1/4)",
+                Err(Error::TypeMismatch(
+                    "non-negative integer".to_owned(),
+                    parse_expr("1/4").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn numerator_denominator() {
+        let cases = vec![
+            ("(numerator 6/10)", Ok("3")),
+            ("(denominator 6/10)", Ok("5")),
+            ("(numerator -6/10)", Ok("-3")),
+            ("(denominator -6/10)", Ok("5")),
+            ("(numerator 7)", Ok("7")),
+            ("(denominator 7)", Ok("1")),
+            ("(rationalize 3/10 1/10)", Ok("1/3")),
+            ("(rationalize -3/10 1/10)", Ok("-1/3")),
+            ("(rationalize 5/2 1)", Ok("2")),
+            ("(rationalize 1/4 1/2)", Ok("0")),
+            ("(rationalize 22/7 0)", Ok("22/7")),
+            ("(rationalize 333/1000 -1/100)", Ok("1/3")),
+            (
+                "(numerator \"1\")",
+                Err(Error::TypeMismatch(
+                    "number".to_owned(),
+                    Value::String("1".to_owned()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn load_relative() {
+        let dir = std::env::temp_dir().join("scheme-rs-load-relative");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        let files = [
+            ("main.scm", "(load \"lib/helper.scm\")\n(+ helper 1)"),
+            (
+                "lib/helper.scm",
+                "(load \"inner.scm\")\n(define helper (* inner 10))",
+            ),
+            ("lib/inner.scm", "(define inner 4)"),
+            ("lib/broken.scm", "(car '())"),
+        ];
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        let load_main = format!("(load \"{}\")", dir.join("main.scm").display());
+        let load_broken = format!("(load \"{}\")", dir.join("lib/broken.scm").display());
+        let cases = vec![
+            (load_main.as_str(), Ok("41")),
+            ("helper", Ok("40")),
+            (
+                load_broken.as_str(),
+                Err(Error::TypeMismatch(
+                    "pair".to_owned(),
+                    Value::List(Rc::new([])),
+                )),
+            ),
+            (
+                "(load \"inner.scm\")",
+                Err(Error::IO(std::io::ErrorKind::NotFound.into())),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn load_path() {
+        let dir = std::env::temp_dir().join("scheme-rs-load-path");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("only-here.scm"), "(define only-here 7)").unwrap();
+        let add_dir = format!("(add-to-load-path \"{}\")", dir.display());
+        let not_found = format!(
+            "\"IO error: missing.scm not found, tried missing.scm, {}\"",
+            dir.join("missing.scm").display()
+        );
+        let cases = vec![
+            (
+                "(load \"only-here.scm\")",
+                Err(Error::IO(std::io::ErrorKind::NotFound.into())),
+            ),
+            (add_dir.as_str(), Ok("#t")),
+            ("(load \"only-here.scm\")", Ok("7")),
+            ("only-here", Ok("7")),
+            (
+                "(guard (e (#t (error-message e))) (load \"missing.scm\"))",
+                Ok(not_found.as_str()),
+            ),
+            (
+                "(add-to-load-path 'lib)",
+                Err(Error::TypeMismatch(
+                    "string".to_owned(),
+                    Value::Atom("lib".into()),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn libraries() {
+        let cases = vec![
+            (
+                "(define-library (my lib) (export square) (import (scheme base)) (begin (define (square x) (helper x)) (define (helper x) (* x x))))",
+                Ok("#<unspecified>"),
+            ),
+            (
+                "square",
+                Err(Error::UnboundVar(
+                    "Getting an unbound variable".to_owned(),
+                    "square".to_owned(),
+                )),
+            ),
+            ("(import (scheme base) (my lib))", Ok("#<unspecified>")),
+            ("(square 5)", Ok("25")),
+            (
+                "helper",
+                Err(Error::UnboundVar(
+                    "Getting an unbound variable".to_owned(),
+                    "helper".to_owned(),
+                )),
+            ),
+            (
+                "(define-library (counter) (export count bump!) (begin (define count 0) (define (bump!) (set! count (+ count 1)))))",
+                Ok("#<unspecified>"),
+            ),
+            ("(define-library (uses counter) (export twice) (import (counter)) (begin (define (twice) (bump!) (bump!) count)))", Ok("#<unspecified>")),
+            ("(import (uses counter))", Ok("#<unspecified>")),
+            ("(twice)", Ok("2")),
+            (
+                "(import (no such lib))",
+                Err(Error::UnboundVar(
+                    "Importing an unknown library".to_owned(),
+                    "(no such lib)".to_owned(),
+                )),
+            ),
+            (
+                "(define-library (bad) (export missing))",
+                Err(Error::UnboundVar(
+                    "Exporting an unbound variable".to_owned(),
+                    "missing".to_owned(),
+                )),
+            ),
+            (
+                "(import my)",
+                Err(Error::BadSpecialForm(
+                    "import: expected (import (name ...) ...)".to_owned(),
+                    parse_expr("(import my)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn load_prefix() {
+        let dir = std::env::temp_dir().join("scheme-rs-load-prefix");
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            (
+                "one.scm",
+                "(define (helper) 1)\n(define (api) (cons (helper) '(helper)))",
+            ),
+            ("two.scm", "(define helper 2)"),
+        ];
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        let add_dir = format!("(add-to-load-path \"{}\")", dir.display());
+        let cases = vec![
+            (add_dir.as_str(), Ok("#t")),
+            ("(load \"one.scm\" \"one:\")", Ok("(lambda () ...)")),
+            ("(load \"two.scm\" \"two:\")", Ok("2")),
+            ("(one:helper)", Ok("1")),
+            ("two:helper", Ok("2")),
+            ("(one:api)", Ok("(1 helper)")),
+            (
+                "helper",
+                Err(Error::UnboundVar(
+                    "Getting an unbound variable".to_owned(),
+                    "helper".to_owned(),
+                )),
+            ),
+            (
+                "(load \"two.scm\" 'two)",
+                Err(Error::BadSpecialForm(
+                    "load: expected (load \"path\" [\"prefix\"])".to_owned(),
+                    parse_expr("(load \"two.scm\" 'two)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn prefix_renames() {
+        let source = "(define (helper) 1)\n\
+            (define (api) `(helper ,(helper) ,@(map helper '(helper))))\n\
+            (define (shadowed helper #!optional (n (helper))) helper)\n\
+            (define (local) (define (helper) 2) (helper))\n\
+            (define (counted . helper) (do ((helper helper (cdr helper))) ((null? helper) (helper))))\n\
+            (define (nested) (letrec* ((helper (lambda () helper))) helper))";
+        let expected = [
+            "(define (one:helper) 1)",
+            "(define (one:api) `(helper ,(one:helper) ,@(map one:helper '(helper))))",
+            "(define (one:shadowed helper #!optional (n (helper))) helper)",
+            "(define (one:local) (define (helper) 2) (helper))",
+            "(define (one:counted . helper) (do ((helper helper (cdr helper))) ((null? helper) (helper))))",
+            "(define (one:nested) (letrec* ((helper (lambda () helper))) helper))",
+        ];
+        let forms = super::prefix_defines(&parse_exprs(source).unwrap(), "one:");
+        let forms: Vec<String> = forms.iter().map(Value::to_string).collect();
+        assert_eq!(expected.as_slice(), forms.as_slice());
+    }
+
+    #[test]
+    fn rest_args() {
+        let cases = vec![
+            ("((lambda (a . rest) rest) 1 2 3)", Ok("(2 3)")),
+            (
+                "((lambda (a b . rest) (cons b rest)) 1 2 3 4)",
+                Ok("(2 3 4)"),
+            ),
+            ("((lambda (a . rest) rest) 1)", Ok("()")),
+            ("((lambda args args) 1 2 3)", Ok("(1 2 3)")),
+            ("((lambda args args))", Ok("()")),
+            (
+                "(define (tail a . rest) rest)",
+                Ok("(lambda (a . rest) ...)"),
+            ),
+            ("(apply tail '(1 2 3))", Ok("(2 3)")),
+            (
+                "((lambda (a b . rest) rest) 1)",
+                Err(Error::ProcedureNumArgs(
+                    None,
+                    Arity::AtLeast(2),
+                    vec![Value::Number(1.into())],
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn bad_params() {
+        let cases = vec![
+            (
+                "(lambda (1 2) 3)",
+                Err(Error::BadSpecialForm(
+                    "lambda: parameter is not a symbol: 1".to_owned(),
+                    parse_expr("(lambda (1 2) 3)").unwrap(),
+                )),
+            ),
+            (
+                "(lambda (x x) x)",
+                Err(Error::BadSpecialForm(
+                    "lambda: duplicate parameter: x".to_owned(),
+                    parse_expr("(lambda (x x) x)").unwrap(),
+                )),
+            ),
+            (
+                "(lambda (x . x) x)",
+                Err(Error::BadSpecialForm(
+                    "lambda: duplicate parameter: x".to_owned(),
+                    parse_expr("(lambda (x . x) x)").unwrap(),
+                )),
+            ),
+            (
+                "(define (f x \"y\") x)",
+                Err(Error::BadSpecialForm(
+                    "define: parameter is not a symbol: \"y\"".to_owned(),
+                    parse_expr("(define (f x \"y\") x)").unwrap(),
+                )),
+            ),
+            (
+                "(define (f a b . a) a)",
+                Err(Error::BadSpecialForm(
+                    "define: duplicate parameter: a".to_owned(),
+                    parse_expr("(define (f a b . a) a)").unwrap(),
+                )),
+            ),
+            ("(define (f a b . c) c)", Ok("(lambda (a b . c) ...)")),
+            ("(f 1 2 3)", Ok("(3)")),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn optional_params() {
+        let cases = vec![
+            (
+                "(define (add x #!optional (y 10)) (+ x y))",
+                Ok("(lambda (x #!optional (y 10)) ...)"),
+            ),
+            ("(add 1)", Ok("11")),
+            ("(add 1 2)", Ok("3")),
+            (
+                "(add 1 2 3)",
+                Err(Error::ProcedureNumArgs(
+                    Some("add".to_owned()),
+                    Arity::Between(1, 2),
+                    vec![
+                        Value::Number(1.into()),
+                        Value::Number(2.into()),
+                        Value::Number(3.into()),
+                    ],
+                )),
+            ),
+            (
+                "(add)",
+                Err(Error::ProcedureNumArgs(
+                    Some("add".to_owned()),
+                    Arity::Between(1, 2),
+                    vec![],
+                )),
+            ),
+            (
+                "((lambda (#!optional (a 1) (b (+ a 1))) (cons a b)))",
+                Ok("(1 . 2)"),
+            ),
+            (
+                "((lambda (#!optional (a 1) (b (+ a 1))) (cons a b)) 5)",
+                Ok("(5 . 6)"),
+            ),
+            (
+                "((lambda (a #!optional (b 0) . rest) (cons b rest)) 1)",
+                Ok("(0)"),
+            ),
+            (
+                "((lambda (a #!optional (b 0) . rest) (cons b rest)) 1 2 3 4)",
+                Ok("(2 3 4)"),
+            ),
+            (
+                "(lambda (#!optional y) y)",
+                Err(Error::BadSpecialForm(
+                    "lambda: expected (name default) for optional parameter: y".to_owned(),
+                    parse_expr("(lambda (#!optional y) y)").unwrap(),
+                )),
+            ),
+            (
+                "(lambda (x #!optional (x 1)) x)",
+                Err(Error::BadSpecialForm(
+                    "lambda: duplicate parameter: x".to_owned(),
+                    parse_expr("(lambda (x #!optional (x 1)) x)").unwrap(),
+                )),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
+    }
+
+    #[test]
+    fn arity_names() {
+        let cases = vec![
+            ("(define (pair a b) (cons a b))", Ok("(lambda (a b) ...)")),
+            (
+                "(pair 1 2 3)",
+                Err(Error::ProcedureNumArgs(
+                    Some("pair".to_owned()),
+                    Arity::Exactly(2),
+                    vec![
+                        Value::Number(1.into()),
+                        Value::Number(2.into()),
+                        Value::Number(3.into()),
+                    ],
+                )),
+            ),
+            (
+                "(guard (e (#t (error-message e))) (pair 1 2 3))",
+                Ok("\"pair: expected 2 args; found values 1 2 3\""),
+            ),
+            ("(define single (lambda (x) x))", Ok("(lambda (x) ...)")),
+            ("(define alias single)", Ok("(lambda (x) ...)")),
+            (
+                "(alias)",
+                Err(Error::ProcedureNumArgs(
+                    Some("single".to_owned()),
+                    Arity::Exactly(1),
+                    vec![],
+                )),
+            ),
+            (
+                "(define (outer) (define (inner x) x) (inner))",
+                Ok("(lambda () ...)"),
+            ),
+            (
+                "(outer)",
+                Err(Error::ProcedureNumArgs(
+                    Some("inner".to_owned()),
+                    Arity::Exactly(1),
+                    vec![],
+                )),
+            ),
+            (
+                "((lambda (x) x))",
+                Err(Error::ProcedureNumArgs(None, Arity::Exactly(1), vec![])),
+            ),
+            (
+                "(guard (e (#t (error-message e))) ((lambda (x #!optional (y 1)) x)))",
+                Ok("\"Expected 1 to 2 args; found values \""),
+            ),
+            (
+                "(define (at-least-two a b . rest) rest)",
+                Ok("(lambda (a b . rest) ...)"),
+            ),
+            (
+                "(at-least-two 1)",
+                Err(Error::ProcedureNumArgs(
+                    Some("at-least-two".to_owned()),
+                    Arity::AtLeast(2),
+                    vec![Value::Number(1.into())],
+                )),
+            ),
+            (
+                "(guard (e (#t (error-message e))) (at-least-two 1))",
+                Ok("\"at-least-two: expected at least 2 args; found values 1\""),
+            ),
+        ];
+        let mut env = Env::primitive_bindings();
+        check(&mut env, cases);
     }
 }
@@ -0,0 +1,130 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use crate::{error::Error, number::Number, symbol::Symbol, value::Value};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The hashable form of a value usable as a hash table key. Only plain data
+/// has one: procedures, ports and mutable containers such as bytevectors
+/// could not keep a stable hash, so turning them into a key fails.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValueKey {
+    Atom(Symbol),
+    Number(Number),
+    String(String),
+    Char(char),
+    Bool(bool),
+    List(Vec<ValueKey>),
+    DottedList(Vec<ValueKey>, Box<ValueKey>),
+}
+
+impl TryFrom<&Value> for ValueKey {
+    type Error = Error;
+
+    fn try_from(val: &Value) -> Result<Self> {
+        match val {
+            Value::Atom(atom) => Ok(ValueKey::Atom(*atom)),
+            Value::Number(n) => Ok(ValueKey::Number(n.clone())),
+            Value::String(s) => Ok(ValueKey::String(s.clone())),
+            Value::Char(c) => Ok(ValueKey::Char(*c)),
+            Value::Bool(b) => Ok(ValueKey::Bool(*b)),
+            Value::List(vals) => Ok(ValueKey::List(keys(vals)?)),
+            Value::DottedList(vals, tail) => Ok(ValueKey::DottedList(
+                keys(vals)?,
+                Box::new(ValueKey::try_from(tail.as_ref())?),
+            )),
+            _ => Err(Error::TypeMismatch(
+                "hashable value".to_owned(),
+                val.clone(),
+            )),
+        }
+    }
+}
+
+impl ValueKey {
+    /// A hash of the key that is the same every time within a run, for
+    /// `equal-hash`.
+    pub fn hash_code(&self) -> i64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish() as i64
+    }
+}
+
+fn keys(vals: &[Value]) -> Result<Vec<ValueKey>> {
+    vals.iter().map(ValueKey::try_from).collect()
+}
+
+/// The entries of a hash table in insertion order, indexed by key.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HashTable {
+    entries: Vec<(Value, Value)>,
+    index: HashMap<ValueKey, usize>,
+}
+
+impl HashTable {
+    /// Sets the value for `key`, keeping the key's original position if it
+    /// was already present.
+    pub fn insert(&mut self, key: &Value, val: Value) -> Result<()> {
+        let hashed = ValueKey::try_from(key)?;
+        match self.index.get(&hashed) {
+            Some(&i) => self.entries[i].1 = val,
+            None => {
+                self.index.insert(hashed, self.entries.len());
+                self.entries.push((key.clone(), val));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<Value>> {
+        let hashed = ValueKey::try_from(key)?;
+        Ok(self.index.get(&hashed).map(|&i| self.entries[i].1.clone()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::ValueKey;
+    use crate::{
+        error::Error,
+        parser::parse_expr,
+        value::{PrimitiveFunc, Value},
+    };
+
+    fn key(input: &str) -> ValueKey {
+        ValueKey::try_from(&parse_expr(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn equal_keys_collide() {
+        let mut map = HashMap::new();
+        map.insert(key("(a \"b\" 1/2 (#\\c . #t))"), 1);
+        map.insert(key("(a \"b\" 2/4 (#\\c . #t))"), 2);
+        map.insert(key("(a \"b\" 1/3 (#\\c . #t))"), 3);
+        assert_eq!(2, map.len());
+        assert_eq!(Some(&2), map.get(&key("(a \"b\" 1/2 (#\\c . #t))")));
+        assert_ne!(key("a"), key("\"a\""));
+    }
+
+    #[test]
+    fn procedures_are_not_keys() {
+        let car = Value::PrimitiveFunc(PrimitiveFunc::Car);
+        let list = Value::List([Value::Number(1.into()), car.clone()].into());
+        let expected = Err(Error::TypeMismatch("hashable value".to_owned(), car));
+        assert_eq!(expected, ValueKey::try_from(&list));
+    }
+}
@@ -1,36 +1,154 @@
 use std::fmt::Display;
 
-use logos::{Lexer, Logos};
+use logos::{Lexer, Logos, Skip};
+
+use crate::{bigint::BigInt, number::Number, value::Value};
 
 fn lex_string(lex: &mut Lexer<Token>) -> Option<String> {
     let slice = lex.slice();
-    let string = slice[1..slice.len() - 1].to_owned();
-    Some(string)
+    unescape(&slice[1..slice.len() - 1])
+}
+
+/// Decodes the body of a string or `|...|` symbol. A backslash escapes the
+/// next character, with `\xHH;` giving one by its code point and `\n`, `\t`,
+/// `\r`, `\a` and `\b` the usual control characters.
+fn unescape(body: &str) -> Option<String> {
+    let mut chars = body.chars();
+    let mut decoded = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        let escaped = match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\u{7}',
+            'b' => '\u{8}',
+            'x' => {
+                let code: String = chars.by_ref().take_while(|c| *c != ';').collect();
+                char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+            }
+            c => c,
+        };
+        decoded.push(escaped);
+    }
+    Some(decoded)
+}
+
+/// Characters with a name in `#\name` syntax, shared with the writer.
+pub static CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("escape", '\u{1b}'),
+    ("delete", '\u{7f}'),
+];
+
+fn lex_char(lex: &mut Lexer<Token>) -> Option<char> {
+    // Only the `#\` prefix is matched by the token pattern so that multi-byte
+    // characters are consumed whole rather than split by the byte-level DFA.
+    let remainder = lex.remainder();
+    let first = remainder.chars().next()?;
+    let len = if first.is_ascii_alphanumeric() {
+        remainder
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(remainder.len())
+    } else {
+        first.len_utf8()
+    };
+    lex.bump(len);
+    let name = &remainder[..len];
+    if len == first.len_utf8() {
+        return Some(first);
+    }
+    if let Some((_, c)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*c);
+    }
+    let code = name.strip_prefix('x')?;
+    char::from_u32(u32::from_str_radix(code, 16).ok()?)
+}
+
+/// Reader settings changed by directives in the source being lexed.
+#[derive(Default)]
+pub struct ReaderState {
+    /// Set by `#!fold-case` and cleared by `#!no-fold-case`: whether plain
+    /// symbols are downcased as they are read.
+    fold_case: bool,
+}
+
+fn fold_case(lex: &mut Lexer<Token>) -> Skip {
+    lex.extras.fold_case = true;
+    Skip
+}
+
+fn no_fold_case(lex: &mut Lexer<Token>) -> Skip {
+    lex.extras.fold_case = false;
+    Skip
 }
 
 fn lex_atom(lex: &mut Lexer<Token>) -> Option<String> {
     let slice = lex.slice();
+    if lex.extras.fold_case {
+        return Some(slice.to_lowercase());
+    }
     let atom = slice[..slice.len()].to_owned();
     Some(atom)
 }
 
-fn lex_number(lex: &mut Lexer<Token>) -> Option<i64> {
+/// Reads a `|...|` symbol, whose name may hold any character, escaped as in
+/// strings.
+fn lex_bar_atom(lex: &mut Lexer<Token>) -> Option<String> {
+    let slice = lex.slice();
+    unescape(&slice[1..slice.len() - 1])
+}
+
+fn lex_number(lex: &mut Lexer<Token>) -> Option<Number> {
     let slice = lex.slice();
-    let number: i64 = slice[..slice.len()].parse().ok()?;
-    Some(number)
+    let number: BigInt = slice[..slice.len()].parse().ok()?;
+    Some(Number::Integer(number))
+}
+
+fn lex_rational(lex: &mut Lexer<Token>) -> Option<Number> {
+    let (num, den) = lex.slice().split_once('/')?;
+    Number::rational(num.parse().ok()?, den.parse().ok()?)
 }
 
 #[derive(Logos, Clone, Debug, PartialEq, Eq)]
 #[logos(subpattern symbol = r"[!#$%&|*+\-/:<=>?@^_~]")]
+#[logos(extras = ReaderState)]
 pub enum Token {
-    #[regex(r#""([^"\\]|\\t|\\u|\\n|\\")*""#, lex_string)]
+    #[regex(r#""([^"\\]|\\(.|\n))*""#, lex_string)]
     String(String),
-    #[regex(r#"([a-z]|(?&symbol))([a-z0-9]|(?&symbol))*"#, lex_atom)]
+    #[regex(r#"([a-zA-Z]|(?&symbol))([a-zA-Z0-9.]|(?&symbol))*"#, lex_atom)]
+    #[token("...", lex_atom)]
+    #[token("1+", lex_atom)]
+    #[token("1-", lex_atom)]
+    // `||` is left to the plain symbol pattern, as it names the `or` primitive.
+    #[regex(r#"\|([^|\\]|\\.)+\|"#, lex_bar_atom, priority = 3)]
     Atom(String),
-    #[regex(r#"[0-9]+"#, lex_number)]
-    Number(i64),
+    #[regex(r#"-?[0-9]+"#, lex_number, priority = 3)]
+    #[regex(r#"-?[0-9]+/[0-9]+"#, lex_rational, priority = 3)]
+    Number(Number),
+    #[token("#\\", lex_char)]
+    Char(char),
+    #[token("#u8(")]
+    BytevectorStart,
+    #[token("#(")]
+    VectorStart,
     #[token("'")]
     Quote,
+    #[token("`")]
+    Quasiquote,
+    #[token(",")]
+    Unquote,
+    #[token(",@")]
+    UnquoteSplicing,
     #[token(".")]
     Dot,
     #[token("(")]
@@ -39,6 +157,8 @@ pub enum Token {
     RParen,
     #[error]
     #[regex(r"[ \t\n\r\f]+", logos::skip)]
+    #[token("#!fold-case", fold_case)]
+    #[token("#!no-fold-case", no_fold_case)]
     Error,
 }
 
@@ -48,7 +168,13 @@ impl Display for Token {
             Token::String(s) => write!(f, "\"{}\"", s),
             Token::Atom(a) => write!(f, "{}", a),
             Token::Number(n) => write!(f, "{}", n),
+            Token::Char(c) => write!(f, "{}", Value::Char(*c)),
+            Token::BytevectorStart => write!(f, "#u8("),
+            Token::VectorStart => write!(f, "#("),
             Token::Quote => write!(f, "'"),
+            Token::Quasiquote => write!(f, "`"),
+            Token::Unquote => write!(f, ","),
+            Token::UnquoteSplicing => write!(f, ",@"),
             Token::Dot => write!(f, "."),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
@@ -57,25 +183,40 @@ impl Display for Token {
     }
 }
 
+/// Lexes `input` lazily, one token per call to `next`, so a parser can stop
+/// at the first error without lexing the rest.
+pub fn tokens(input: &str) -> Lexer<'_, Token> {
+    Token::lexer(input)
+}
+
+/// Collects every token of `input` up front.
+#[cfg(test)]
 pub fn lex(input: &str) -> Vec<Token> {
-    let lex = Token::lexer(input);
-    let mut tokens = Vec::new();
-    for token in lex {
-        tokens.push(token);
-    }
-    tokens
+    tokens(input).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::{lex, Token};
+    use crate::number::Number;
 
     #[test]
     fn string() {
-        let input = "\"this is a test\"";
-        let expected = vec![Token::String("this is a test".to_owned())];
-        let actual = lex(input);
-        assert_eq!(expected, actual);
+        let cases = vec![
+            ("\"this is a test\"", "this is a test"),
+            ("\"a\\nb\\tc\"", "a\nb\tc"),
+            ("\"say \\\"hi\\\"\"", "say \"hi\""),
+            ("\"back\\\\slash\"", "back\\slash"),
+            ("\"\\x3bb; \\a\"", "λ \u{7}"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                vec![Token::String(expected.to_owned())],
+                lex(input),
+                "{}",
+                input
+            );
+        }
     }
 
     #[test]
@@ -84,6 +225,25 @@ mod tests {
             ("a", vec![Token::Atom("a".to_owned())]),
             ("#e", vec![Token::Atom("#e".to_owned())]),
             ("@", vec![Token::Atom("@".to_owned())]),
+            ("<...>", vec![Token::Atom("<...>".to_owned())]),
+            ("...", vec![Token::Atom("...".to_owned())]),
+            ("1+", vec![Token::Atom("1+".to_owned())]),
+            ("1-", vec![Token::Atom("1-".to_owned())]),
+            (". x", vec![Token::Dot, Token::Atom("x".to_owned())]),
+            ("|hello world|", vec![Token::Atom("hello world".to_owned())]),
+            ("||", vec![Token::Atom("||".to_owned())]),
+            ("|a\\|b\\\\c|", vec![Token::Atom("a|b\\c".to_owned())]),
+            ("|\\x3bb;\\n|", vec![Token::Atom("λ\n".to_owned())]),
+            ("a|b", vec![Token::Atom("a|b".to_owned())]),
+            ("Foo", vec![Token::Atom("Foo".to_owned())]),
+            (
+                "#!fold-case FOO |Bar| #!no-fold-case Baz",
+                vec![
+                    Token::Atom("foo".to_owned()),
+                    Token::Atom("Bar".to_owned()),
+                    Token::Atom("Baz".to_owned()),
+                ],
+            ),
             (
                 "(a test)",
                 vec![
@@ -99,4 +259,55 @@ mod tests {
             assert_eq!(expected, actual);
         }
     }
+
+    #[test]
+    fn char() {
+        let cases = vec![
+            ("#\\a", vec![Token::Char('a')]),
+            ("#\\(", vec![Token::Char('(')]),
+            ("#\\space", vec![Token::Char(' ')]),
+            ("#\\newline", vec![Token::Char('\n')]),
+            ("#\\x3bb", vec![Token::Char('λ')]),
+            ("#\\λ", vec![Token::Char('λ')]),
+            (
+                "(#\\a #\\b)",
+                vec![
+                    Token::LParen,
+                    Token::Char('a'),
+                    Token::Char('b'),
+                    Token::RParen,
+                ],
+            ),
+        ];
+        for (input, expected) in cases {
+            let actual = lex(input);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn number() {
+        let cases = vec![
+            ("42", vec![Token::Number(42.into())]),
+            (
+                "6/10",
+                vec![Token::Number(Number::rational(3.into(), 5.into()).unwrap())],
+            ),
+            ("4/2", vec![Token::Number(2.into())]),
+            ("1/0", vec![Token::Error]),
+            ("-7", vec![Token::Number((-7).into())]),
+            (
+                "-6/10",
+                vec![Token::Number(
+                    Number::rational((-3).into(), 5.into()).unwrap(),
+                )],
+            ),
+            ("-", vec![Token::Atom("-".to_owned())]),
+            ("-7x", vec![Token::Atom("-7x".to_owned())]),
+        ];
+        for (input, expected) in cases {
+            let actual = lex(input);
+            assert_eq!(expected, actual);
+        }
+    }
 }
@@ -1,36 +1,191 @@
+#![allow(clippy::result_large_err)]
+
+use std::{io, path::Path, process::ExitCode, thread};
+
 use env::Env;
 use eval::eval;
-use parser::parse_expr;
-use repl::run;
+use optimize::fold_constants;
+use parser::parse_exprs;
+use repl::{eval_line, run};
 
+mod bigint;
 mod env;
 mod error;
 mod eval;
+mod hash;
 mod lexer;
+mod number;
+mod optimize;
 mod parser;
 mod primitive;
 mod repl;
+mod symbol;
+mod syntax;
 mod util;
 mod value;
 
-fn run_arg(arg: &str) {
-    match parse_expr(arg) {
-        Ok(value) => {
-            let mut env = Env::primitive_bindings();
-            match eval(&mut env, &value) {
-                Ok(value) => println!("{}", value),
-                Err(e) => println!("Eval error: {}", e),
-            }
+fn run_arg(mut env: Env, arg: &str) -> ExitCode {
+    match eval_line(&mut env, arg) {
+        Ok(output) => {
+            output.iter().for_each(|output| println!("{}", output));
+            ExitCode::SUCCESS
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// Reports an error on stderr, where it can't be mistaken for the program's
+/// own output.
+fn fail(message: impl std::fmt::Display) -> ExitCode {
+    eprintln!("{}", message);
+    ExitCode::FAILURE
+}
+
+/// What the command line asks to run. Arguments after a file or `-` are left
+/// for the program to read with `command-line`.
+#[derive(Debug, PartialEq, Eq)]
+enum Mode<'a> {
+    Repl,
+    Eval(&'a str),
+    File(&'a str),
+    Stdin,
+    Dump(&'a str),
+}
+
+static USAGE: &str = "\
+usage: scheme-rs              start the REPL
+       scheme-rs -e <expr>    evaluate an expression and print its value
+       scheme-rs <file> ...   run a program from a file
+       scheme-rs - ...        run a program read from stdin
+       scheme-rs -O <file> ...
+                              run a program with constant folding
+       scheme-rs --safe ...   refuse to redefine or set! primitives
+       scheme-rs --dump <file>
+                              print each form of a file as JSON";
+
+fn parse_args(args: &[String]) -> Option<Mode<'_>> {
+    match args {
+        [] => Some(Mode::Repl),
+        [flag, expr] if flag == "-e" => Some(Mode::Eval(expr)),
+        [flag, path] if flag == "--dump" => Some(Mode::Dump(path)),
+        [stdin, ..] if stdin == "-" => Some(Mode::Stdin),
+        [path, ..] if !path.starts_with('-') => Some(Mode::File(path)),
+        _ => None,
+    }
+}
+
+/// Evaluates every form of a program in order, stopping at the first error.
+/// With `optimize`, constant subexpressions of each form are folded just
+/// before it runs.
+fn run_program(mut env: Env, source: io::Result<String>, optimize: bool) -> ExitCode {
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => return fail(format_args!("IO error: {}", e)),
+    };
+    let forms = match parse_exprs(&source) {
+        Ok(forms) => forms,
+        Err(e) => return fail(format_args!("Parse error: {}", e)),
+    };
+    for mut form in forms {
+        if optimize {
+            form = fold_constants(&mut env, &form);
+        }
+        if let Err(e) = eval(&mut env, &form) {
+            return fail(format_args!("Eval error: {}", e));
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints the structure of every form of a source file, one JSON object per
+/// line, for tools that want the syntax tree without running anything.
+fn dump_forms(source: io::Result<String>) -> ExitCode {
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => return fail(format_args!("IO error: {}", e)),
+    };
+    match parse_exprs(&source) {
+        Ok(forms) => {
+            forms
+                .iter()
+                .for_each(|form| println!("{}", form.to_debug_json()));
+            ExitCode::SUCCESS
         }
-        Err(e) => println!("Parse error: {}", e),
+        Err(e) => fail(format_args!("Parse error: {}", e)),
     }
 }
 
-fn main() {
+/// The stack the interpreter runs on: deep enough that nested calls hit the
+/// `Env` depth limit, and fail with an error, before they overflow it.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+fn main() -> ExitCode {
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(start)
+        .expect("failed to start the interpreter thread");
+    interpreter.join().unwrap_or(ExitCode::from(101))
+}
+
+fn start() -> ExitCode {
     let args: Vec<String> = std::env::args().collect();
-    match &args[..] {
-        [_program] => run(),
-        [_program, arg] => run_arg(arg),
-        _ => println!("Pass no argument for repl, one argument for eval"),
+    let mut env = Env::primitive_bindings();
+    env.set_command_line(args.clone());
+    if let Some(load_path) = std::env::var_os("SCHEME_LOAD_PATH") {
+        for dir in std::env::split_paths(&load_path) {
+            env.add_to_load_path(dir);
+        }
+    }
+    let mut optimize = false;
+    let mut args = &args[1..];
+    loop {
+        match args {
+            [flag, ..] if flag == "-O" => optimize = true,
+            [flag, ..] if flag == "--safe" => env.set_safe_primitives(true),
+            _ => break,
+        }
+        args = &args[1..];
+    }
+    match parse_args(args) {
+        Some(Mode::Repl) => {
+            run(env);
+            ExitCode::SUCCESS
+        }
+        Some(Mode::Eval(expr)) => run_arg(env, expr),
+        Some(Mode::File(path)) => {
+            env.begin_load(Path::new(path));
+            run_program(env, std::fs::read_to_string(path), optimize)
+        }
+        Some(Mode::Stdin) => run_program(env, io::read_to_string(io::stdin()), optimize),
+        Some(Mode::Dump(path)) => dump_forms(std::fs::read_to_string(path)),
+        None => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, Mode};
+
+    #[test]
+    fn args() {
+        let cases = vec![
+            (vec![], Some(Mode::Repl)),
+            (vec!["-e", "(+ 1 2)"], Some(Mode::Eval("(+ 1 2)"))),
+            (vec!["prog.scm"], Some(Mode::File("prog.scm"))),
+            (vec!["prog.scm", "-e", "x"], Some(Mode::File("prog.scm"))),
+            (vec!["-"], Some(Mode::Stdin)),
+            (vec!["--dump", "prog.scm"], Some(Mode::Dump("prog.scm"))),
+            (vec!["--dump"], None),
+            (vec!["-e"], None),
+            (vec!["-e", "1", "2"], None),
+            (vec!["--help"], None),
+        ];
+        for (args, expected) in cases {
+            let args: Vec<String> = args.into_iter().map(str::to_owned).collect();
+            assert_eq!(expected, parse_args(&args), "{:?}", args);
+        }
     }
 }
@@ -0,0 +1,226 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+use crate::bigint::BigInt;
+
+/// An exact number. Rationals are always kept in lowest terms with a positive
+/// denominator greater than one, so the derived equality is numeric equality
+/// and a whole result is always an `Integer`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Number {
+    Integer(BigInt),
+    Rational { num: BigInt, den: BigInt },
+}
+
+impl Number {
+    /// Builds the normalized number `num / den`, or `None` if `den` is zero.
+    pub fn rational(num: BigInt, den: BigInt) -> Option<Number> {
+        if den.is_zero() {
+            return None;
+        }
+        let gcd = num.gcd(&den);
+        let (mut num, mut den) = (&num / &gcd, &den / &gcd);
+        if den.is_negative() {
+            num = -num;
+            den = -den;
+        }
+        if den == BigInt::from(1) {
+            return Some(Number::Integer(num));
+        }
+        Some(Number::Rational { num, den })
+    }
+
+    pub fn numerator(&self) -> BigInt {
+        match self {
+            Number::Integer(n) => n.clone(),
+            Number::Rational { num, .. } => num.clone(),
+        }
+    }
+
+    pub fn denominator(&self) -> BigInt {
+        match self {
+            Number::Integer(_) => BigInt::from(1),
+            Number::Rational { den, .. } => den.clone(),
+        }
+    }
+
+    /// The largest integer not greater than the number.
+    pub fn floor(&self) -> BigInt {
+        match self {
+            Number::Integer(n) => n.clone(),
+            Number::Rational { num, den } => num.div_rem_floor(den).0,
+        }
+    }
+
+    /// The rational with the smallest denominator in `[lo, hi]`, taking the
+    /// one nearest zero among those.
+    pub fn simplest_between(lo: Number, hi: Number) -> Number {
+        let zero = Number::from(0);
+        if lo > hi {
+            return Number::simplest_between(hi, lo);
+        }
+        if lo <= zero && zero <= hi {
+            return zero;
+        }
+        if hi < zero {
+            return -Number::simplest_between(-hi, -lo);
+        }
+        // Both bounds are positive: take the integer part of `lo` if an
+        // integer fits, otherwise recurse on the reciprocals of what's left.
+        let floor = Number::Integer(lo.floor());
+        if floor == lo {
+            return lo;
+        }
+        if floor < Number::Integer(hi.floor()) {
+            return floor + Number::from(1);
+        }
+        let one = Number::from(1);
+        let lo = one
+            .checked_div(&(lo - floor.clone()))
+            .expect("lo is above its floor");
+        let hi = one
+            .checked_div(&(hi - floor.clone()))
+            .expect("hi is above lo's floor");
+        let rest = Number::simplest_between(hi, lo);
+        floor + one.checked_div(&rest).expect("rest is positive")
+    }
+
+    /// Exact division, or `None` when dividing by zero.
+    pub fn checked_div(&self, rhs: &Number) -> Option<Number> {
+        Number::rational(
+            &self.numerator() * &rhs.denominator(),
+            &self.denominator() * &rhs.numerator(),
+        )
+    }
+}
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Self {
+        Number::Integer(n.into())
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(n: BigInt) -> Self {
+        Number::Integer(n)
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Integer(n) => write!(f, "{}", n),
+            Number::Rational { num, den } => write!(f, "{}/{}", num, den),
+        }
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Number::Integer(lhs), Number::Integer(rhs)) => lhs.cmp(rhs),
+            _ => (&self.numerator() * &other.denominator())
+                .cmp(&(&other.numerator() * &self.denominator())),
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, rhs: Number) -> Number {
+        match (&self, &rhs) {
+            (Number::Integer(lhs), Number::Integer(rhs)) => Number::Integer(lhs + rhs),
+            _ => Number::rational(
+                &(&self.numerator() * &rhs.denominator())
+                    + &(&rhs.numerator() * &self.denominator()),
+                &self.denominator() * &rhs.denominator(),
+            )
+            .expect("denominators are never zero"),
+        }
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Integer(n) => Number::Integer(-n),
+            Number::Rational { num, den } => Number::Rational { num: -num, den },
+        }
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, rhs: Number) -> Number {
+        self + -rhs
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, rhs: Number) -> Number {
+        match (&self, &rhs) {
+            (Number::Integer(lhs), Number::Integer(rhs)) => Number::Integer(lhs * rhs),
+            _ => Number::rational(
+                &self.numerator() * &rhs.numerator(),
+                &self.denominator() * &rhs.denominator(),
+            )
+            .expect("denominators are never zero"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Number;
+
+    fn ratio(num: i64, den: i64) -> Number {
+        Number::rational(num.into(), den.into()).unwrap()
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!("1/2", ratio(2, 4).to_string());
+        assert_eq!("-1/2", ratio(2, -4).to_string());
+        assert_eq!(Number::from(3), ratio(6, 2));
+        assert_eq!(None, Number::rational(1.into(), 0.into()));
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(ratio(5, 6), ratio(1, 2) + ratio(1, 3));
+        assert_eq!(Number::from(1), ratio(1, 2) + ratio(1, 2));
+        assert_eq!(ratio(7, 2), Number::from(3) + ratio(1, 2));
+        assert_eq!(ratio(-1, 6), ratio(1, 3) - ratio(1, 2));
+        assert_eq!(Number::from(2), ratio(4, 3) * ratio(3, 2));
+        assert_eq!(Some(ratio(1, 3)), Number::from(1).checked_div(&3.into()));
+        assert_eq!(None, Number::from(1).checked_div(&0.into()));
+        assert!(ratio(1, 3) < ratio(1, 2));
+        assert!(ratio(-1, 2) < Number::from(0));
+    }
+
+    #[test]
+    fn simplest_between() {
+        let simplest = Number::simplest_between;
+        assert_eq!(ratio(1, 3), simplest(ratio(1, 5), ratio(2, 5)));
+        assert_eq!(Number::from(2), simplest(ratio(3, 2), ratio(7, 2)));
+        assert_eq!(Number::from(0), simplest(ratio(-1, 4), ratio(3, 4)));
+        assert_eq!(ratio(-1, 3), simplest(ratio(-2, 5), ratio(-1, 5)));
+        assert_eq!(ratio(3, 7), simplest(ratio(3, 7), ratio(3, 7)));
+        assert_eq!(Number::from(-3), ratio(-5, 2).floor().into());
+    }
+}
@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use crate::{
+    env::Env,
+    eval::{apply, is_special_form},
+    value::{PrimitiveFunc, Value, QUOTE},
+};
+
+/// Special forms whose subexpressions are all evaluated in the enclosing
+/// scope, so folding inside them can't be confused by a local binding.
+static TRANSPARENT_FORMS: &[&str] = &["if", "and", "or", "when", "unless", "assert"];
+
+/// Primitives whose result depends only on their arguments, so a call with
+/// constant arguments can be made once, ahead of time.
+fn is_pure(func: &PrimitiveFunc) -> bool {
+    use PrimitiveFunc::*;
+    matches!(
+        func,
+        Add | Sub
+            | Mul
+            | Div
+            | Quotient
+            | Rem
+            | FloorQuotient
+            | FloorRemainder
+            | Increment
+            | Decrement
+            | IsInteger
+            | IsRational
+            | IsReal
+            | IsExact
+            | IsInexact
+            | IsExactInteger
+            | IsExactNonnegativeInteger
+            | Numerator
+            | Denominator
+            | Eq
+            | Lt
+            | Gt
+            | Ne
+            | Ge
+            | Le
+            | Not
+            | StringEq
+            | StringLt
+            | StringGt
+            | StringLe
+            | StringGe
+            | CharToInteger
+            | IntegerToChar
+            | CharUpcase
+            | CharDowncase
+            | CharFoldcase
+            | EqualHash
+            | StringFoldcase
+            | StringLength
+            | StringRef
+            | Substring
+    )
+}
+
+/// Values that evaluate to themselves.
+fn is_constant(val: &Value) -> bool {
+    matches!(
+        val,
+        Value::Number(_) | Value::String(_) | Value::Char(_) | Value::Bool(_)
+    )
+}
+
+/// Replaces calls to pure primitives whose arguments are all constants with
+/// their results, and quoted self-evaluating data with the data itself.
+/// Operators are looked up in `env` as it is when the pass runs, so a name
+/// rebound to something other than the primitive is left alone. Anything
+/// that binds names, such as `lambda`, is not looked into, and a call that
+/// fails is kept so that it fails when the program gets to it.
+pub fn fold_constants(env: &mut Env, val: &Value) -> Value {
+    let Value::List(vals) = val else {
+        return val.clone();
+    };
+    match &vals[..] {
+        [Value::Atom(atom), datum] if atom == QUOTE && is_constant(datum) => datum.clone(),
+        [Value::Atom(atom), args @ ..] if TRANSPARENT_FORMS.contains(&atom.as_str()) => {
+            let args = args.iter().map(|arg| fold_constants(env, arg));
+            Value::List(std::iter::once(vals[0].clone()).chain(args).collect())
+        }
+        [define @ Value::Atom(atom), var @ Value::Atom(_), expr]
+            if ["define", "define-constant", "set!"].contains(&atom.as_str()) =>
+        {
+            let expr = fold_constants(env, expr);
+            Value::List(Rc::new([define.clone(), var.clone(), expr]))
+        }
+        [Value::Atom(atom), ..] if is_special_form(atom.as_str()) => val.clone(),
+        // A macro decides what its arguments mean, so they may not be
+        // expressions at all.
+        [Value::Atom(atom), ..] if env.is_macro(atom.as_str()) => val.clone(),
+        [head, args @ ..] => {
+            let args: Vec<Value> = args.iter().map(|arg| fold_constants(env, arg)).collect();
+            if let Some(folded) = fold_call(env, head, &args) {
+                return folded;
+            }
+            let head = fold_constants(env, head);
+            Value::List(std::iter::once(head).chain(args).collect())
+        }
+        [] => val.clone(),
+    }
+}
+
+fn fold_call(env: &mut Env, head: &Value, args: &[Value]) -> Option<Value> {
+    let Value::Atom(name) = head else {
+        return None;
+    };
+    let func = match env.get_var(name.as_str()) {
+        Ok(func @ Value::PrimitiveFunc(primitive)) if is_pure(primitive) => func.clone(),
+        _ => return None,
+    };
+    if !args.iter().all(is_constant) {
+        return None;
+    }
+    apply(env, &func, args).ok().filter(is_constant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fold_constants;
+    use crate::{env::Env, eval::eval, parser::parse_expr};
+
+    #[test]
+    fn folds_constant_calls() {
+        let cases = vec![
+            ("(+ 1 2)", "3"),
+            ("(* (+ 1 2) (- 10 4))", "18"),
+            ("(if (< 1 2) (+ 1 1) 'no)", "(if #t 2 'no)"),
+            ("(define x (/ 6 4))", "(define x 3/2)"),
+            ("(cons (string=? \"a\" \"a\") '())", "(cons #t '())"),
+            ("'7", "7"),
+            ("(not '#f)", "#t"),
+        ];
+        let mut env = Env::primitive_bindings();
+        for (input, expected) in cases {
+            let val = parse_expr(input).unwrap();
+            let folded = fold_constants(&mut env, &val);
+            assert_eq!(expected, folded.to_string(), "{}", input);
+            let mut unfolded_env = Env::primitive_bindings();
+            let mut folded_env = Env::primitive_bindings();
+            assert_eq!(
+                eval(&mut unfolded_env, &val).map(|val| val.to_string()),
+                eval(&mut folded_env, &folded).map(|val| val.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_other_expressions() {
+        let mut env = Env::primitive_bindings();
+        eval(&mut env, &parse_expr("(define (sub a b) (+ a b))").unwrap()).unwrap();
+        eval(&mut env, &parse_expr("(define - sub)").unwrap()).unwrap();
+        let quoter = "(define-syntax q (syntax-rules () ((_ e) 'e)))";
+        eval(&mut env, &parse_expr(quoter).unwrap()).unwrap();
+        let cases = vec![
+            "(+ x 1)",
+            "(- 5 1)",
+            "(/ 1 0)",
+            "'(+ 1 2)",
+            "(lambda (+) (+ 1 2))",
+            "(car '(1 2))",
+            "(display 1)",
+            "(q (+ 1 2))",
+            "(display (q (* 2 3)))",
+        ];
+        for input in cases {
+            let val = parse_expr(input).unwrap();
+            assert_eq!(val, fold_constants(&mut env, &val), "{}", input);
+        }
+    }
+}
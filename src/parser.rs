@@ -1,8 +1,12 @@
-use std::iter::Peekable;
+use std::{cell::Cell, iter::Peekable, rc::Rc};
+
+use logos::Logos;
 
 use crate::{
     error::ParserError,
     lexer::{self, Token},
+    number::Number,
+    symbol::Symbol,
     value::{Value, FALSE, QUOTE, TRUE},
 };
 
@@ -40,7 +44,7 @@ fn parse_atom<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Val
         Some(Token::Atom(atom)) => match atom.as_str() {
             atom if atom == TRUE => Ok(Value::Bool(true)),
             atom if atom == FALSE => Ok(Value::Bool(false)),
-            _ => Ok(Value::Atom(atom)),
+            _ => Ok(Value::Atom(Symbol::new(&atom))),
         },
         Some(token) => Err(ParserError::UnexpectedToken(token)),
         None => Err(ParserError::NoMoreTokens),
@@ -55,10 +59,23 @@ fn parse_number<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<V
     }
 }
 
-fn parse_quoted<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Value> {
-    expect_token(Token::Quote, tokens)?;
+fn parse_char<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Value> {
+    match tokens.next() {
+        Some(Token::Char(c)) => Ok(Value::Char(c)),
+        Some(token) => Err(ParserError::UnexpectedToken(token)),
+        None => Err(ParserError::NoMoreTokens),
+    }
+}
+
+/// Parses an abbreviated form such as `'datum` into `(name datum)`.
+fn parse_quoted<T: Iterator<Item = Token>>(
+    token: Token,
+    name: &str,
+    tokens: &mut Peekable<T>,
+) -> Result<Value> {
+    expect_token(token, tokens)?;
     let expr = parse_expr_impl(tokens)?;
-    Ok(Value::List(vec![Value::Atom(QUOTE.to_owned()), expr]))
+    Ok(Value::List(Rc::new([Value::Atom(name.into()), expr])))
 }
 
 fn parse_any_list<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Value> {
@@ -68,13 +85,13 @@ fn parse_any_list<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result
         match tokens.peek() {
             Some(Token::RParen) => {
                 expect_token(Token::RParen, tokens)?;
-                return Ok(Value::List(values));
+                return Ok(Value::List(values.into()));
             }
             Some(Token::Dot) => {
                 expect_token(Token::Dot, tokens)?;
                 let last = parse_expr_impl(tokens)?;
                 expect_token(Token::RParen, tokens)?;
-                return Ok(Value::DottedList(values, Box::new(last)));
+                return Ok(Value::DottedList(values.into(), Rc::new(last)));
             }
             Some(_) => {
                 let value = parse_expr_impl(tokens)?;
@@ -87,30 +104,75 @@ fn parse_any_list<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result
     }
 }
 
+fn parse_bytevector<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Value> {
+    expect_token(Token::BytevectorStart, tokens)?;
+    let mut bytes = Vec::new();
+    loop {
+        match tokens.next() {
+            Some(Token::RParen) => return Ok(Value::Bytevector(Rc::new(bytes.into()))),
+            Some(Token::Number(Number::Integer(n))) => {
+                let byte = n.to_i64().and_then(|n| u8::try_from(n).ok());
+                match byte {
+                    Some(byte) => bytes.push(byte),
+                    None => {
+                        let token = Token::Number(Number::Integer(n));
+                        return Err(ParserError::UnexpectedToken(token));
+                    }
+                }
+            }
+            Some(token) => return Err(ParserError::UnexpectedToken(token)),
+            None => return Err(ParserError::NoMoreTokens),
+        }
+    }
+}
+
+fn parse_vector<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Value> {
+    expect_token(Token::VectorStart, tokens)?;
+    let mut vals = Vec::new();
+    loop {
+        match tokens.peek() {
+            Some(Token::RParen) => {
+                tokens.next();
+                return Ok(Value::Vector(Rc::new(vals.into())));
+            }
+            Some(_) => vals.push(parse_expr_impl(tokens)?),
+            None => return Err(ParserError::NoMoreTokens),
+        }
+    }
+}
+
 fn parse_expr_impl<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> Result<Value> {
     match tokens.peek() {
         Some(Token::Atom(_)) => parse_atom(tokens),
         Some(Token::String(_)) => parse_string(tokens),
         Some(Token::Number(_)) => parse_number(tokens),
-        Some(Token::Quote) => parse_quoted(tokens),
+        Some(Token::Char(_)) => parse_char(tokens),
+        Some(Token::Quote) => parse_quoted(Token::Quote, QUOTE, tokens),
+        Some(Token::Quasiquote) => parse_quoted(Token::Quasiquote, "quasiquote", tokens),
+        Some(Token::Unquote) => parse_quoted(Token::Unquote, "unquote", tokens),
+        Some(Token::UnquoteSplicing) => {
+            parse_quoted(Token::UnquoteSplicing, "unquote-splicing", tokens)
+        }
         Some(Token::LParen) => parse_any_list(tokens),
+        Some(Token::BytevectorStart) => parse_bytevector(tokens),
+        Some(Token::VectorStart) => parse_vector(tokens),
         Some(token) => Err(ParserError::UnexpectedToken(token.clone())),
         None => Err(ParserError::NoMoreTokens),
     }
 }
 
 pub fn parse_expr(input: &str) -> Result<Value> {
-    let mut tokens = lexer::lex(input).into_iter().peekable();
+    let mut tokens = lexer::tokens(input).peekable();
     let value = parse_expr_impl(&mut tokens)?;
     check_tokens_left(&mut tokens)?;
     Ok(value)
 }
 
 pub fn parse_exprs(input: &str) -> Result<Vec<Value>> {
-    let mut tokens = lexer::lex(input).into_iter().peekable();
+    let mut tokens = lexer::tokens(input).peekable();
     let mut vals = Vec::new();
     loop {
-        if None == tokens.peek() {
+        if tokens.peek().is_none() {
             break;
         }
         let val = parse_expr_impl(&mut tokens)?;
@@ -119,61 +181,261 @@ pub fn parse_exprs(input: &str) -> Result<Vec<Value>> {
     Ok(vals)
 }
 
+/// Parses the first datum of `input`, returning it along with the number of
+/// bytes it spans so a port can resume reading right after it. Returns `None`
+/// when `input` holds no more tokens.
+pub fn parse_datum(input: &str) -> Result<Option<(Value, usize)>> {
+    // The parser never peeks past the closing token of a datum, so the end of
+    // the last token pulled from the lexer is also the end of the datum.
+    let end = Cell::new(0);
+    let mut tokens = Token::lexer(input)
+        .spanned()
+        .map(|(token, span)| {
+            end.set(span.end);
+            token
+        })
+        .peekable();
+    if tokens.peek().is_none() {
+        return Ok(None);
+    }
+    let value = parse_expr_impl(&mut tokens)?;
+    Ok(Some((value, end.get())))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{error::ParserError, value::Value};
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        error::{Error, ParserError},
+        lexer::Token,
+        number::Number,
+        value::{PrimitiveFunc, Value},
+    };
 
     #[test]
     fn parse() {
         let cases = vec![
             (
                 "(a test)",
-                Ok(Value::List(vec![
-                    Value::Atom("a".to_owned()),
-                    Value::Atom("test".to_owned()),
-                ])),
+                Ok(Value::List(Rc::new([
+                    Value::Atom("a".into()),
+                    Value::Atom("test".into()),
+                ]))),
             ),
             (
                 "(a (nested) test)",
-                Ok(Value::List(vec![
-                    Value::Atom("a".to_owned()),
-                    Value::List(vec![Value::Atom("nested".to_owned())]),
-                    Value::Atom("test".to_owned()),
-                ])),
+                Ok(Value::List(Rc::new([
+                    Value::Atom("a".into()),
+                    Value::List(Rc::new([Value::Atom("nested".into())])),
+                    Value::Atom("test".into()),
+                ]))),
             ),
             (
                 "(a (dotted . list) test)",
-                Ok(Value::List(vec![
-                    Value::Atom("a".to_owned()),
+                Ok(Value::List(Rc::new([
+                    Value::Atom("a".into()),
                     Value::DottedList(
-                        vec![Value::Atom("dotted".to_owned())],
-                        Box::new(Value::Atom("list".to_owned())),
+                        Rc::new([Value::Atom("dotted".into())]),
+                        Rc::new(Value::Atom("list".into())),
                     ),
-                    Value::Atom("test".to_owned()),
-                ])),
+                    Value::Atom("test".into()),
+                ]))),
             ),
             (
                 "(a '(quoted (dotted . list)) test)",
-                Ok(Value::List(vec![
-                    Value::Atom("a".to_owned()),
-                    Value::List(vec![
-                        Value::Atom("quote".to_owned()),
-                        Value::List(vec![
-                            Value::Atom("quoted".to_owned()),
+                Ok(Value::List(Rc::new([
+                    Value::Atom("a".into()),
+                    Value::List(Rc::new([
+                        Value::Atom("quote".into()),
+                        Value::List(Rc::new([
+                            Value::Atom("quoted".into()),
                             Value::DottedList(
-                                vec![Value::Atom("dotted".to_owned())],
-                                Box::new(Value::Atom("list".to_owned())),
+                                Rc::new([Value::Atom("dotted".into())]),
+                                Rc::new(Value::Atom("list".into())),
                             ),
-                        ]),
-                    ]),
-                    Value::Atom("test".to_owned()),
-                ])),
+                        ])),
+                    ])),
+                    Value::Atom("test".into()),
+                ]))),
+            ),
+            (
+                "`(a ,@b)",
+                Ok(Value::List(Rc::new([
+                    Value::Atom("quasiquote".into()),
+                    Value::List(Rc::new([
+                        Value::Atom("a".into()),
+                        Value::List(Rc::new([
+                            Value::Atom("unquote-splicing".into()),
+                            Value::Atom("b".into()),
+                        ])),
+                    ])),
+                ]))),
+            ),
+            (
+                "(|hello world| x)",
+                Ok(Value::List(Rc::new([
+                    Value::Atom("hello world".into()),
+                    Value::Atom("x".into()),
+                ]))),
+            ),
+            (
+                "#!fold-case (FOO foo)",
+                Ok(Value::List(Rc::new([
+                    Value::Atom("foo".into()),
+                    Value::Atom("foo".into()),
+                ]))),
+            ),
+            (
+                "#(1 (a) #\\b)",
+                Ok(Value::Vector(Rc::new(
+                    vec![
+                        Value::Number(1.into()),
+                        Value::List(Rc::new([Value::Atom("a".into())])),
+                        Value::Char('b'),
+                    ]
+                    .into(),
+                ))),
             ),
             ("(a '(imbalanced parens)", Err(ParserError::NoMoreTokens)),
+            (
+                "#u8(1 2 255)",
+                Ok(Value::Bytevector(Rc::new(vec![1, 2, 255].into()))),
+            ),
+            (
+                "#u8(256)",
+                Err(ParserError::UnexpectedToken(Token::Number(256.into()))),
+            ),
+            (
+                "#u8(a)",
+                Err(ParserError::UnexpectedToken(Token::Atom("a".to_owned()))),
+            ),
         ];
         for (input, expected) in cases {
             let actual = super::parse_expr(input);
             assert_eq!(expected, actual);
         }
     }
+
+    #[test]
+    fn parse_many() {
+        let input: String = (0..10000)
+            .map(|i| format!("(define x{} '(a \"b\" {}))\n", i, i))
+            .collect();
+        let vals = super::parse_exprs(&input).unwrap();
+        assert_eq!(10000, vals.len());
+        assert_eq!("(define x9999 '(a \"b\" 9999))", vals[9999].to_string());
+
+        let input = format!("(a) ) {}", input);
+        let expected = Err(ParserError::UnexpectedToken(Token::RParen));
+        assert_eq!(expected, super::parse_exprs(&input));
+    }
+
+    /// A small deterministic generator of serializable values, to cover many
+    /// shapes without pulling in a property-testing crate.
+    struct Gen(u64);
+
+    impl Gen {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % n
+        }
+
+        fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+            items[self.below(items.len() as u64) as usize].clone()
+        }
+
+        fn values(&mut self, depth: u32, max: u64) -> Vec<Value> {
+            (0..self.below(max))
+                .map(|_| self.value(depth - 1))
+                .collect()
+        }
+
+        fn value(&mut self, depth: u32) -> Value {
+            match self.below(if depth == 0 { 6 } else { 10 }) {
+                0 => Value::Atom(
+                    self.pick(&[
+                        "a",
+                        "foo",
+                        "set!",
+                        "x.y",
+                        "<=?",
+                        "-x",
+                        "hello world",
+                        "Big",
+                        "1+",
+                        ".",
+                        "a|b",
+                        "|x|",
+                        "back\\slash",
+                        "new\nline",
+                    ])
+                    .into(),
+                ),
+                1 => {
+                    let num = self.below(2000) as i64 - 1000;
+                    match self.below(2) {
+                        0 => Value::Number(num.into()),
+                        _ => {
+                            let den = self.below(20) as i64 + 1;
+                            Value::Number(Number::rational(num.into(), den.into()).unwrap())
+                        }
+                    }
+                }
+                2 => Value::String(
+                    self.pick(&[
+                        "",
+                        "hello world",
+                        "tab\t",
+                        "q\"uote",
+                        "back\\slash",
+                        "\\\"",
+                        "new\nline",
+                        "\u{1}λ",
+                    ])
+                    .to_owned(),
+                ),
+                3 => Value::Char(self.pick(&['a', 'x', ' ', '\n', '\u{1}', '(', 'λ'])),
+                4 => Value::Bool(self.below(2) == 0),
+                5 => {
+                    let bytes = (0..self.below(4)).map(|_| self.below(256) as u8).collect();
+                    Value::Bytevector(Rc::new(RefCell::new(bytes)))
+                }
+                6 => Value::List(self.values(depth, 4).into()),
+                9 => Value::Vector(Rc::new(self.values(depth, 4).into())),
+                7 => {
+                    let mut head = self.values(depth, 3);
+                    head.push(self.value(depth - 1));
+                    let tail = self.value(depth - 1);
+                    Value::DottedList(head.into(), Rc::new(tail))
+                }
+                _ => {
+                    let name = self.pick(&["quote", "quasiquote", "unquote", "unquote-splicing"]);
+                    Value::List(Rc::new([Value::Atom(name.into()), self.value(depth - 1)]))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unparse_roundtrip() {
+        let mut gen = Gen(42);
+        for _ in 0..2000 {
+            let val = gen.value(4);
+            let source = val.unparse().unwrap();
+            assert_eq!(Ok(val), super::parse_expr(&source), "{}", source);
+        }
+
+        let symbol = Value::Atom("hello world".into());
+        assert_eq!("|hello world|", symbol.unparse().unwrap());
+        assert_eq!("hello world", symbol.to_display_string());
+
+        let unwritable = Value::PrimitiveFunc(PrimitiveFunc::Car);
+        let list = Value::List(Rc::new([Value::Bool(true), unwritable.clone()]));
+        assert_eq!(Err(Error::Unwritable(unwritable)), list.unparse());
+        assert_eq!(Err(Error::Unwritable(Value::Eof)), Value::Eof.unparse());
+    }
 }
@@ -1,23 +1,32 @@
-use std::io::{stdin, BufRead, Write};
+use std::{
+    cell::RefCell,
+    io::{stdin, stdout, BufRead, IsTerminal, Write},
+    path::Path,
+    rc::Rc,
+};
 
 use crate::{
+    bigint::BigInt,
     env::Env,
-    error::Error,
-    eval::apply,
-    parser::{parse_expr, parse_exprs},
-    value::Value,
+    error::{Arity, Error},
+    eval::{apply, eval},
+    hash::{HashTable, ValueKey},
+    number::Number,
+    parser::{parse_datum, parse_expr, parse_exprs},
+    symbol::Symbol,
+    value::{lambda_list, NativeClosure, PromiseState, Value},
 };
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn as_number(val: &Value) -> Result<i64> {
+fn as_number(val: &Value) -> Result<Number> {
     match val {
-        Value::Number(number) => Ok(*number),
+        Value::Number(number) => Ok(number.clone()),
         Value::String(string) => {
-            let number: i64 = string
+            let number: BigInt = string
                 .parse()
                 .map_err(|_| Error::TypeMismatch("number".to_owned(), val.clone()))?;
-            Ok(number)
+            Ok(Number::Integer(number))
         }
         _ => Err(Error::TypeMismatch("number".to_owned(), val.clone())),
     }
@@ -32,6 +41,26 @@ fn as_string(val: &Value) -> Result<String> {
     }
 }
 
+fn as_index(val: &Value) -> Result<usize> {
+    match val {
+        Value::Number(Number::Integer(n)) => n
+            .to_i64()
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| Error::TypeMismatch("index".to_owned(), val.clone())),
+        _ => Err(Error::TypeMismatch("index".to_owned(), val.clone())),
+    }
+}
+
+fn as_byte(val: &Value) -> Result<u8> {
+    match val {
+        Value::Number(Number::Integer(n)) => n
+            .to_i64()
+            .and_then(|n| u8::try_from(n).ok())
+            .ok_or_else(|| Error::TypeMismatch("byte".to_owned(), val.clone())),
+        _ => Err(Error::TypeMismatch("byte".to_owned(), val.clone())),
+    }
+}
+
 fn as_bool(val: &Value) -> Result<bool> {
     match val {
         Value::Bool(bool) => Ok(*bool),
@@ -41,16 +70,17 @@ fn as_bool(val: &Value) -> Result<bool> {
 
 pub fn numeric_bool_binop<F>(vals: &[Value], f: F) -> Result<Value>
 where
-    F: Fn(i64, i64) -> bool,
+    F: Fn(Number, Number) -> bool,
 {
     bool_binop(vals, as_number, f)
 }
 
+/// Combines the truthiness of two values, so any value can be an operand.
 pub fn bool_bool_binop<F>(vals: &[Value], f: F) -> Result<Value>
 where
     F: Fn(bool, bool) -> bool,
 {
-    bool_binop(vals, as_bool, f)
+    bool_binop(vals, |val| Ok(val.is_truthy()), f)
 }
 
 pub fn string_bool_binop<F>(vals: &[Value], f: F) -> Result<Value>
@@ -78,22 +108,182 @@ where
 
 pub fn numeric_binop<F>(vals: &[Value], f: F) -> Result<Value>
 where
-    F: FnMut(i64, i64) -> i64,
+    F: FnMut(Number, Number) -> Result<Number>,
 {
     match vals {
         [] => Err(Error::NumArgs(2, vec![])),
         [val] => Err(Error::NumArgs(2, vec![val.clone()])),
-        _ => {
-            let num_vals = vals.iter().map(as_number).collect::<Result<Vec<_>>>()?;
-            let result = num_vals
-                .into_iter()
-                .reduce(f)
-                .ok_or_else(|| Error::NumArgs(2, vals.to_vec()))?;
+        [first, rest @ ..] => {
+            let first = as_number(first)?;
+            let rest = rest.iter().map(as_number).collect::<Result<Vec<_>>>()?;
+            let result = rest.into_iter().try_fold(first, f)?;
             Ok(Value::Number(result))
         }
     }
 }
 
+/// Folds `f` over the arguments starting from `identity`, so that `(+)` and
+/// `(*)` give their identity and a single argument is returned unchanged.
+pub fn numeric_fold<F>(vals: &[Value], identity: Number, f: F) -> Result<Value>
+where
+    F: FnMut(Number, Number) -> Result<Number>,
+{
+    let vals = vals.iter().map(as_number).collect::<Result<Vec<_>>>()?;
+    let result = vals.into_iter().try_fold(identity, f)?;
+    Ok(Value::Number(result))
+}
+
+/// `-`, which negates a single argument.
+pub fn sub(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Number(-as_number(val)?)),
+        _ => numeric_binop(vals, |acc, val| Ok(acc - val)),
+    }
+}
+
+/// `/`, which takes the reciprocal of a single argument. Integers divide
+/// into exact rationals, so `(/ 2)` is `1/2`.
+pub fn div(vals: &[Value]) -> Result<Value> {
+    let checked_div = |acc: Number, val: Number| acc.checked_div(&val).ok_or(Error::DivByZero);
+    match vals {
+        [val] => Ok(Value::Number(checked_div(1.into(), as_number(val)?)?)),
+        _ => numeric_binop(vals, checked_div),
+    }
+}
+
+/// Like `numeric_binop` but for the integer division family, which rejects
+/// rationals and a zero divisor. Numbers are kept in lowest terms, so a
+/// whole value written as a fraction, such as `14/2`, is the integer it
+/// equals and is accepted; only a value with a fractional part is refused.
+pub fn integer_div_binop<F>(vals: &[Value], f: F) -> Result<Value>
+where
+    F: Fn(&BigInt, &BigInt) -> BigInt,
+{
+    numeric_binop(vals, |acc, val| match (acc, val) {
+        (Number::Integer(_), Number::Integer(rhs)) if rhs.is_zero() => Err(Error::DivByZero),
+        (Number::Integer(lhs), Number::Integer(rhs)) => Ok(Number::Integer(f(&lhs, &rhs))),
+        (Number::Integer(_), val) | (val, _) => Err(Error::TypeMismatch(
+            "integer".to_owned(),
+            Value::Number(val),
+        )),
+    })
+}
+
+/// Like `integer_div_binop` but for the `/` variants, which divide exactly two
+/// integers and return the quotient and remainder as two values.
+pub fn integer_div_values<F>(vals: &[Value], f: F) -> Result<Value>
+where
+    F: Fn(&BigInt, &BigInt) -> (BigInt, BigInt),
+{
+    match vals {
+        [Value::Number(Number::Integer(_)), Value::Number(Number::Integer(rhs))]
+            if rhs.is_zero() =>
+        {
+            Err(Error::DivByZero)
+        }
+        [Value::Number(Number::Integer(lhs)), Value::Number(Number::Integer(rhs))] => {
+            let (quotient, rem) = f(lhs, rhs);
+            Ok(Value::Values(vec![
+                Value::Number(Number::Integer(quotient)),
+                Value::Number(Number::Integer(rem)),
+            ]))
+        }
+        [Value::Number(Number::Integer(_)), val] | [val, _] => {
+            Err(Error::TypeMismatch("integer".to_owned(), val.clone()))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// The integer square root `s` and remainder `r` of `n`, as two values, with
+/// `s*s + r = n`.
+pub fn exact_integer_sqrt(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Number(Number::Integer(n))] if !n.is_negative() => {
+            let root = n.sqrt();
+            let rem = n - &(&root * &root);
+            Ok(Value::Values(vec![
+                Value::Number(Number::Integer(root)),
+                Value::Number(Number::Integer(rem)),
+            ]))
+        }
+        [val] => Err(Error::TypeMismatch(
+            "non-negative integer".to_owned(),
+            val.clone(),
+        )),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// `1+` and `1-`, which add `delta` to a number.
+pub fn step(vals: &[Value], delta: i64) -> Result<Value> {
+    match vals {
+        [Value::Number(n)] => Ok(Value::Number(n.clone() + delta.into())),
+        [val] => Err(Error::TypeMismatch("number".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// For `exact?` and `inexact?`, which have the same answer for every number
+/// because every number is exact. These only check that they were given a
+/// number.
+pub fn exact_number_is(vals: &[Value], answer: bool) -> Result<Value> {
+    match vals {
+        [Value::Number(_)] => Ok(Value::Bool(answer)),
+        [val] => Err(Error::TypeMismatch("number".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// A numeric type predicate, such as `integer?`, which is false for
+/// anything that isn't a number.
+pub fn is_number_where(vals: &[Value], pred: impl Fn(&Number) -> bool) -> Result<Value> {
+    match vals {
+        [Value::Number(n)] => Ok(Value::Bool(pred(n))),
+        [_] => Ok(Value::Bool(false)),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// Coerces any value to a boolean: only `#f` is false.
+pub fn boolean(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Bool(val.is_truthy())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn numerator(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Number(n)] => Ok(Value::Number(Number::Integer(n.numerator()))),
+        [val] => Err(Error::TypeMismatch("number".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn denominator(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Number(n)] => Ok(Value::Number(Number::Integer(n.denominator()))),
+        [val] => Err(Error::TypeMismatch("number".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// The simplest rational within `tolerance` of `x`.
+pub fn rationalize(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Number(x), Value::Number(tolerance)] => {
+            let lo = x.clone() - tolerance.clone();
+            let hi = x.clone() + tolerance.clone();
+            Ok(Value::Number(Number::simplest_between(lo, hi)))
+        }
+        [Value::Number(_), val] | [val, _] => {
+            Err(Error::TypeMismatch("number".to_owned(), val.clone()))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
 pub fn car(vals: &[Value]) -> Result<Value> {
     match vals {
         [val @ Value::List(vals)] => match &vals[..] {
@@ -112,11 +302,12 @@ pub fn car(vals: &[Value]) -> Result<Value> {
 pub fn cdr(vals: &[Value]) -> Result<Value> {
     match vals {
         [val @ Value::List(vals)] => match &vals[..] {
-            [_, vals @ ..] => Ok(Value::List(vals.to_vec())),
+            [_, vals @ ..] => Ok(Value::List(vals.into())),
             _ => Err(Error::TypeMismatch("pair".to_owned(), val.clone())),
         },
         [val @ Value::DottedList(vals, dval)] => match &vals[..] {
-            [_, vals @ ..] => Ok(Value::DottedList(vals.to_vec(), dval.clone())),
+            [_] => Ok(dval.as_ref().clone()),
+            [_, vals @ ..] => Ok(Value::DottedList(vals.into(), dval.clone())),
             _ => Err(Error::TypeMismatch("pair".to_owned(), val.clone())),
         },
         [val] => Err(Error::TypeMismatch("pair".to_owned(), val.clone())),
@@ -127,16 +318,17 @@ pub fn cdr(vals: &[Value]) -> Result<Value> {
 pub fn cons(vals: &[Value]) -> Result<Value> {
     match vals {
         [val, Value::List(vals)] => {
-            let mut vals = vals.clone();
-            vals.insert(0, val.clone());
+            let vals = std::iter::once(val).chain(vals.iter()).cloned().collect();
             Ok(Value::List(vals))
         }
         [val, Value::DottedList(vals, dval)] => {
-            let mut vals = vals.clone();
-            vals.insert(0, val.clone());
+            let vals = std::iter::once(val).chain(vals.iter()).cloned().collect();
             Ok(Value::DottedList(vals, dval.clone()))
         }
-        [val, dval] => Ok(Value::DottedList(vec![val.clone()], Box::new(dval.clone()))),
+        [val, dval] => Ok(Value::DottedList(
+            Rc::new([val.clone()]),
+            Rc::new(dval.clone()),
+        )),
         _ => Err(Error::NumArgs(2, vals.to_vec())),
     }
 }
@@ -147,12 +339,15 @@ fn eqv_impl(vals: &[Value]) -> Result<bool> {
         [Value::Number(val1), Value::Number(val2)] => Ok(val1 == val2),
         [Value::String(val1), Value::String(val2)] => Ok(val1 == val2),
         [Value::Atom(val1), Value::Atom(val2)] => Ok(val1 == val2),
+        [Value::Char(val1), Value::Char(val2)] => Ok(val1 == val2),
+        [Value::Bytevector(val1), Value::Bytevector(val2)] => Ok(Rc::ptr_eq(val1, val2)),
+        [Value::Vector(val1), Value::Vector(val2)] => Ok(Rc::ptr_eq(val1, val2)),
         [Value::DottedList(vals1, val1), Value::DottedList(vals2, val2)] => {
             let mut vals1 = vals1.to_vec();
-            vals1.push(*val1.clone());
+            vals1.push(val1.as_ref().clone());
             let mut vals2 = vals2.to_vec();
-            vals2.push(*val2.clone());
-            eqv_impl(&[Value::List(vals1), Value::List(vals2)])
+            vals2.push(val2.as_ref().clone());
+            eqv_impl(&[Value::List(vals1.into()), Value::List(vals2.into())])
         }
         [Value::List(vals1), Value::List(vals2)] => {
             if vals1.len() != vals2.len() {
@@ -177,6 +372,9 @@ pub fn eqv(vals: &[Value]) -> Result<Value> {
 
 pub fn equal(vals: &[Value]) -> Result<Value> {
     match vals {
+        [Value::Char(val1), Value::Char(val2)] => Ok(Value::Bool(val1 == val2)),
+        [Value::Bytevector(val1), Value::Bytevector(val2)] => Ok(Value::Bool(val1 == val2)),
+        [Value::Vector(val1), Value::Vector(val2)] => Ok(Value::Bool(val1 == val2)),
         [val1, val2] => match (as_number(val1), as_number(val2)) {
             (Ok(val1), Ok(val2)) => Ok(Value::Bool(val1 == val2)),
             _ => match (as_string(val1), as_string(val2)) {
@@ -191,89 +389,1622 @@ pub fn equal(vals: &[Value]) -> Result<Value> {
     }
 }
 
-pub fn apply_proc(env: &mut Env, vals: &[Value]) -> Result<Value> {
+/// Whether two values hold the same data, looking inside lists, strings and
+/// vectors.
+fn same_content(lhs: &Value, rhs: &Value) -> Result<bool> {
+    let pair = [lhs.clone(), rhs.clone()];
+    Ok(eqv_impl(&pair)? || equal(&pair)?.is_truthy())
+}
+
+pub fn values(vals: &[Value]) -> Result<Value> {
     match vals {
-        [func, Value::List(args)] => apply(env, func, args),
-        [func, args @ ..] => apply(env, func, args),
-        _ => todo!(),
+        [val] => Ok(val.clone()),
+        _ => Ok(Value::Values(vals.to_vec())),
     }
 }
 
-pub fn make_read_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+pub fn call_with_values(env: &mut Env, vals: &[Value]) -> Result<Value> {
     match vals {
-        [Value::String(path)] => env.make_read_port(path),
-        _ => todo!(),
+        [producer, consumer] => match apply(env, producer, &[])? {
+            Value::Values(vals) => apply(env, consumer, &vals),
+            val => apply(env, consumer, &[val]),
+        },
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
     }
 }
 
-pub fn make_write_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+pub fn force(env: &mut Env, vals: &[Value]) -> Result<Value> {
     match vals {
-        [Value::String(path)] => env.make_write_port(path),
-        _ => todo!(),
+        [Value::Promise(promise)] => {
+            let state = promise.borrow().clone();
+            match state {
+                PromiseState::Forced(val) => Ok(val),
+                PromiseState::Delayed { expr, closure } => {
+                    let saved = env.make_closure();
+                    env.with_closure(&closure);
+                    let val = eval(env, &expr);
+                    env.load_closure(saved);
+                    let val = val?;
+                    *promise.borrow_mut() = PromiseState::Forced(val.clone());
+                    Ok(val)
+                }
+            }
+        }
+        [val] => Ok(val.clone()),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
     }
 }
 
-pub fn close_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+pub fn stream_cdr(env: &mut Env, vals: &[Value]) -> Result<Value> {
     match vals {
-        [Value::Port(port_id)] => env.close_port(port_id),
-        _ => Ok(Value::Bool(false)),
+        [Value::DottedList(head, tail)] if head.len() == 1 => force(env, &[tail.as_ref().clone()]),
+        [val] => Err(Error::TypeMismatch("stream".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
     }
 }
 
-pub fn read_proc(env: &mut Env, vals: &[Value]) -> Result<Value> {
+pub fn stream_ref(env: &mut Env, vals: &[Value]) -> Result<Value> {
     match vals {
-        [] => {
-            let mut buf = String::new();
-            stdin().read_line(&mut buf).map_err(Error::IO)?;
-            parse_expr(&buf).map_err(Error::Parser)
+        [stream, k] => {
+            let k = as_index(k)?;
+            let mut stream = stream.clone();
+            for _ in 0..k {
+                stream = stream_cdr(env, &[stream])?;
+            }
+            car(&[stream])
         }
-        [Value::Port(port_id)] => {
-            let reader = env.get_read_port(port_id)?;
-            let mut buf = String::new();
-            reader.read_line(&mut buf).map_err(Error::IO)?;
-            parse_expr(&buf).map_err(Error::Parser)
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn char_to_integer(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Char(c)] => Ok(Value::Number((*c as i64).into())),
+        [val] => Err(Error::TypeMismatch("char".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn integer_to_char(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val @ Value::Number(Number::Integer(n))] => n
+            .to_i64()
+            .and_then(|n| u32::try_from(n).ok())
+            .and_then(char::from_u32)
+            .map(Value::Char)
+            .ok_or_else(|| Error::TypeMismatch("unicode scalar value".to_owned(), val.clone())),
+        [val] => Err(Error::TypeMismatch("number".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn fold_case(string: String) -> String {
+    string.to_lowercase()
+}
+
+/// Applies a case mapping to a char, keeping the char unchanged when the
+/// mapping expands to several chars (like `ß` to `SS`).
+pub fn char_unop<F, I>(vals: &[Value], f: F) -> Result<Value>
+where
+    F: Fn(char) -> I,
+    I: Iterator<Item = char>,
+{
+    match vals {
+        [Value::Char(c)] => {
+            let mut mapped = f(*c);
+            match (mapped.next(), mapped.next()) {
+                (Some(mapped), None) => Ok(Value::Char(mapped)),
+                _ => Ok(Value::Char(*c)),
+            }
         }
-        _ => todo!(),
+        [val] => Err(Error::TypeMismatch("char".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
     }
 }
 
-pub fn write_proc(env: &mut Env, vals: &[Value]) -> Result<Value> {
+pub fn string_foldcase(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(s)] => Ok(Value::String(fold_case(s.clone()))),
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// The length of a string in chars, not in UTF-8 bytes.
+pub fn string_length(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(s)] => Ok(Value::Number((s.chars().count() as i64).into())),
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn string_ref(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val @ Value::String(s), k] => {
+            let k = as_index(k)?;
+            s.chars()
+                .nth(k)
+                .map(Value::Char)
+                .ok_or_else(|| Error::OutOfRange(k, val.clone()))
+        }
+        [val, _] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// The chars of a string from `start` up to `end`, which defaults to the end
+/// of the string. Indices count chars, so they never split a multi-byte char.
+pub fn substring(vals: &[Value]) -> Result<Value> {
+    let (val, s, start, end) = match vals {
+        [val @ Value::String(s), start] => (val, s, start, None),
+        [val @ Value::String(s), start, end] => (val, s, start, Some(end)),
+        [val, _] | [val, _, _] => {
+            return Err(Error::TypeMismatch("string".to_owned(), val.clone()))
+        }
+        _ => return Err(Error::NumArgs(3, vals.to_vec())),
+    };
+    let len = s.chars().count();
+    let start = as_index(start)?;
+    let end = end.map(as_index).transpose()?.unwrap_or(len);
+    if end > len {
+        return Err(Error::OutOfRange(end, val.clone()));
+    }
+    if start > end {
+        return Err(Error::OutOfRange(start, val.clone()));
+    }
+    Ok(Value::String(
+        s.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+pub fn bytevector(vals: &[Value]) -> Result<Value> {
+    let bytes = vals.iter().map(as_byte).collect::<Result<Vec<_>>>()?;
+    Ok(Value::Bytevector(Rc::new(bytes.into())))
+}
+
+pub fn string_to_vector(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(s)] => {
+            let chars: Vec<Value> = s.chars().map(Value::Char).collect();
+            Ok(Value::Vector(Rc::new(chars.into())))
+        }
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// Joins chars into a string, failing on the first value that isn't one.
+fn chars_to_string(chars: &[Value]) -> Result<Value> {
+    let s = chars
+        .iter()
+        .map(|c| match c {
+            Value::Char(c) => Ok(*c),
+            _ => Err(Error::TypeMismatch("char".to_owned(), c.clone())),
+        })
+        .collect::<Result<String>>()?;
+    Ok(Value::String(s))
+}
+
+/// `string-trim` and its one-sided variants, stripping from the chosen ends
+/// either whitespace or the characters of an optional string.
+pub fn string_trim(vals: &[Value], left: bool, right: bool) -> Result<Value> {
+    let (s, set) = match vals {
+        [Value::String(s)] => (s, None),
+        [Value::String(s), Value::String(set)] => (s, Some(set)),
+        [Value::String(_), val] | [val] | [val, _] => {
+            return Err(Error::TypeMismatch("string".to_owned(), val.clone()))
+        }
+        _ => return Err(Error::NumArgs(1, vals.to_vec())),
+    };
+    let strip = |c: char| match set {
+        Some(set) => set.contains(c),
+        None => c.is_whitespace(),
+    };
+    let mut s = s.as_str();
+    if left {
+        s = s.trim_start_matches(strip);
+    }
+    if right {
+        s = s.trim_end_matches(strip);
+    }
+    Ok(Value::String(s.to_owned()))
+}
+
+pub fn string(vals: &[Value]) -> Result<Value> {
+    chars_to_string(vals)
+}
+
+pub fn list_to_string(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::List(chars)] => chars_to_string(chars),
+        [val] => Err(Error::TypeMismatch("list".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn vector_to_string(vals: &[Value]) -> Result<Value> {
     match vals {
+        [Value::Vector(chars)] => chars_to_string(&chars.borrow()),
+        [val] => Err(Error::TypeMismatch("vector".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// A symbol no name in the program so far refers to, named after the given
+/// symbol or string, or `g` by default.
+pub fn gensym(vals: &[Value]) -> Result<Value> {
+    let base = match vals {
+        [] => "g",
+        [Value::Atom(atom)] => atom.as_str(),
+        [Value::String(s)] => s.as_str(),
         [val] => {
-            println!("{}", val);
-            Ok(Value::Bool(true))
+            return Err(Error::TypeMismatch(
+                "symbol or string".to_owned(),
+                val.clone(),
+            ))
         }
-        [val, Value::Port(port_id)] => {
-            let writer = env.get_write_port(port_id)?;
-            let buf = val.to_string();
-            writer.write_all(buf.as_bytes()).map_err(Error::IO)?;
+        _ => return Err(Error::NumArgs(1, vals.to_vec())),
+    };
+    Ok(Value::Atom(Symbol::gensym(base)))
+}
+
+pub fn vector_append(vals: &[Value]) -> Result<Value> {
+    let mut appended = Vec::new();
+    for val in vals {
+        match val {
+            Value::Vector(vector) => appended.extend(vector.borrow().iter().cloned()),
+            _ => return Err(Error::TypeMismatch("vector".to_owned(), val.clone())),
+        }
+    }
+    Ok(Value::Vector(Rc::new(appended.into())))
+}
+
+/// `vector-copy` and `subvector`: a new vector holding the elements from
+/// `start` up to `end`, which default to the whole vector.
+pub fn vector_copy(vals: &[Value]) -> Result<Value> {
+    let (vector, start, end) = match vals {
+        [Value::Vector(vector)] => (vector, None, None),
+        [Value::Vector(vector), start] => (vector, Some(start), None),
+        [Value::Vector(vector), start, end] => (vector, Some(start), Some(end)),
+        [val] | [val, _] | [val, _, _] => {
+            return Err(Error::TypeMismatch("vector".to_owned(), val.clone()))
+        }
+        _ => return Err(Error::NumArgs(3, vals.to_vec())),
+    };
+    let elems = vector.borrow();
+    let start = start.map(as_index).transpose()?.unwrap_or(0);
+    let end = end.map(as_index).transpose()?.unwrap_or(elems.len());
+    if end > elems.len() {
+        return Err(Error::OutOfRange(end, Value::Vector(vector.clone())));
+    }
+    if start > end {
+        return Err(Error::OutOfRange(start, Value::Vector(vector.clone())));
+    }
+    Ok(Value::Vector(Rc::new(elems[start..end].to_vec().into())))
+}
+
+pub fn make_bytevector(vals: &[Value]) -> Result<Value> {
+    let (len, fill) = match vals {
+        [len] => (as_index(len)?, 0),
+        [len, fill] => (as_index(len)?, as_byte(fill)?),
+        _ => return Err(Error::NumArgs(2, vals.to_vec())),
+    };
+    Ok(Value::Bytevector(Rc::new(vec![fill; len].into())))
+}
+
+pub fn bytevector_u8_ref(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val @ Value::Bytevector(bytes), k] => {
+            let k = as_index(k)?;
+            let byte = bytes.borrow().get(k).copied();
+            byte.map(|byte| Value::Number((byte as i64).into()))
+                .ok_or_else(|| Error::OutOfRange(k, val.clone()))
+        }
+        [val, _] => Err(Error::TypeMismatch("bytevector".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn bytevector_u8_set(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val @ Value::Bytevector(bytes), k, byte] => {
+            let k = as_index(k)?;
+            let byte = as_byte(byte)?;
+            match bytes.borrow_mut().get_mut(k) {
+                Some(slot) => *slot = byte,
+                None => return Err(Error::OutOfRange(k, val.clone())),
+            }
             Ok(Value::Bool(true))
         }
-        _ => todo!(),
+        [val, _, _] => Err(Error::TypeMismatch("bytevector".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(3, vals.to_vec())),
     }
 }
 
-pub fn read_contents(vals: &[Value]) -> Result<Value> {
+pub fn bytevector_length(vals: &[Value]) -> Result<Value> {
     match vals {
-        [Value::String(path)] => {
-            let lines = std::fs::read_to_string(path).map_err(Error::IO)?;
-            Ok(Value::String(lines))
+        [Value::Bytevector(bytes)] => Ok(Value::Number((bytes.borrow().len() as i64).into())),
+        [val] => Err(Error::TypeMismatch("bytevector".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn get_environment_variable(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(name)] => match std::env::var(name) {
+            Ok(val) => Ok(Value::String(val)),
+            Err(_) => Ok(Value::Bool(false)),
+        },
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn add_to_load_path(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(dir)] => {
+            env.add_to_load_path(dir.into());
+            Ok(Value::Bool(true))
         }
-        _ => todo!(),
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
     }
 }
 
-pub fn load(path: &str) -> Result<Vec<Value>> {
-    let lines = std::fs::read_to_string(path).map_err(Error::IO)?;
-    parse_exprs(&lines).map_err(Error::Parser)
+pub fn command_line(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [] => Ok(Value::List(
+            env.command_line()
+                .iter()
+                .map(|arg| Value::String(arg.clone()))
+                .collect(),
+        )),
+        _ => Err(Error::NumArgs(0, vals.to_vec())),
+    }
 }
 
-pub fn read_all(vals: &[Value]) -> Result<Value> {
+/// Fails with a type error unless the value can be applied, so primitives
+/// taking a procedure reject a bad one before doing any work.
+fn expect_procedure(val: &Value) -> Result<()> {
+    match val.type_name() {
+        "procedure" => Ok(()),
+        _ => Err(Error::TypeMismatch("procedure".to_owned(), val.clone())),
+    }
+}
+
+pub fn identity(vals: &[Value]) -> Result<Value> {
     match vals {
-        [Value::String(path)] => {
-            let vals = load(path)?;
-            Ok(Value::List(vals))
+        [val] => Ok(val.clone()),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn compose(vals: &[Value]) -> Result<Value> {
+    for val in vals {
+        expect_procedure(val)?;
+    }
+    Ok(Value::NativeClosure(NativeClosure::Compose(vals.into())))
+}
+
+pub fn curry(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [func, args @ ..] => Ok(Value::NativeClosure(NativeClosure::Curry(
+            Rc::new(func.clone()),
+            args.into(),
+        ))),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn append(vals: &[Value]) -> Result<Value> {
+    let Some((last, init)) = vals.split_last() else {
+        return Ok(Value::List(Rc::new([])));
+    };
+    let mut items = Vec::new();
+    for val in init {
+        match val {
+            Value::List(vals) => items.extend_from_slice(vals),
+            _ => return Err(Error::TypeMismatch("list".to_owned(), val.clone())),
         }
-        _ => todo!(),
+    }
+    match last {
+        _ if items.is_empty() => Ok(last.clone()),
+        Value::List(vals) => {
+            items.extend_from_slice(vals);
+            Ok(Value::List(items.into()))
+        }
+        Value::DottedList(vals, tail) => {
+            items.extend_from_slice(vals);
+            Ok(Value::DottedList(items.into(), tail.clone()))
+        }
+        _ => Ok(Value::DottedList(items.into(), Rc::new(last.clone()))),
+    }
+}
+
+/// Splices the elements of nested lists, at any depth, into one list. The
+/// tail of a dotted list is taken as its last element, so `(1 (2 . 3))`
+/// flattens to `(1 2 3)`, and empty lists contribute nothing.
+pub fn flatten(vals: &[Value]) -> Result<Value> {
+    fn flatten_into(val: &Value, items: &mut Vec<Value>) {
+        match val {
+            Value::List(vals) => vals.iter().for_each(|val| flatten_into(val, items)),
+            Value::DottedList(vals, tail) => {
+                vals.iter().for_each(|val| flatten_into(val, items));
+                flatten_into(tail, items);
+            }
+            _ => items.push(val.clone()),
+        }
+    }
+    match vals {
+        [val @ (Value::List(_) | Value::DottedList(_, _))] => {
+            let mut items = Vec::new();
+            flatten_into(val, &mut items);
+            Ok(Value::List(items.into()))
+        }
+        [val] => Err(Error::TypeMismatch("list".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn reverse(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::List(vals)] => Ok(Value::List(vals.iter().rev().cloned().collect())),
+        [val] => Err(Error::TypeMismatch("list".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn eof_object(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [] => Ok(Value::Eof),
+        _ => Err(Error::NumArgs(0, vals.to_vec())),
+    }
+}
+
+pub fn is_eof_object(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Bool(matches!(val, Value::Eof))),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn is_port(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Bool(matches!(val, Value::Port(_)))),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// `input-port?`, which like `output-port?` is false once the port is closed.
+pub fn is_input_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Port(port_id)] => Ok(Value::Bool(env.is_input_port(port_id))),
+        [_] => Ok(Value::Bool(false)),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn is_output_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Port(port_id)] => Ok(Value::Bool(env.is_output_port(port_id))),
+        [_] => Ok(Value::Bool(false)),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn with_exception_handler(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [handler, thunk] => {
+            expect_procedure(handler)?;
+            expect_procedure(thunk)?;
+            env.push_handler(handler.clone());
+            let ret = apply(env, thunk, &[]);
+            env.pop_handler();
+            ret
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// Calls the innermost handler on the object, with the handlers outside it
+/// installed so that raising from the handler goes to the next one out. The
+/// handler's value is the result of `raise-continuable`; a handler returning
+/// from a plain `raise` leaves the object raised, for an enclosing `guard`.
+pub fn raise(env: &mut Env, vals: &[Value], continuable: bool) -> Result<Value> {
+    let obj = match vals {
+        [obj] => obj,
+        _ => return Err(Error::NumArgs(1, vals.to_vec())),
+    };
+    let handler = match env.pop_handler() {
+        Some(handler) => handler,
+        None => return Err(Error::Raised(obj.clone())),
+    };
+    let ret = apply(env, &handler, vals);
+    env.push_handler(handler);
+    match ret? {
+        val if continuable => Ok(val),
+        _ => Err(Error::Raised(obj.clone())),
+    }
+}
+
+pub fn not(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Bool(!val.is_truthy())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn error_kind(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Condition { kind, .. }] => Ok(Value::String((*kind).to_owned())),
+        [val] => Err(Error::TypeMismatch("condition".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn error_message(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Condition { message, .. }] => Ok(Value::String(message.clone())),
+        [val] => Err(Error::TypeMismatch("condition".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn make_hash_table(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [] => Ok(Value::HashTable(Rc::new(
+            RefCell::new(HashTable::default()),
+        ))),
+        _ => Err(Error::NumArgs(0, vals.to_vec())),
+    }
+}
+
+pub fn is_hash_table(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Bool(matches!(val, Value::HashTable(_)))),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn hash_set(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::HashTable(table), key, val] => {
+            table.borrow_mut().insert(key, val.clone())?;
+            Ok(Value::Bool(true))
+        }
+        [val, _, _] => Err(Error::TypeMismatch("hash-table".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(3, vals.to_vec())),
+    }
+}
+
+pub fn hash_ref(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::HashTable(table), key] => table
+            .borrow()
+            .get(key)?
+            .ok_or_else(|| Error::MissingKey(key.clone())),
+        [Value::HashTable(table), key, default] => {
+            Ok(table.borrow().get(key)?.unwrap_or_else(|| default.clone()))
+        }
+        [val, _] | [val, _, _] => Err(Error::TypeMismatch("hash-table".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// Follows a list of keys through nested alists and hash tables. Alist keys
+/// are compared by content, and the first missing key or value along the
+/// way that is neither an alist nor a hash table gives the default, `#f`
+/// unless one is supplied.
+pub fn nested_ref(vals: &[Value]) -> Result<Value> {
+    let (mut val, path, default) = match vals {
+        [val, path] => (val.clone(), path, Value::Bool(false)),
+        [val, path, default] => (val.clone(), path, default.clone()),
+        _ => return Err(Error::NumArgs(2, vals.to_vec())),
+    };
+    let Value::List(keys) = path else {
+        return Err(Error::TypeMismatch("list".to_owned(), path.clone()));
+    };
+    for key in keys.iter() {
+        match nested_lookup(&val, key)? {
+            Some(next) => val = next,
+            None => return Ok(default),
+        }
+    }
+    Ok(val)
+}
+
+fn nested_lookup(val: &Value, key: &Value) -> Result<Option<Value>> {
+    let entries = match val {
+        Value::HashTable(table) => return table.borrow().get(key),
+        Value::List(entries) => entries,
+        _ => return Ok(None),
+    };
+    for entry in entries.iter() {
+        let is_pair = match entry {
+            Value::List(vals) => !vals.is_empty(),
+            Value::DottedList(_, _) => true,
+            _ => false,
+        };
+        if !is_pair {
+            return Ok(None);
+        }
+        let entry_key = car(std::slice::from_ref(entry))?;
+        if same_content(&entry_key, key)? {
+            return cdr(std::slice::from_ref(entry)).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+pub fn hash_count(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::HashTable(table)] => Ok(Value::Number((table.borrow().len() as i64).into())),
+        [val] => Err(Error::TypeMismatch("hash-table".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// `equal-hash` and `eqv-hash`. `eqv?` already compares lists and strings
+/// by content, so both hash a value by its hash table key: values that would
+/// find the same entry hash equally, and those with no key are an error.
+pub fn equal_hash(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => Ok(Value::Number(ValueKey::try_from(val)?.hash_code().into())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// Builds a hash table from `(key . value)` pairs, later pairs winning.
+pub fn alist_to_hash_table(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::List(pairs)] => {
+            let mut table = HashTable::default();
+            for pair in pairs.iter() {
+                let (key, val) = match pair {
+                    Value::DottedList(head, tail) if head.len() == 1 => {
+                        (&head[0], tail.as_ref().clone())
+                    }
+                    Value::List(list) if !list.is_empty() => {
+                        (&list[0], Value::List(list[1..].into()))
+                    }
+                    _ => return Err(Error::TypeMismatch("pair".to_owned(), pair.clone())),
+                };
+                table.insert(key, val)?;
+            }
+            Ok(Value::HashTable(Rc::new(RefCell::new(table))))
+        }
+        [val] => Err(Error::TypeMismatch("list".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn hash_table_to_alist(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::HashTable(table)] => Ok(Value::List(
+            table
+                .borrow()
+                .entries()
+                .iter()
+                .map(|(key, val)| cons(&[key.clone(), val.clone()]))
+                .collect::<Result<_>>()?,
+        )),
+        [val] => Err(Error::TypeMismatch("hash-table".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn hash_update(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    let (table, key, proc, default) = match vals {
+        [Value::HashTable(table), key, proc] => (table, key, proc, None),
+        [Value::HashTable(table), key, proc, default] => (table, key, proc, Some(default)),
+        [val, _, _] | [val, _, _, _] => {
+            return Err(Error::TypeMismatch("hash-table".to_owned(), val.clone()))
+        }
+        _ => return Err(Error::NumArgs(3, vals.to_vec())),
+    };
+    let current = match (table.borrow().get(key)?, default) {
+        (Some(val), _) => val,
+        (None, Some(default)) => default.clone(),
+        (None, None) => return Err(Error::MissingKey(key.clone())),
+    };
+    let val = apply(env, proc, &[current])?;
+    table.borrow_mut().insert(key, val)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn apply_proc(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [func, args @ .., Value::List(rest)] => {
+            expect_procedure(func)?;
+            let mut args = args.to_vec();
+            args.extend(rest.iter().cloned());
+            apply(env, func, &args)
+        }
+        [_, .., last] => Err(Error::TypeMismatch("list".to_owned(), last.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn make_read_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path)] => env.make_read_port(path),
+        _ => todo!(),
+    }
+}
+
+pub fn make_write_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path)] => env.make_write_port(path),
+        _ => todo!(),
+    }
+}
+
+pub fn make_string_read_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(contents)] => Ok(env.make_string_read_port(contents.clone())),
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn make_binary_read_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path)] => env.make_binary_read_port(path),
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn make_binary_write_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path)] => env.make_binary_write_port(path),
+        [val] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn close_port(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Port(port_id)] => env.close_port(port_id),
+        _ => Ok(Value::Bool(false)),
+    }
+}
+
+/// Applies `proc` to a freshly opened port, closing the port whether or not
+/// the call succeeds.
+fn call_with_port(env: &mut Env, port: Value, proc: &Value) -> Result<Value> {
+    let result = apply(env, proc, std::slice::from_ref(&port));
+    close_port(env, &[port])?;
+    result
+}
+
+pub fn call_with_input_file(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path), proc] => {
+            let port = env.make_read_port(path)?;
+            call_with_port(env, port, proc)
+        }
+        [val, _] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn call_with_output_file(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path), proc] => {
+            let port = env.make_write_port(path)?;
+            call_with_port(env, port, proc)
+        }
+        [val, _] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn with_input_from_file(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path), thunk] => {
+            let port = env.make_read_port(path)?;
+            let saved = env.replace_current_input_port(port_id(&port));
+            let result = apply(env, thunk, &[]);
+            env.replace_current_input_port(saved);
+            close_port(env, &[port])?;
+            result
+        }
+        [val, _] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn with_output_to_file(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path), thunk] => {
+            let port = env.make_write_port(path)?;
+            let saved = env.replace_current_output_port(port_id(&port));
+            let result = apply(env, thunk, &[]);
+            env.replace_current_output_port(saved);
+            close_port(env, &[port])?;
+            result
+        }
+        [val, _] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+fn port_id(val: &Value) -> Option<usize> {
+    match val {
+        Value::Port(port_id) => Some(*port_id),
+        _ => None,
+    }
+}
+
+fn read_port(env: &mut Env, port_id: &usize) -> Result<Value> {
+    let port = env.get_read_port(port_id)?;
+    match parse_datum(port.remaining()).map_err(Error::Parser)? {
+        Some((val, len)) => {
+            port.consume(len);
+            Ok(val)
+        }
+        None => Ok(Value::Eof),
+    }
+}
+
+fn write_port(env: &mut Env, port_id: &usize, buf: &str) -> Result<Value> {
+    let writer = env.get_write_port(port_id)?;
+    writer.write_all(buf.as_bytes()).map_err(Error::IO)?;
+    Ok(Value::Bool(true))
+}
+
+pub fn read_proc(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [] => {
+            if let Some(port_id) = env.current_input_port() {
+                return read_port(env, &port_id);
+            }
+            let mut buf = String::new();
+            if stdin().read_line(&mut buf).map_err(Error::IO)? == 0 {
+                return Ok(Value::Eof);
+            }
+            parse_expr(&buf).map_err(Error::Parser)
+        }
+        [Value::Port(port_id)] => read_port(env, port_id),
+        [val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+fn read_line_port(env: &mut Env, port_id: &usize) -> Result<Value> {
+    let port = env.get_read_port(port_id)?;
+    let remaining = port.remaining();
+    if remaining.is_empty() {
+        return Ok(Value::Eof);
+    }
+    let (line, len) = match remaining.find('\n') {
+        Some(end) => (&remaining[..end], end + 1),
+        None => (remaining, remaining.len()),
+    };
+    let line = line.to_owned();
+    port.consume(len);
+    Ok(Value::String(line))
+}
+
+pub fn read_line(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [] => {
+            if let Some(port_id) = env.current_input_port() {
+                return read_line_port(env, &port_id);
+            }
+            let mut buf = String::new();
+            if stdin().read_line(&mut buf).map_err(Error::IO)? == 0 {
+                return Ok(Value::Eof);
+            }
+            Ok(Value::String(buf.trim_end_matches('\n').to_owned()))
+        }
+        [Value::Port(port_id)] => read_line_port(env, port_id),
+        [val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+fn read_char_port(env: &mut Env, port_id: &usize, consume: bool) -> Result<Value> {
+    let port = env.get_read_port(port_id)?;
+    match port.remaining().chars().next() {
+        Some(c) => {
+            if consume {
+                port.consume(c.len_utf8());
+            }
+            Ok(Value::Char(c))
+        }
+        None => Ok(Value::Eof),
+    }
+}
+
+fn read_char_stdin(consume: bool) -> Result<Value> {
+    let mut stdin = stdin().lock();
+    let buf = stdin.fill_buf().map_err(Error::IO)?;
+    let valid = match std::str::from_utf8(buf) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    match valid.chars().next() {
+        Some(c) => {
+            if consume {
+                stdin.consume(c.len_utf8());
+            }
+            Ok(Value::Char(c))
+        }
+        None => Ok(Value::Eof),
+    }
+}
+
+/// Reads the next character, leaving it in the port unless `consume` is set
+/// so that `peek-char` and `read-char` share one implementation.
+pub fn read_char(env: &mut Env, vals: &[Value], consume: bool) -> Result<Value> {
+    match vals {
+        [] => match env.current_input_port() {
+            Some(port_id) => read_char_port(env, &port_id, consume),
+            None => read_char_stdin(consume),
+        },
+        [Value::Port(port_id)] => read_char_port(env, port_id, consume),
+        [val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// Whether a character, or the end of the input, can be read without
+/// waiting. In-memory ports always can. Standard input can when it's a
+/// file; a terminal or a pipe can't be asked without blocking, so they
+/// never count as ready.
+fn char_ready_port(env: &mut Env, port_id: Option<usize>) -> Result<Value> {
+    let ready = match port_id {
+        Some(port_id) => {
+            env.get_read_port(&port_id)?;
+            true
+        }
+        None => !stdin().is_terminal() && stdin_is_file(),
+    };
+    Ok(Value::Bool(ready))
+}
+
+#[cfg(unix)]
+fn stdin_is_file() -> bool {
+    use std::os::fd::AsFd;
+    stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| std::fs::File::from(fd).metadata())
+        .is_ok_and(|metadata| metadata.is_file())
+}
+
+#[cfg(not(unix))]
+fn stdin_is_file() -> bool {
+    false
+}
+
+pub fn char_ready(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [] => {
+            let port_id = env.current_input_port();
+            char_ready_port(env, port_id)
+        }
+        [Value::Port(port_id)] => char_ready_port(env, Some(*port_id)),
+        [val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn write_char(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Char(_)] | [Value::Char(_), _] => display(env, vals),
+        [val] | [val, _] => Err(Error::TypeMismatch("char".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn write_proc(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    write_rendered(env, vals, Value::to_string)
+}
+
+pub fn write_shared(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    write_rendered(env, vals, Value::to_shared_string)
+}
+
+/// Writes a value as `render` renders it, to the given port or else the
+/// current output.
+fn write_rendered(env: &mut Env, vals: &[Value], render: fn(&Value) -> String) -> Result<Value> {
+    match vals {
+        [val] => {
+            if let Some(port_id) = env.current_output_port() {
+                return write_port(env, &port_id, &render(val));
+            }
+            println!("{}", render(val));
+            Ok(Value::Bool(true))
+        }
+        [val, Value::Port(port_id)] => write_port(env, port_id, &render(val)),
+        [_, val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn display(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => {
+            if let Some(port_id) = env.current_output_port() {
+                return write_port(env, &port_id, &val.to_display_string());
+            }
+            print!("{}", val.to_display_string());
+            stdout().flush().map_err(Error::IO)?;
+            Ok(Value::Bool(true))
+        }
+        [val, Value::Port(port_id)] => write_port(env, port_id, &val.to_display_string()),
+        [_, val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+/// Prints a one-line summary of a value for exploring at the REPL.
+pub fn describe(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [val] => {
+            let summary = format!("{}\n", describe_value(env, val));
+            display(env, &[Value::String(summary)])?;
+            Ok(Value::Unspecified)
+        }
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+fn describe_value(env: &Env, val: &Value) -> String {
+    let count = |n: usize, one: &str, many: &str| match n {
+        1 => format!("1 {}", one),
+        n => format!("{} {}", n, many),
+    };
+    match val {
+        Value::Func {
+            name,
+            params,
+            optionals,
+            vararg,
+            ..
+        } => {
+            let name = name.map_or(String::new(), |name| format!(" {}", name));
+            let arity = match Arity::new(params.len(), optionals.len(), vararg.is_some()) {
+                Arity::Exactly(n) => count(n, "arg", "args"),
+                arity => format!("{} args", arity),
+            };
+            let params = lambda_list(params, optionals, vararg);
+            format!("procedure{} {}, takes {}", name, params, arity)
+        }
+        Value::PrimitiveFunc(_) | Value::IOFunc(_) => "primitive procedure".to_owned(),
+        Value::List(vals) if vals.is_empty() => "empty list".to_owned(),
+        Value::List(vals) => format!("list of {}", count(vals.len(), "element", "elements")),
+        Value::DottedList(vals, _) => {
+            format!(
+                "improper list of {}",
+                count(vals.len(), "element", "elements")
+            )
+        }
+        Value::String(s) => format!("string of {}", count(s.chars().count(), "char", "chars")),
+        Value::Vector(vals) => format!(
+            "vector of {}",
+            count(vals.borrow().len(), "element", "elements")
+        ),
+        Value::Bytevector(bytes) => {
+            format!(
+                "bytevector of {}",
+                count(bytes.borrow().len(), "byte", "bytes")
+            )
+        }
+        Value::HashTable(table) => {
+            format!(
+                "hash table of {}",
+                count(table.borrow().len(), "entry", "entries")
+            )
+        }
+        Value::Port(port_id) => env.port_description(port_id).to_owned(),
+        Value::Condition { kind, message } => format!("condition ({}): {}", kind, message),
+        Value::Atom(_) | Value::Number(_) | Value::Char(_) | Value::Bool(_) => {
+            format!("{} {}", val.type_name(), val)
+        }
+        _ => val.type_name().to_owned(),
+    }
+}
+
+pub fn read_u8(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Port(port_id)] => match env.get_binary_read_port(port_id)?.read(1) {
+            [byte] => Ok(Value::Number((*byte as i64).into())),
+            _ => Ok(Value::Eof),
+        },
+        [val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
+    }
+}
+
+pub fn write_u8(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [byte, Value::Port(port_id)] => {
+            let byte = as_byte(byte)?;
+            let writer = env.get_binary_write_port(port_id)?;
+            writer.write_all(&[byte]).map_err(Error::IO)?;
+            Ok(Value::Bool(true))
+        }
+        [_, val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn read_bytevector(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [k, Value::Port(port_id)] => {
+            let k = as_index(k)?;
+            match env.get_binary_read_port(port_id)?.read(k) {
+                [] if k > 0 => Ok(Value::Eof),
+                bytes => Ok(Value::Bytevector(Rc::new(bytes.to_vec().into()))),
+            }
+        }
+        [_, val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn write_bytevector(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Bytevector(bytes), Value::Port(port_id)] => {
+            let writer = env.get_binary_write_port(port_id)?;
+            writer.write_all(&bytes.borrow()).map_err(Error::IO)?;
+            Ok(Value::Bool(true))
+        }
+        [Value::Bytevector(_), val] => Err(Error::TypeMismatch("port".to_owned(), val.clone())),
+        [val, _] => Err(Error::TypeMismatch("bytevector".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+fn call_less(env: &mut Env, less: &Value, lhs: &Value, rhs: &Value) -> Result<bool> {
+    match apply(env, less, &[lhs.clone(), rhs.clone()])? {
+        Value::Bool(b) => Ok(b),
+        val => Err(Error::TypeMismatch("boolean".to_owned(), val)),
+    }
+}
+
+/// Merges two sorted runs, taking from `lhs` on ties so sorting is stable.
+fn merge_sorted(env: &mut Env, less: &Value, lhs: &[Value], rhs: &[Value]) -> Result<Vec<Value>> {
+    let mut merged = Vec::with_capacity(lhs.len() + rhs.len());
+    let (mut i, mut j) = (0, 0);
+    while i < lhs.len() && j < rhs.len() {
+        if call_less(env, less, &rhs[j], &lhs[i])? {
+            merged.push(rhs[j].clone());
+            j += 1;
+        } else {
+            merged.push(lhs[i].clone());
+            i += 1;
+        }
+    }
+    merged.extend_from_slice(&lhs[i..]);
+    merged.extend_from_slice(&rhs[j..]);
+    Ok(merged)
+}
+
+fn merge_sort(env: &mut Env, less: &Value, vals: &[Value]) -> Result<Vec<Value>> {
+    if vals.len() <= 1 {
+        return Ok(vals.to_vec());
+    }
+    let (lhs, rhs) = vals.split_at(vals.len() / 2);
+    let lhs = merge_sort(env, less, lhs)?;
+    let rhs = merge_sort(env, less, rhs)?;
+    merge_sorted(env, less, &lhs, &rhs)
+}
+
+fn sort_impl(env: &mut Env, less: &Value, list: &Value) -> Result<Value> {
+    expect_procedure(less)?;
+    match list {
+        Value::List(vals) => Ok(Value::List(merge_sort(env, less, vals)?.into())),
+        _ => Err(Error::TypeMismatch("list".to_owned(), list.clone())),
+    }
+}
+
+pub fn sort(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [list, less] => sort_impl(env, less, list),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn list_sort(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [less, list] => sort_impl(env, less, list),
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+pub fn merge(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [less, Value::List(lhs), Value::List(rhs)] => {
+            expect_procedure(less)?;
+            Ok(Value::List(merge_sorted(env, less, lhs, rhs)?.into()))
+        }
+        [_, Value::List(_), val] | [_, val, _] => {
+            Err(Error::TypeMismatch("list".to_owned(), val.clone()))
+        }
+        _ => Err(Error::NumArgs(3, vals.to_vec())),
+    }
+}
+
+/// The arguments for each step of `map` or `for-each`, one element from each
+/// list. R7RS leaves lists of unequal length unspecified; like most
+/// implementations, this stops at the end of the shortest.
+fn map_args(lists: &[Value]) -> Result<Vec<Vec<Value>>> {
+    let lists = lists
+        .iter()
+        .map(|list| match list {
+            Value::List(list) => Ok(list),
+            _ => Err(Error::TypeMismatch("list".to_owned(), list.clone())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let len = lists.iter().map(|list| list.len()).min().unwrap_or(0);
+    Ok((0..len)
+        .map(|i| lists.iter().map(|list| list[i].clone()).collect())
+        .collect())
+}
+
+pub fn map(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [func, lists @ ..] if !lists.is_empty() => {
+            expect_procedure(func)?;
+            let mapped = map_args(lists)?
+                .iter()
+                .map(|args| apply(env, func, args))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Value::List(mapped.into()))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// Maps like `map` and appends the resulting lists.
+pub fn append_map(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    let Value::List(mapped) = map(env, vals)? else {
+        unreachable!("map returns a list");
+    };
+    append(&mapped)
+}
+
+pub fn for_each(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [func, lists @ ..] if !lists.is_empty() => {
+            expect_procedure(func)?;
+            for args in map_args(lists)? {
+                apply(env, func, &args)?;
+            }
+            Ok(Value::Unspecified)
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// Splits a list into the elements `pred` accepts and those it rejects,
+/// keeping their order.
+fn split_by(env: &mut Env, pred: &Value, list: &Value) -> Result<(Vec<Value>, Vec<Value>)> {
+    expect_procedure(pred)?;
+    let Value::List(vals) = list else {
+        return Err(Error::TypeMismatch("list".to_owned(), list.clone()));
+    };
+    let (mut accepted, mut rejected) = (Vec::new(), Vec::new());
+    for val in vals.iter() {
+        match apply(env, pred, std::slice::from_ref(val))?.is_truthy() {
+            true => accepted.push(val.clone()),
+            false => rejected.push(val.clone()),
+        }
+    }
+    Ok((accepted, rejected))
+}
+
+/// `filter`, or `remove` when `keep` is false, which drops the elements the
+/// predicate accepts instead.
+pub fn filter(env: &mut Env, vals: &[Value], keep: bool) -> Result<Value> {
+    match vals {
+        [pred, list] => {
+            let (accepted, rejected) = split_by(env, pred, list)?;
+            let kept = if keep { accepted } else { rejected };
+            Ok(Value::List(kept.into()))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// Returns the elements the predicate accepts and those it rejects as two
+/// values.
+pub fn partition(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [pred, list] => {
+            let (accepted, rejected) = split_by(env, pred, list)?;
+            Ok(Value::Values(vec![
+                Value::List(accepted.into()),
+                Value::List(rejected.into()),
+            ]))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// The number of elements, taken across the lists in step, that `pred`
+/// accepts.
+pub fn count(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [pred, lists @ ..] if !lists.is_empty() => {
+            expect_procedure(pred)?;
+            let mut count = 0;
+            for args in map_args(lists)? {
+                if apply(env, pred, &args)?.is_truthy() {
+                    count += 1;
+                }
+            }
+            Ok(Value::Number(count.into()))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// The first true result of `pred` across the lists, or `#f`. Elements
+/// after that one are not tested.
+pub fn any(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [pred, lists @ ..] if !lists.is_empty() => {
+            expect_procedure(pred)?;
+            for args in map_args(lists)? {
+                let result = apply(env, pred, &args)?;
+                if result.is_truthy() {
+                    return Ok(result);
+                }
+            }
+            Ok(Value::Bool(false))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// `#f` as soon as `pred` rejects an element, otherwise its last result, or
+/// `#t` for empty lists.
+pub fn every(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [pred, lists @ ..] if !lists.is_empty() => {
+            expect_procedure(pred)?;
+            let mut result = Value::Bool(true);
+            for args in map_args(lists)? {
+                result = apply(env, pred, &args)?;
+                if !result.is_truthy() {
+                    break;
+                }
+            }
+            Ok(result)
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// The first element of the list that `pred` accepts, or `#f`.
+pub fn find(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [pred, list] => {
+            expect_procedure(pred)?;
+            for args in map_args(std::slice::from_ref(list))? {
+                if apply(env, pred, &args)?.is_truthy() {
+                    return Ok(args[0].clone());
+                }
+            }
+            Ok(Value::Bool(false))
+        }
+        _ => Err(Error::NumArgs(2, vals.to_vec())),
+    }
+}
+
+/// `fold`, or `fold-right` when `from_right` is set. The procedure gets an
+/// element from each list followed by the accumulator, and the lists are
+/// walked in step until the shortest runs out.
+pub fn fold(env: &mut Env, vals: &[Value], from_right: bool) -> Result<Value> {
+    match vals {
+        [func, init, lists @ ..] if !lists.is_empty() => {
+            expect_procedure(func)?;
+            let mut rows = map_args(lists)?;
+            if from_right {
+                rows.reverse();
+            }
+            rows.into_iter().try_fold(init.clone(), |acc, mut args| {
+                args.push(acc);
+                apply(env, func, &args)
+            })
+        }
+        _ => Err(Error::NumArgs(3, vals.to_vec())),
+    }
+}
+
+/// Compares two values with the procedure the caller supplied, or by
+/// content if there's none.
+fn is_same(env: &mut Env, same: Option<&Value>, lhs: &Value, rhs: &Value) -> Result<bool> {
+    match same {
+        Some(same) => Ok(apply(env, same, &[lhs.clone(), rhs.clone()])?.is_truthy()),
+        None => same_content(lhs, rhs),
+    }
+}
+
+/// Splits off an optional comparison procedure following `n` arguments.
+fn with_comparator(vals: &[Value], n: usize) -> Result<(&[Value], Option<&Value>)> {
+    match vals.len() {
+        len if len == n => Ok((vals, None)),
+        len if len == n + 1 => {
+            expect_procedure(&vals[n])?;
+            Ok((&vals[..n], Some(&vals[n])))
+        }
+        _ => Err(Error::NumArgs(n, vals.to_vec())),
+    }
+}
+
+/// The list without repeated elements, keeping the first of each. Elements
+/// are compared by content, or with the given procedure.
+pub fn delete_duplicates(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    let ([list], same) = with_comparator(vals, 1)? else {
+        unreachable!("with_comparator splits off one argument");
+    };
+    let Value::List(items) = list else {
+        return Err(Error::TypeMismatch("list".to_owned(), list.clone()));
+    };
+    let mut kept: Vec<Value> = Vec::new();
+    for item in items.iter() {
+        let mut seen = false;
+        for prev in &kept {
+            seen = is_same(env, same, prev, item)?;
+            if seen {
+                break;
+            }
+        }
+        if !seen {
+            kept.push(item.clone());
+        }
+    }
+    Ok(Value::List(kept.into()))
+}
+
+fn alist_key(entry: &Value) -> Result<&Value> {
+    match entry {
+        Value::List(vals) | Value::DottedList(vals, _) if !vals.is_empty() => Ok(&vals[0]),
+        _ => Err(Error::TypeMismatch("pair".to_owned(), entry.clone())),
+    }
+}
+
+/// A copy of the alist with the first entry for `key` given the new value,
+/// or with a new entry at the end if there's none. Keys are compared by
+/// content, or with the given procedure.
+pub fn alist_update(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    let ([key, val, alist], same) = with_comparator(vals, 3)? else {
+        unreachable!("with_comparator splits off three arguments");
+    };
+    let Value::List(entries) = alist else {
+        return Err(Error::TypeMismatch("list".to_owned(), alist.clone()));
+    };
+    let mut entries = entries.to_vec();
+    let entry = cons(&[key.clone(), val.clone()])?;
+    for slot in entries.iter_mut() {
+        if is_same(env, same, key, alist_key(slot)?)? {
+            *slot = entry;
+            return Ok(Value::List(entries.into()));
+        }
+    }
+    entries.push(entry);
+    Ok(Value::List(entries.into()))
+}
+
+/// A copy of the alist without any entries for `key`. Keys are compared by
+/// content, or with the given procedure.
+pub fn alist_delete(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    let ([key, alist], same) = with_comparator(vals, 2)? else {
+        unreachable!("with_comparator splits off two arguments");
+    };
+    let Value::List(entries) = alist else {
+        return Err(Error::TypeMismatch("list".to_owned(), alist.clone()));
+    };
+    let mut kept = Vec::new();
+    for entry in entries.iter() {
+        if !is_same(env, same, key, alist_key(entry)?)? {
+            kept.push(entry.clone());
+        }
+    }
+    Ok(Value::List(kept.into()))
+}
+
+pub fn read_contents(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path)] => {
+            let lines = std::fs::read_to_string(path).map_err(Error::IO)?;
+            Ok(Value::String(lines))
+        }
+        _ => todo!(),
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Value>> {
+    let lines = std::fs::read_to_string(path).map_err(Error::IO)?;
+    parse_exprs(&lines).map_err(Error::Parser)
+}
+
+pub fn read_all(vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(path)] => {
+            let vals = load(path)?;
+            Ok(Value::List(vals.into()))
+        }
+        _ => todo!(),
+    }
+}
+
+fn format_string(template: &str, args: &[Value]) -> Result<String> {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('a') => {
+                let arg = args
+                    .next()
+                    .ok_or_else(|| Error::Format("not enough arguments".to_owned()))?;
+                output.push_str(&arg.to_display_string());
+            }
+            Some('s') => {
+                let arg = args
+                    .next()
+                    .ok_or_else(|| Error::Format("not enough arguments".to_owned()))?;
+                output.push_str(&arg.to_string());
+            }
+            Some('%') => output.push('\n'),
+            Some('~') => output.push('~'),
+            Some(c) => return Err(Error::Format(format!("unknown directive ~{}", c))),
+            None => return Err(Error::Format("trailing ~".to_owned())),
+        }
+    }
+    Ok(output)
+}
+
+pub fn format(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::String(template), args @ ..] => format_string(template, args).map(Value::String),
+        [Value::Bool(false), Value::String(template), args @ ..] => {
+            format_string(template, args).map(Value::String)
+        }
+        [Value::Bool(true), Value::String(template), args @ ..] => {
+            let buf = format_string(template, args)?;
+            if let Some(port_id) = env.current_output_port() {
+                return write_port(env, &port_id, &buf);
+            }
+            print!("{}", buf);
+            stdout().flush().map_err(Error::IO)?;
+            Ok(Value::Bool(true))
+        }
+        [Value::Port(port_id), Value::String(template), args @ ..] => {
+            let buf = format_string(template, args)?;
+            let writer = env.get_write_port(port_id)?;
+            writer.write_all(buf.as_bytes()).map_err(Error::IO)?;
+            Ok(Value::Bool(true))
+        }
+        [Value::Bool(_) | Value::Port(_), val, ..] => {
+            Err(Error::TypeMismatch("string".to_owned(), val.clone()))
+        }
+        [val, Value::String(_), ..] => Err(Error::TypeMismatch(
+            "port or boolean".to_owned(),
+            val.clone(),
+        )),
+        [val, ..] => Err(Error::TypeMismatch("string".to_owned(), val.clone())),
+        [] => Err(Error::NumArgs(2, vec![])),
+    }
+}
+
+pub fn bound(env: &mut Env, vals: &[Value]) -> Result<Value> {
+    match vals {
+        [Value::Atom(var)] => Ok(Value::Bool(env.get_var(var.as_str()).is_ok())),
+        [val] => Err(Error::TypeMismatch("symbol".to_owned(), val.clone())),
+        _ => Err(Error::NumArgs(1, vals.to_vec())),
     }
 }
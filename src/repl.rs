@@ -1,14 +1,233 @@
-use std::io::{self, BufRead, Write};
+use std::{
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
 
-use crate::{env::Env, eval::eval, parser::parse_expr};
+use crate::{
+    env::Env,
+    error::Error,
+    eval::eval,
+    parser::{parse_expr, parse_exprs},
+    util::intersperse,
+    value::{lambda_list, NativeClosure, Value},
+};
+
+/// A REPL command introduced by a leading comma rather than Scheme input.
+#[derive(Debug, PartialEq, Eq)]
+enum MetaCommand<'a> {
+    Help,
+    Env,
+    Load(&'a str),
+    Save(&'a str),
+    LoadSession(&'a str),
+    Limit(Option<usize>),
+    Depth(Option<usize>),
+    Quit,
+    Unknown(&'a str),
+}
+
+static HELP: &str = "\
+,help         Show this list of commands
+,env          List the current bindings
+,load <file>  Load and evaluate a file
+,save <file>  Save the current definitions to a file
+,load-session <file>
+              Restore definitions saved with ,save
+,limit <n|off>
+              Print at most n characters of each result
+,depth <n|off>
+              Fail calls nested more than n deep
+,quit         Exit the REPL (same as quit)";
+
+fn parse_meta_command(input: &str) -> Option<MetaCommand<'_>> {
+    let input = input.strip_prefix(',')?.trim();
+    let (name, arg) = match input.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+    let command = match (name, arg) {
+        ("help", "") => MetaCommand::Help,
+        ("env", "") => MetaCommand::Env,
+        ("load", path) if !path.is_empty() => MetaCommand::Load(path),
+        ("save", path) if !path.is_empty() => MetaCommand::Save(path),
+        ("load-session", path) if !path.is_empty() => MetaCommand::LoadSession(path),
+        ("limit", "off") => MetaCommand::Limit(None),
+        ("limit", n) if n.parse::<usize>().is_ok() => MetaCommand::Limit(n.parse().ok()),
+        ("depth", "off") => MetaCommand::Depth(None),
+        ("depth", n) if n.parse::<usize>().is_ok() => MetaCommand::Depth(n.parse().ok()),
+        ("quit", "") => MetaCommand::Quit,
+        _ => MetaCommand::Unknown(input),
+    };
+    Some(command)
+}
+
+/// Runs a metacommand, returning whether the REPL should exit.
+fn run_meta_command(env: &mut Env, command: MetaCommand) -> bool {
+    match command {
+        MetaCommand::Help => println!("{}", HELP),
+        MetaCommand::Env => println!("{}", env.bound_names().join(" ")),
+        MetaCommand::Load(path) => {
+            let form = Value::List(Rc::new([
+                Value::Atom("load".into()),
+                Value::String(path.to_owned()),
+            ]));
+            match eval(env, &form) {
+                Ok(value) => println!("{}", value),
+                Err(e) => println!("Eval error: {}", e),
+            }
+        }
+        MetaCommand::Save(path) => {
+            if let Err(e) = std::fs::write(path, session_source(env)) {
+                println!("IO error: {}", e);
+            }
+        }
+        MetaCommand::LoadSession(path) => {
+            if let Err(e) = load_session(env, path) {
+                println!("Eval error: {}", e);
+            }
+        }
+        MetaCommand::Limit(limit) => env.set_print_limit(limit),
+        MetaCommand::Depth(limit) => env.set_depth_limit(limit),
+        MetaCommand::Quit => return true,
+        MetaCommand::Unknown(command) => {
+            println!("Unknown command: ,{} (try ,help)", command)
+        }
+    }
+    false
+}
+
+/// The source of an expression evaluating to `val`, if it can be written.
+fn value_source(val: &Value) -> Option<String> {
+    match val {
+        Value::Atom(_) | Value::List(_) | Value::DottedList(_, _) => {
+            Some(format!("'{}", val.unparse().ok()?))
+        }
+        Value::Func {
+            name: _,
+            params,
+            optionals,
+            vararg,
+            body,
+            closure: _,
+        } => {
+            let params = lambda_list(params, optionals, vararg);
+            Some(format!("(lambda {} {})", params, intersperse(body)))
+        }
+        _ => val.unparse().ok(),
+    }
+}
+
+/// A `define-memoized` form defining `name` as a memoized `func`, whose
+/// cache starts out empty again.
+fn memoized_source(name: &str, func: &Value) -> Option<String> {
+    let Value::Func {
+        params,
+        optionals,
+        vararg,
+        body,
+        ..
+    } = func
+    else {
+        return None;
+    };
+    let params = lambda_list(params, optionals, vararg);
+    let signature = match params.strip_prefix('(') {
+        Some(")") => format!("({})", name),
+        Some(params) => format!("({} {}", name, params),
+        None => format!("({} . {})", name, params),
+    };
+    Some(format!(
+        "(define-memoized {} {})",
+        signature,
+        intersperse(body)
+    ))
+}
+
+/// The source of the definitions made on top of the primitive bindings.
+/// Functions are saved as their lambda forms, or `define-memoized` forms,
+/// and lose whatever they had captured. Constants stay constant, while
+/// values that can't be written back, such as ports, are skipped.
+fn session_source(env: &Env) -> String {
+    let primitives = Env::primitive_bindings();
+    let mut source = String::new();
+    for (name, val) in env.bindings() {
+        if primitives.get_var(name) == Ok(val) {
+            continue;
+        }
+        let val = match val {
+            Value::PrimitiveFunc(_) | Value::IOFunc(_) => primitives
+                .bindings()
+                .into_iter()
+                .find(|(_, primitive)| *primitive == val)
+                .map(|(primitive, _)| primitive.to_owned()),
+            Value::NativeClosure(NativeClosure::Memoize(func, _)) => {
+                if let Some(definition) = memoized_source(name, func) {
+                    source.push_str(&definition);
+                    source.push('\n');
+                }
+                continue;
+            }
+            _ => value_source(val),
+        };
+        let define = match env.is_constant(name) {
+            true => "define-constant",
+            false => "define",
+        };
+        if let Some(val) = val {
+            source.push_str(&format!("({} {} {})\n", define, name, val));
+        }
+    }
+    source
+}
+
+fn load_session(env: &mut Env, path: &str) -> Result<(), Error> {
+    let source = std::fs::read_to_string(path).map_err(Error::IO)?;
+    for form in parse_exprs(&source).map_err(Error::Parser)? {
+        eval(env, &form)?;
+    }
+    Ok(())
+}
+
+/// Evaluates a line of input, returning what to print for it. Forms run only
+/// for their effects return an unspecified value and print nothing, and so do
+/// definitions: `define` returns the value it binds, so they are recognized by
+/// their form instead.
+pub fn eval_input(env: &mut Env, input: &str) -> Option<String> {
+    eval_line(env, input).unwrap_or_else(Some)
+}
+
+/// Like `eval_input`, but keeps a failure apart from the printed value so a
+/// caller can report it elsewhere.
+pub fn eval_line(env: &mut Env, input: &str) -> Result<Option<String>, String> {
+    match parse_expr(input) {
+        Ok(value) => match eval(env, &value) {
+            Ok(Value::Unspecified) => Ok(None),
+            Ok(_) if is_definition(&value) => Ok(None),
+            Ok(value) => match env.print_limit() {
+                Some(limit) => Ok(Some(value.to_string_limited(limit))),
+                None => Ok(Some(value.to_string())),
+            },
+            Err(e) => Err(format!("Eval error: {}", e)),
+        },
+        Err(e) => Err(format!("Parse error: {}", e)),
+    }
+}
+
+fn is_definition(form: &Value) -> bool {
+    match form {
+        Value::List(vals) => {
+            matches!(&vals[..], [Value::Atom(atom), ..] if ["define", "define-memoized", "define-constant", "define-syntax"].contains(&atom.as_str()))
+        }
+        _ => false,
+    }
+}
 
 fn print(line: &str) {
     print!("{}", line);
     io::stdout().flush().unwrap();
 }
 
-pub fn run() {
-    let mut env = Env::primitive_bindings();
+pub fn run(mut env: Env) {
     print("Lisp>>> ");
     let stdin = io::stdin();
     for line in stdin.lock().lines() {
@@ -17,13 +236,173 @@ pub fn run() {
         if input == "quit" {
             return;
         }
-        match parse_expr(input) {
-            Ok(value) => match eval(&mut env, &value) {
-                Ok(value) => println!("{}", value),
-                Err(e) => println!("Eval error: {}", e),
-            },
-            Err(e) => println!("Parse error: {}", e),
+        if let Some(command) = parse_meta_command(input) {
+            if run_meta_command(&mut env, command) {
+                return;
+            }
+            print("Lisp>>> ");
+            continue;
+        }
+        if let Some(output) = eval_input(&mut env, input) {
+            println!("{}", output);
         }
         print("Lisp>>> ");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{eval_input, parse_meta_command, run_meta_command, session_source, MetaCommand};
+    use crate::{
+        env::Env,
+        error::Error,
+        eval::eval,
+        parser::{parse_expr, parse_exprs},
+        value::Value,
+    };
+
+    #[test]
+    fn meta_command() {
+        let cases = vec![
+            (",help", Some(MetaCommand::Help)),
+            (",env", Some(MetaCommand::Env)),
+            (",quit", Some(MetaCommand::Quit)),
+            (",load foo.scm", Some(MetaCommand::Load("foo.scm"))),
+            (",save s.scm", Some(MetaCommand::Save("s.scm"))),
+            (
+                ",load-session s.scm",
+                Some(MetaCommand::LoadSession("s.scm")),
+            ),
+            (",load   foo.scm  ", Some(MetaCommand::Load("foo.scm"))),
+            (",load", Some(MetaCommand::Unknown("load"))),
+            (",limit 80", Some(MetaCommand::Limit(Some(80)))),
+            (",limit off", Some(MetaCommand::Limit(None))),
+            (",limit lots", Some(MetaCommand::Unknown("limit lots"))),
+            (",depth 5000", Some(MetaCommand::Depth(Some(5000)))),
+            (",depth off", Some(MetaCommand::Depth(None))),
+            (",frobnicate", Some(MetaCommand::Unknown("frobnicate"))),
+            ("(load \"foo.scm\")", None),
+            ("'(a ,b)", None),
+            ("quit", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(expected, parse_meta_command(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn quiet_results() {
+        let mut env = Env::primitive_bindings();
+        let cases = vec![
+            ("(define x 0)", None),
+            ("(define (f) x)", None),
+            ("(+ 1 2)", Some("3")),
+            ("(set! x 1)", None),
+            ("x", Some("1")),
+            ("(when #f x)", None),
+            (
+                "(car x)",
+                Some("Eval error: Invalid type: expected pair, found 1"),
+            ),
+            ("(car", Some("Parse error: No more tokens")),
+        ];
+        for (input, expected) in cases {
+            let expected = expected.map(str::to_owned);
+            assert_eq!(expected, eval_input(&mut env, input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn print_limit() {
+        let mut env = Env::primitive_bindings();
+        run_meta_command(&mut env, MetaCommand::Limit(Some(20)));
+        let long_list = format!("'({})", vec!["x"; 100000].join(" "));
+        let cases = vec![
+            (long_list.as_str(), Some("(x x x x x x x x x x...")),
+            (
+                "'(\"abc\" \"abc\" \"abc\")",
+                Some("(\"abc\" \"abc\" \"abc\")"),
+            ),
+            (
+                "'(\"abc\" \"abc\" \"abc\" \"abc\")",
+                Some("(\"abc\" \"abc\" \"abc\" \"..."),
+            ),
+            (
+                "\"λλλλλλλλλλλλλλλλλλλλλλ\"",
+                Some("\"λλλλλλλλλλλλλλλλλλλ..."),
+            ),
+        ];
+        for (input, expected) in cases {
+            let expected = expected.map(str::to_owned);
+            assert_eq!(expected, eval_input(&mut env, input), "{}", input);
+        }
+        run_meta_command(&mut env, MetaCommand::Limit(None));
+        let output = eval_input(&mut env, &long_list).unwrap();
+        assert_eq!(200001, output.len());
+    }
+
+    #[test]
+    fn session() {
+        let mut env = Env::primitive_bindings();
+        let defines = vec![
+            "(define x 3)",
+            "(define names '(a \"b\" #\\c))",
+            "(define (add a b) (+ a b x))",
+            "(define (first a . more) a)",
+            "(define (all . more) more)",
+            "(define (opt a #!optional (b (+ a 1))) (* a b))",
+            "(define plus +)",
+            "(define port (open-input-string \"\"))",
+            "(define ports (cons port (quote ())))",
+            "(define bytes (bytevector 1 2))",
+            "(define quoted (string #\\a (integer->char 34) #\\b #\\\\))",
+            "(define (greet) \"say \\\"hi\\\"\")",
+            "(define-memoized (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))",
+            "(define-memoized (pick a #!optional (b 2) . rest) (cons b rest))",
+            "(define-memoized (none) 'none)",
+            "(define-memoized (every . args) args)",
+            "(define-constant k 3)",
+        ];
+        for define in defines {
+            eval(&mut env, &parse_expr(define).unwrap()).unwrap();
+        }
+        let source = session_source(&env);
+        assert!(
+            source.contains("(define-memoized (fib n) (if "),
+            "{}",
+            source
+        );
+        let mut restored = Env::primitive_bindings();
+        for form in parse_exprs(&source).unwrap() {
+            eval(&mut restored, &form).unwrap();
+        }
+        let cases = vec![
+            ("names", "(a \"b\" #\\c)"),
+            ("(add 1 2)", "6"),
+            ("(first 1 2 3)", "1"),
+            ("(all 1 2 3)", "(1 2 3)"),
+            ("(opt 3)", "12"),
+            ("(plus 1 2)", "3"),
+            ("bytes", "#u8(1 2)"),
+            ("quoted", "\"a\\\"b\\\\\""),
+            ("(string-length quoted)", "4"),
+            ("(greet)", "\"say \\\"hi\\\"\""),
+            ("(fib 30)", "832040"),
+            ("(pick 1)", "(2)"),
+            ("(pick 1 3 4)", "(3 4)"),
+            ("(none)", "none"),
+            ("(every 1 2)", "(1 2)"),
+        ];
+        for (input, expected) in cases {
+            let actual = eval(&mut restored, &parse_expr(input).unwrap()).unwrap();
+            assert_eq!(expected, actual.to_string(), "{}", input);
+        }
+        assert_eq!(
+            Err(Error::Constant("k".to_owned())),
+            eval(&mut restored, &parse_expr("(set! k 4)").unwrap())
+        );
+        assert_eq!(Ok(&Value::Number(3.into())), restored.get_var("k"));
+        assert!(restored.get_var("port").is_err());
+        assert!(restored.get_var("ports").is_err());
+    }
+}
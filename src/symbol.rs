@@ -0,0 +1,117 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display};
+
+/// An interned symbol name. Every occurrence of the same name shares one id,
+/// so symbols are compared and cloned without touching their strings.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// The names interned so far. They are leaked so that `Symbol::as_str` can
+/// hand out `'static` borrows; the set of distinct names a program uses is
+/// small and lives as long as the program anyway.
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+    /// The counter `Symbol::gensym` appends to its base names.
+    next_gensym: u32,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Symbol {
+    pub fn new(name: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(id) = interner.ids.get(name) {
+                return Symbol(*id);
+            }
+            let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+            let id = interner.names.len() as u32;
+            interner.names.push(name);
+            interner.ids.insert(name, id);
+            Symbol(id)
+        })
+    }
+
+    /// A symbol named after `base` that no name interned so far refers to,
+    /// for bindings that must not clash with anything the program wrote.
+    pub fn gensym(base: &str) -> Symbol {
+        let name = INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            loop {
+                interner.next_gensym += 1;
+                let name = format!("{}.{}", base, interner.next_gensym);
+                if !interner.ids.contains_key(name.as_str()) {
+                    return name;
+                }
+            }
+        });
+        Symbol::new(&name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().names[self.0 as usize])
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Symbol, INTERNER};
+
+    #[test]
+    fn intern() {
+        assert_eq!(Symbol::new("lambda"), Symbol::new("lambda"));
+        assert_ne!(Symbol::new("lambda"), Symbol::new("define"));
+        assert_eq!("lambda", Symbol::new("lambda").as_str());
+        assert!(Symbol::new("if") == *"if");
+    }
+
+    #[test]
+    fn gensym() {
+        let taken = Symbol::new("tmp.1");
+        let first = Symbol::gensym("tmp");
+        let second = Symbol::gensym("tmp");
+        assert_ne!(taken, first);
+        assert_ne!(first, second);
+        assert!(first.as_str().starts_with("tmp."));
+    }
+
+    #[test]
+    fn intern_many() {
+        let names: Vec<String> = (0..10000).map(|i| format!("sym{}", i)).collect();
+        let first: Vec<Symbol> = names.iter().map(|name| Symbol::new(name)).collect();
+        let interned = INTERNER.with(|interner| interner.borrow().names.len());
+        let second: Vec<Symbol> = names.iter().map(|name| Symbol::new(name)).collect();
+        assert_eq!(first, second);
+        assert_eq!(
+            interned,
+            INTERNER.with(|interner| interner.borrow().names.len())
+        );
+    }
+}
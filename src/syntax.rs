@@ -0,0 +1,381 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    env::Env,
+    error::Error,
+    eval::is_special_form,
+    symbol::Symbol,
+    value::{Value, QUOTE},
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+const ELLIPSIS: &str = "...";
+
+/// Identifiers a template may use that name syntax rather than variables,
+/// so they are never renamed.
+static KEYWORDS: &[&str] = &["else", "=>", ELLIPSIS, "_"];
+
+/// A macro defined with `syntax-rules`: the identifiers its patterns match
+/// literally, and its `(pattern template)` rules in the order they're tried.
+#[derive(Debug)]
+pub struct SyntaxRules {
+    literals: Vec<Symbol>,
+    rules: Vec<(Value, Value)>,
+}
+
+/// What a pattern variable matched: a single form, or one binding per
+/// repetition of the ellipsis it appeared under.
+#[derive(Clone)]
+enum Binding {
+    One(Value),
+    Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<Symbol, Binding>;
+
+impl SyntaxRules {
+    /// Reads `(syntax-rules (literal ...) (pattern template) ...)`, or
+    /// returns `None` if `spec` isn't shaped like that.
+    pub fn parse(spec: &Value) -> Option<SyntaxRules> {
+        let Value::List(vals) = spec else {
+            return None;
+        };
+        let [Value::Atom(keyword), Value::List(literals), rules @ ..] = &vals[..] else {
+            return None;
+        };
+        if keyword != "syntax-rules" {
+            return None;
+        }
+        let literals = literals
+            .iter()
+            .map(|literal| match literal {
+                Value::Atom(literal) => Some(*literal),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let rules = rules
+            .iter()
+            .map(|rule| match rule {
+                Value::List(rule) => match &rule[..] {
+                    [pattern @ Value::List(_), template] => {
+                        Some((pattern.clone(), template.clone()))
+                    }
+                    [pattern @ Value::DottedList(_, _), template] => {
+                        Some((pattern.clone(), template.clone()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(SyntaxRules { literals, rules })
+    }
+
+    /// Rewrites a use of the macro with the first rule whose pattern matches
+    /// it. The keyword position of a pattern is ignored. Identifiers the
+    /// template introduces, other than syntactic keywords, are renamed to
+    /// fresh symbols so that bindings the template makes can't capture the
+    /// user's variables; `Env` resolves a renamed identifier the expansion
+    /// leaves unbound to the name it was written as.
+    pub fn expand(&self, env: &mut Env, form: &Value) -> Result<Value> {
+        for (pattern, template) in &self.rules {
+            let mut binds = Bindings::new();
+            if self.match_tail(pattern, form, &mut binds) {
+                let mut renames = HashMap::new();
+                return self.instantiate(env, template, &binds, &mut renames, true);
+            }
+        }
+        Err(Error::BadSpecialForm(
+            "no syntax rule matches".to_owned(),
+            form.clone(),
+        ))
+    }
+
+    /// Matches everything after the keyword of a pattern and a form.
+    fn match_tail(&self, pattern: &Value, form: &Value, binds: &mut Bindings) -> bool {
+        let (pats, pat_tail) = match pattern {
+            Value::List(pats) => (&pats[1..], None),
+            Value::DottedList(pats, tail) => (&pats[1..], Some(tail.as_ref())),
+            _ => return false,
+        };
+        let (forms, form_tail) = match form {
+            Value::List(forms) => (&forms[1..], None),
+            Value::DottedList(forms, tail) => (&forms[1..], Some(tail.as_ref())),
+            _ => return false,
+        };
+        self.match_items(pats, pat_tail, forms, form_tail, binds)
+    }
+
+    fn match_pattern(&self, pattern: &Value, form: &Value, binds: &mut Bindings) -> bool {
+        match (pattern, form) {
+            (Value::Atom(atom), _) if atom == "_" => true,
+            (Value::Atom(atom), _) if self.literals.contains(atom) => {
+                matches!(form, Value::Atom(name) if name == atom)
+            }
+            (Value::Atom(atom), _) => {
+                binds.insert(*atom, Binding::One(form.clone()));
+                true
+            }
+            (Value::List(pats), Value::List(forms)) => {
+                self.match_items(pats, None, forms, None, binds)
+            }
+            (Value::List(pats), Value::DottedList(forms, tail)) => {
+                self.match_items(pats, None, forms, Some(tail), binds)
+            }
+            (Value::DottedList(pats, pat_tail), Value::List(forms)) => {
+                self.match_items(pats, Some(pat_tail), forms, None, binds)
+            }
+            (Value::DottedList(pats, pat_tail), Value::DottedList(forms, tail)) => {
+                self.match_items(pats, Some(pat_tail), forms, Some(tail), binds)
+            }
+            _ => pattern == form,
+        }
+    }
+
+    fn match_items(
+        &self,
+        pats: &[Value],
+        pat_tail: Option<&Value>,
+        forms: &[Value],
+        form_tail: Option<&Value>,
+        binds: &mut Bindings,
+    ) -> bool {
+        let ellipsis = pats
+            .iter()
+            .position(|pat| matches!(pat, Value::Atom(atom) if atom == ELLIPSIS));
+        let Some(i) = ellipsis.filter(|i| *i > 0) else {
+            return self.match_fixed(pats, pat_tail, forms, form_tail, binds);
+        };
+        let (before, repeated, after) = (&pats[..i - 1], &pats[i - 1], &pats[i + 1..]);
+        if pat_tail.is_some() || form_tail.is_some() || forms.len() < before.len() + after.len() {
+            return false;
+        }
+        let (head, rest) = forms.split_at(before.len());
+        let (middle, end) = rest.split_at(rest.len() - after.len());
+        if !self.match_fixed(before, None, head, None, binds)
+            || !self.match_fixed(after, None, end, None, binds)
+        {
+            return false;
+        }
+        let mut repetitions = Vec::new();
+        for form in middle {
+            let mut repetition = Bindings::new();
+            if !self.match_pattern(repeated, form, &mut repetition) {
+                return false;
+            }
+            repetitions.push(repetition);
+        }
+        for var in self.pattern_vars(repeated) {
+            let matches = repetitions
+                .iter_mut()
+                .map(|repetition| repetition.remove(&var).expect("every repetition binds var"))
+                .collect();
+            binds.insert(var, Binding::Many(matches));
+        }
+        true
+    }
+
+    /// Matches patterns one to one with forms, the pattern's tail taking
+    /// whatever forms are left over.
+    fn match_fixed(
+        &self,
+        pats: &[Value],
+        pat_tail: Option<&Value>,
+        forms: &[Value],
+        form_tail: Option<&Value>,
+        binds: &mut Bindings,
+    ) -> bool {
+        match pat_tail {
+            None if pats.len() != forms.len() || form_tail.is_some() => false,
+            Some(_) if pats.len() > forms.len() => false,
+            _ => {
+                let (head, rest) = forms.split_at(pats.len());
+                if !pats
+                    .iter()
+                    .zip(head)
+                    .all(|(pat, form)| self.match_pattern(pat, form, binds))
+                {
+                    return false;
+                }
+                let Some(pat_tail) = pat_tail else {
+                    return true;
+                };
+                let rest = match form_tail {
+                    None => Value::List(rest.into()),
+                    Some(tail) if rest.is_empty() => tail.clone(),
+                    Some(tail) => Value::DottedList(rest.into(), Rc::new(tail.clone())),
+                };
+                self.match_pattern(pat_tail, &rest, binds)
+            }
+        }
+    }
+
+    fn pattern_vars(&self, pattern: &Value) -> Vec<Symbol> {
+        match pattern {
+            Value::Atom(atom)
+                if atom == "_" || atom == ELLIPSIS || self.literals.contains(atom) =>
+            {
+                vec![]
+            }
+            Value::Atom(atom) => vec![*atom],
+            Value::List(pats) => pats.iter().flat_map(|pat| self.pattern_vars(pat)).collect(),
+            Value::DottedList(pats, tail) => pats
+                .iter()
+                .chain(std::iter::once(tail.as_ref()))
+                .flat_map(|pat| self.pattern_vars(pat))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn instantiate(
+        &self,
+        env: &mut Env,
+        template: &Value,
+        binds: &Bindings,
+        renames: &mut HashMap<Symbol, Symbol>,
+        rename: bool,
+    ) -> Result<Value> {
+        match template {
+            Value::Atom(atom) => match binds.get(atom) {
+                Some(Binding::One(val)) => Ok(val.clone()),
+                Some(Binding::Many(_)) => Err(Error::BadSpecialForm(
+                    "pattern variable used without an ellipsis".to_owned(),
+                    template.clone(),
+                )),
+                None if rename => Ok(Value::Atom(rename_introduced(env, *atom, renames))),
+                None => Ok(template.clone()),
+            },
+            Value::List(items) => match &items[..] {
+                [quote @ Value::Atom(atom), datum] if atom == QUOTE => {
+                    let datum = self.instantiate(env, datum, binds, renames, false)?;
+                    Ok(Value::List([quote.clone(), datum].into()))
+                }
+                _ => Ok(Value::List(
+                    self.instantiate_items(env, items, binds, renames, rename)?
+                        .into(),
+                )),
+            },
+            Value::DottedList(items, tail) => {
+                let items = self.instantiate_items(env, items, binds, renames, rename)?;
+                let tail = self.instantiate(env, tail, binds, renames, rename)?;
+                Ok(Value::DottedList(items.into(), Rc::new(tail)))
+            }
+            _ => Ok(template.clone()),
+        }
+    }
+
+    fn instantiate_items(
+        &self,
+        env: &mut Env,
+        items: &[Value],
+        binds: &Bindings,
+        renames: &mut HashMap<Symbol, Symbol>,
+        rename: bool,
+    ) -> Result<Vec<Value>> {
+        let mut vals = Vec::new();
+        let mut items = items.iter().peekable();
+        while let Some(item) = items.next() {
+            if !matches!(items.peek(), Some(Value::Atom(atom)) if atom == ELLIPSIS) {
+                vals.push(self.instantiate(env, item, binds, renames, rename)?);
+                continue;
+            }
+            items.next();
+            let vars: Vec<(Symbol, &Vec<Binding>)> = self
+                .pattern_vars(item)
+                .into_iter()
+                .filter_map(|var| match binds.get(&var) {
+                    Some(Binding::Many(matches)) => Some((var, matches)),
+                    _ => None,
+                })
+                .collect();
+            let Some((_, first)) = vars.first() else {
+                return Err(Error::BadSpecialForm(
+                    "ellipsis follows no repeated pattern variable".to_owned(),
+                    item.clone(),
+                ));
+            };
+            for i in 0..first.len() {
+                let mut repetition = binds.clone();
+                for (var, matches) in &vars {
+                    let Some(binding) = matches.get(i) else {
+                        return Err(Error::BadSpecialForm(
+                            "pattern variables repeat a different number of times".to_owned(),
+                            item.clone(),
+                        ));
+                    };
+                    repetition.insert(*var, binding.clone());
+                }
+                vals.push(self.instantiate(env, item, &repetition, renames, rename)?);
+            }
+        }
+        Ok(vals)
+    }
+}
+
+/// Gives an identifier introduced by a template the same fresh name
+/// everywhere it appears in one expansion.
+fn rename_introduced(env: &mut Env, atom: Symbol, renames: &mut HashMap<Symbol, Symbol>) -> Symbol {
+    let name = atom.as_str();
+    if is_special_form(name) || env.is_macro(name) || KEYWORDS.contains(&name) {
+        return atom;
+    }
+    *renames.entry(atom).or_insert_with(|| {
+        let alias = Symbol::gensym(name);
+        env.define_alias(alias, atom);
+        alias
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyntaxRules;
+    use crate::{env::Env, parser::parse_expr, value::Value};
+
+    /// Expands `form` with `rules` defined as the macro `form` uses.
+    fn expand(rules: &str, form: &str) -> String {
+        let rules = SyntaxRules::parse(&parse_expr(rules).unwrap()).unwrap();
+        let form = parse_expr(form).unwrap();
+        let Value::List(vals) = &form else {
+            panic!("not a macro use: {}", form);
+        };
+        let mut env = Env::primitive_bindings();
+        env.define_macro(vals[0].to_string(), rules).unwrap();
+        let rules = env.get_macro(&vals[0].to_string()).unwrap();
+        match rules.expand(&mut env, &form) {
+            Ok(val) => val.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn matches_ellipses_and_literals() {
+        let my_or = "(syntax-rules () ((_) #f) ((_ e) e) ((_ e r ...) (if e e (my-or r ...))))";
+        assert_eq!("#f", expand(my_or, "(my-or)"));
+        assert_eq!("x", expand(my_or, "(my-or x)"));
+        assert_eq!("(if a a (my-or b c))", expand(my_or, "(my-or a b c)"));
+        let arrow = "(syntax-rules (=>) ((_ a => b) '(a b)))";
+        assert_eq!("'(x y)", expand(arrow, "(arrow x => y)"));
+        assert_eq!(
+            "no syntax rule matches: (arrow x y z)",
+            expand(arrow, "(arrow x y z)")
+        );
+        let pairs = "(syntax-rules () ((_ (k v) ...) '((k ...) (v ...))))";
+        assert_eq!("'((a b) (1 2))", expand(pairs, "(pairs (a 1) (b 2))"));
+    }
+
+    #[test]
+    fn renames_introduced_identifiers() {
+        let swap = "(syntax-rules () ((_ a b) ((lambda (tmp) (set! a b) (set! b tmp)) a)))";
+        let expanded = expand(swap, "(swap! tmp other)");
+        let tmp = expanded
+            .split(['(', ')', ' '])
+            .find(|part| part.starts_with("tmp."))
+            .unwrap();
+        assert_eq!(
+            format!("((lambda ({tmp}) (set! tmp other) (set! other {tmp})) tmp)"),
+            expanded
+        );
+    }
+}
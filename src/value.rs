@@ -1,18 +1,127 @@
-use std::fmt::Display;
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
 
-use crate::{env::Closure, util::intersperse};
+use crate::{
+    env::Closure,
+    error::Error,
+    hash::{HashTable, ValueKey},
+    lexer::{self, Token, CHAR_NAMES},
+    number::Number,
+    symbol::Symbol,
+    util::intersperse,
+};
 
 pub static QUOTE: &str = "quote";
 pub static TRUE: &str = "#t";
 pub static FALSE: &str = "#f";
 
+/// The two-element forms the reader abbreviates, with their prefixes.
+static ABBREVIATIONS: &[(&str, &str)] = &[
+    (QUOTE, "'"),
+    ("quasiquote", "`"),
+    ("unquote", ","),
+    ("unquote-splicing", ",@"),
+];
+
+/// The reader prefix and datum for a list written with an abbreviation.
+fn abbreviation(list: &[Value]) -> Option<(&'static str, &Value)> {
+    match list {
+        [Value::Atom(atom), datum] => ABBREVIATIONS
+            .iter()
+            .find(|(name, _)| atom.as_str() == *name)
+            .map(|(_, prefix)| (*prefix, datum)),
+        _ => None,
+    }
+}
+
+/// Writes a symbol's name, between bars if the reader would not read it back
+/// as the same plain symbol.
+fn write_atom(f: &mut std::fmt::Formatter<'_>, name: &str) -> std::fmt::Result {
+    if name.bytes().all(|b| b.is_ascii_lowercase()) && !name.is_empty() {
+        return write!(f, "{}", name);
+    }
+    let mut tokens = lexer::tokens(name);
+    if let (Some(Token::Atom(atom)), None) = (tokens.next(), tokens.next()) {
+        if atom == name {
+            return write!(f, "{}", name);
+        }
+    }
+    write!(f, "|")?;
+    for c in name.chars() {
+        match c {
+            '|' | '\\' => write!(f, "\\{}", c)?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\x{:x};", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "|")
+}
+
+/// Writes a string in quotes, escaping what the reader would otherwise take
+/// as its end or as an escape, and control characters.
+fn write_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => write!(f, "\\{}", c)?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\x{:x};", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// The parameter list of a procedure as written in a `lambda` form.
+pub fn lambda_list(
+    params: &[String],
+    optionals: &[(String, Value)],
+    vararg: &Option<String>,
+) -> String {
+    let mut parts = params.to_vec();
+    if !optionals.is_empty() {
+        parts.push("#!optional".to_owned());
+        parts.extend(
+            optionals
+                .iter()
+                .map(|(name, default)| format!("({} {})", name, default)),
+        );
+    }
+    match vararg {
+        Some(vararg) if parts.is_empty() => vararg.clone(),
+        Some(vararg) => format!("({} . {})", intersperse(&parts), vararg),
+        None => format!("({})", intersperse(&parts)),
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PrimitiveFunc {
     Add,
     Sub,
     Mul,
     Div,
+    Quotient,
     Rem,
+    FloorDiv,
+    FloorQuotient,
+    FloorRemainder,
+    TruncateDiv,
+    ExactIntegerSqrt,
+    Increment,
+    Decrement,
+    Boolean,
+    IsInteger,
+    IsRational,
+    IsReal,
+    IsExact,
+    IsInexact,
+    IsExactInteger,
+    IsExactNonnegativeInteger,
+    Numerator,
+    Denominator,
+    Rationalize,
     Eq,
     Lt,
     Gt,
@@ -26,11 +135,63 @@ pub enum PrimitiveFunc {
     StringGt,
     StringLe,
     StringGe,
+    StringCiEq,
+    StringCiLt,
+    StringCiGt,
+    StringCiLe,
+    StringCiGe,
     Car,
     Cdr,
     Cons,
     Eqv,
     Equal,
+    Values,
+    CharToInteger,
+    IntegerToChar,
+    CharUpcase,
+    CharDowncase,
+    CharFoldcase,
+    StringFoldcase,
+    StringLength,
+    StringRef,
+    Substring,
+    Bytevector,
+    MakeBytevector,
+    BytevectorU8Ref,
+    BytevectorU8Set,
+    BytevectorLength,
+    StringToVector,
+    VectorToString,
+    String,
+    ListToString,
+    StringTrim,
+    StringTrimLeft,
+    StringTrimRight,
+    VectorAppend,
+    Gensym,
+    VectorCopy,
+    Not,
+    ErrorKind,
+    ErrorMessage,
+    MakeHashTable,
+    IsHashTable,
+    HashSet,
+    HashRef,
+    NestedRef,
+    HashCount,
+    EqualHash,
+    AlistToHashTable,
+    HashTableToAlist,
+    GetEnvironmentVariable,
+    Identity,
+    Compose,
+    Curry,
+    Append,
+    Flatten,
+    Reverse,
+    EofObject,
+    IsEofObject,
+    IsPort,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -38,61 +199,602 @@ pub enum IOFunc {
     Apply,
     MakeReadPort,
     MakeWritePort,
+    MakeStringReadPort,
+    MakeBinaryReadPort,
+    MakeBinaryWritePort,
     ClosePort,
+    CallWithInputFile,
+    CallWithOutputFile,
+    WithInputFromFile,
+    WithOutputToFile,
     Read,
+    ReadLine,
+    ReadChar,
+    PeekChar,
+    CharReady,
+    IsInputPort,
+    IsOutputPort,
+    WithExceptionHandler,
+    Raise,
+    RaiseContinuable,
+    WriteChar,
     Write,
+    WriteShared,
+    Display,
+    Describe,
+    ReadU8,
+    WriteU8,
+    ReadBytevector,
+    WriteBytevector,
+    CommandLine,
+    AddToLoadPath,
+    HashUpdate,
+    Sort,
+    ListSort,
+    Map,
+    ForEach,
+    Filter,
+    Remove,
+    Partition,
+    Count,
+    Any,
+    Every,
+    Find,
+    Fold,
+    FoldRight,
+    DeleteDuplicates,
+    AppendMap,
+    AlistUpdate,
+    AlistDelete,
+    Merge,
     ReadContents,
     ReadAll,
+    Format,
+    Bound,
+    CallWithValues,
+    Force,
+    StreamCdr,
+    StreamRef,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PromiseState {
+    Delayed { expr: Value, closure: Closure },
+    Forced(Value),
+}
+
+/// A procedure built by a primitive at runtime, carrying the values it
+/// closes over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NativeClosure {
+    /// Applies the procedures right to left, each to the previous result.
+    Compose(Rc<[Value]>),
+    /// Applies the procedure to the captured arguments followed by the ones
+    /// it is called with.
+    Curry(Rc<Value>, Rc<[Value]>),
+    /// Applies the procedure, remembering its result for each list of
+    /// arguments so that a repeated call returns it without running again.
+    Memoize(Rc<Value>, Rc<RefCell<HashMap<ValueKey, Value>>>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Value {
-    Atom(String),
-    List(Vec<Value>),
-    DottedList(Vec<Value>, Box<Value>),
-    Number(i64),
+    Atom(Symbol),
+    List(Rc<[Value]>),
+    DottedList(Rc<[Value]>, Rc<Value>),
+    Number(Number),
     String(String),
+    Char(char),
     Bool(bool),
     PrimitiveFunc(PrimitiveFunc),
     Func {
-        params: Vec<String>,
+        /// The name the procedure was defined with, for error messages.
+        name: Option<Symbol>,
+        params: Rc<[String]>,
+        /// Parameters after `#!optional`, with the expressions giving their
+        /// values when a call leaves them out.
+        optionals: Rc<[(String, Value)]>,
         vararg: Option<String>,
-        body: Vec<Value>,
+        body: Rc<[Value]>,
         closure: Closure,
     },
     IOFunc(IOFunc),
+    NativeClosure(NativeClosure),
     Port(usize),
+    Eof,
+    Unspecified,
+    Values(Vec<Value>),
+    Promise(Rc<RefCell<PromiseState>>),
+    Bytevector(Rc<RefCell<Vec<u8>>>),
+    Vector(Rc<RefCell<Vec<Value>>>),
+    HashTable(Rc<RefCell<HashTable>>),
+    /// An error caught by `guard`, with its category and message.
+    Condition {
+        kind: &'static str,
+        message: String,
+    },
+}
+
+impl Value {
+    /// Whether the value counts as true in a test: everything but `#f` does.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false))
+    }
+
+    /// The kind of value this is, as named in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Atom(_) => "symbol",
+            Value::List(_) => "list",
+            Value::DottedList(_, _) => "pair",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Char(_) => "char",
+            Value::Bool(_) => "boolean",
+            Value::PrimitiveFunc(_)
+            | Value::Func { .. }
+            | Value::IOFunc(_)
+            | Value::NativeClosure(_) => "procedure",
+            Value::Port(_) => "port",
+            Value::Eof => "eof-object",
+            Value::Unspecified => "unspecified",
+            Value::Values(_) => "values",
+            Value::Promise(_) => "promise",
+            Value::Bytevector(_) => "bytevector",
+            Value::Vector(_) => "vector",
+            Value::HashTable(_) => "hash-table",
+            Value::Condition { .. } => "condition",
+        }
+    }
+
+    /// Writes the value as source text that reads back as an equal value.
+    /// Unlike `Display`, this fails for values with no written form, such as
+    /// procedures and ports, instead of writing a placeholder.
+    pub fn unparse(&self) -> Result<String, Error> {
+        match self {
+            Value::Atom(_)
+            | Value::Number(_)
+            | Value::String(_)
+            | Value::Char(_)
+            | Value::Bool(_)
+            | Value::Bytevector(_) => Ok(self.to_string()),
+            Value::List(l) => match abbreviation(l) {
+                Some((prefix, datum)) => Ok(format!("{}{}", prefix, datum.unparse()?)),
+                None => {
+                    let l = l
+                        .iter()
+                        .map(Value::unparse)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(format!("({})", intersperse(&l)))
+                }
+            },
+            Value::DottedList(xs, x) => {
+                let xs = xs
+                    .iter()
+                    .map(Value::unparse)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("({} . {})", intersperse(&xs), x.unparse()?))
+            }
+            Value::Vector(vals) => {
+                let vals = vals
+                    .borrow()
+                    .iter()
+                    .map(Value::unparse)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("#({})", intersperse(&vals)))
+            }
+            _ => Err(Error::Unwritable(self.clone())),
+        }
+    }
+
+    /// Renders the value the way `display` shows it, which differs from the
+    /// `Display` (write) form only in leaving strings unquoted.
+    pub fn to_display_string(&self) -> String {
+        match self {
+            Value::String(s) => s.clone(),
+            Value::Char(c) => c.to_string(),
+            Value::Atom(a) => a.to_string(),
+            Value::List(l) if abbreviation(l).is_some() => {
+                let (prefix, datum) = abbreviation(l).unwrap();
+                format!("{}{}", prefix, datum.to_display_string())
+            }
+            Value::List(l) => {
+                let l: Vec<String> = l.iter().map(Value::to_display_string).collect();
+                format!("({})", intersperse(&l))
+            }
+            Value::DottedList(xs, x) => {
+                let xs: Vec<String> = xs.iter().map(Value::to_display_string).collect();
+                format!("({} . {})", intersperse(&xs), x.to_display_string())
+            }
+            Value::Vector(vals) => {
+                let vals: Vec<String> =
+                    vals.borrow().iter().map(Value::to_display_string).collect();
+                format!("#({})", intersperse(&vals))
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// Renders the value as `Display` does, but labels each list or vector
+    /// reached more than once: `#n=` where it's first written and `#n#`
+    /// wherever it appears again, so shared structure shows and cycles end.
+    /// A list is shared only as a whole, as lists don't share their tails.
+    pub fn to_shared_string(&self) -> String {
+        let mut seen = HashMap::new();
+        self.count_shared(&mut seen);
+        let mut labels = seen
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(address, _)| (address, None))
+            .collect();
+        let mut out = String::new();
+        self.write_shared(&mut out, &mut labels);
+        out
+    }
+
+    /// Where a list or vector is stored, which identifies it for labeling.
+    /// The empty list has no identity worth labeling.
+    fn shared_address(&self) -> Option<usize> {
+        match self {
+            Value::List(vals) if vals.is_empty() => None,
+            Value::List(vals) | Value::DottedList(vals, _) => {
+                Some(Rc::as_ptr(vals).cast::<Value>() as usize)
+            }
+            Value::Vector(vals) => Some(Rc::as_ptr(vals) as usize),
+            _ => None,
+        }
+    }
+
+    /// Counts how many times each list and vector is reached, by address,
+    /// without going into one twice.
+    fn count_shared(&self, seen: &mut HashMap<usize, usize>) {
+        if let Some(address) = self.shared_address() {
+            let count = seen.entry(address).or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                return;
+            }
+        }
+        match self {
+            Value::List(vals) => vals.iter().for_each(|val| val.count_shared(seen)),
+            Value::DottedList(vals, tail) => {
+                vals.iter().for_each(|val| val.count_shared(seen));
+                tail.count_shared(seen);
+            }
+            Value::Vector(vals) => vals.borrow().iter().for_each(|val| val.count_shared(seen)),
+            _ => {}
+        }
+    }
+
+    /// Writes the value for `to_shared_string`, where `labels` holds the
+    /// shared lists and vectors with the labels they've been given so far.
+    fn write_shared(&self, out: &mut String, labels: &mut HashMap<usize, Option<usize>>) {
+        if let Some(address) = self.shared_address() {
+            let next = labels.values().flatten().count();
+            match labels.get_mut(&address) {
+                Some(Some(label)) => return out.push_str(&format!("#{}#", label)),
+                Some(label) => {
+                    *label = Some(next);
+                    out.push_str(&format!("#{}=", next));
+                }
+                None => {}
+            }
+        }
+        match self {
+            Value::List(l) => match abbreviation(l) {
+                Some((prefix, datum)) => {
+                    out.push_str(prefix);
+                    datum.write_shared(out, labels);
+                }
+                None => {
+                    out.push('(');
+                    write_shared_items(out, l, labels);
+                    out.push(')');
+                }
+            },
+            Value::DottedList(xs, x) => {
+                out.push('(');
+                write_shared_items(out, xs, labels);
+                out.push_str(" . ");
+                x.write_shared(out, labels);
+                out.push(')');
+            }
+            Value::Vector(vals) => {
+                out.push_str("#(");
+                write_shared_items(out, &vals.borrow(), labels);
+                out.push(')');
+            }
+            _ => out.push_str(&self.to_string()),
+        }
+    }
+
+    /// Renders the value as `Display` does, but stops after `limit`
+    /// characters and marks the cut with `...`.
+    pub fn to_string_limited(&self, limit: usize) -> String {
+        let mut out = Truncated {
+            text: String::new(),
+            len: 0,
+            limit,
+        };
+        if std::fmt::Write::write_fmt(&mut out, format_args!("{}", self)).is_err() {
+            out.text.push_str("...");
+        }
+        out.text
+    }
+
+    /// Describes the value's structure as JSON for tools such as editors.
+    /// Every node is an object whose `kind` is the value's type name, with
+    /// children nested in place; procedures include their parameters and
+    /// body. This is for inspection only and is not read back.
+    pub fn to_debug_json(&self) -> String {
+        let mut out = String::new();
+        self.write_debug_json(&mut out);
+        out
+    }
+
+    fn write_debug_json(&self, out: &mut String) {
+        out.push_str(&format!("{{\"kind\":{}", json_string(self.type_name())));
+        match self {
+            Value::Atom(a) => out.push_str(&format!(",\"name\":{}", json_string(a.as_str()))),
+            Value::String(s) => out.push_str(&format!(",\"value\":{}", json_string(s))),
+            Value::Char(c) => out.push_str(&format!(",\"value\":{}", json_string(&c.to_string()))),
+            Value::Bool(b) => out.push_str(&format!(",\"value\":{}", b)),
+            Value::List(vals) => write_json_items(out, "items", vals),
+            Value::DottedList(vals, tail) => {
+                write_json_items(out, "items", vals);
+                out.push_str(",\"tail\":");
+                tail.write_debug_json(out);
+            }
+            Value::Vector(vals) => write_json_items(out, "items", &vals.borrow()),
+            Value::Values(vals) => write_json_items(out, "items", vals),
+            Value::Bytevector(bytes) => {
+                out.push_str(&format!(
+                    ",\"bytes\":[{}]",
+                    bytes
+                        .borrow()
+                        .iter()
+                        .map(u8::to_string)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ));
+            }
+            Value::Func {
+                name,
+                params,
+                optionals,
+                vararg,
+                body,
+                closure: _,
+            } => {
+                let name = name.map_or("null".to_owned(), |name| json_string(name.as_str()));
+                let params: Vec<String> = params.iter().map(|param| json_string(param)).collect();
+                out.push_str(&format!(
+                    ",\"name\":{},\"params\":[{}]",
+                    name,
+                    params.join(",")
+                ));
+                out.push_str(",\"optionals\":[");
+                for (i, (param, default)) in optionals.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&format!("{{\"name\":{},\"default\":", json_string(param)));
+                    default.write_debug_json(out);
+                    out.push('}');
+                }
+                out.push(']');
+                let rest = vararg.as_deref().map_or("null".to_owned(), json_string);
+                out.push_str(&format!(",\"rest\":{}", rest));
+                write_json_items(out, "body", body);
+            }
+            _ => out.push_str(&format!(",\"value\":{}", json_string(&self.to_string()))),
+        }
+        out.push('}');
+    }
+}
+
+/// Writes `vals` as a JSON array field of a debug node.
+fn write_json_items(out: &mut String, field: &str, vals: &[Value]) {
+    out.push_str(&format!(",\"{}\":[", field));
+    for (i, val) in vals.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        val.write_debug_json(out);
+    }
+    out.push(']');
+}
+
+/// Quotes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes items separated by spaces. Unlike `intersperse`, this goes straight
+/// to the formatter, so a writer that gives up early saves the rest of the
+/// work.
+fn write_items<T: Display>(f: &mut std::fmt::Formatter<'_>, items: &[T]) -> std::fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_shared_items(
+    out: &mut String,
+    items: &[Value],
+    labels: &mut HashMap<usize, Option<usize>>,
+) {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        item.write_shared(out, labels);
+    }
+}
+
+/// A string that refuses writes past `limit` characters, so that formatting
+/// into it stops there.
+struct Truncated {
+    text: String,
+    len: usize,
+    limit: usize,
+}
+
+impl std::fmt::Write for Truncated {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        for c in s.chars() {
+            if self.len == self.limit {
+                return Err(std::fmt::Error);
+            }
+            self.text.push(c);
+            self.len += 1;
+        }
+        Ok(())
+    }
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::String(s) => write!(f, "\"{}\"", s),
-            Value::Atom(a) => write!(f, "{}", a),
+            Value::String(s) => write_string(f, s),
+            Value::Atom(a) => write_atom(f, a.as_str()),
             Value::Number(n) => write!(f, "{}", n),
+            Value::Char(c) => match CHAR_NAMES.iter().find(|(_, named)| named == c) {
+                Some((name, _)) => write!(f, "#\\{}", name),
+                None if c.is_control() => write!(f, "#\\x{:x}", *c as u32),
+                None => write!(f, "#\\{}", c),
+            },
             Value::Bool(b) => write!(f, "{}", if *b { TRUE } else { FALSE }),
-            Value::List(l) => {
-                write!(f, "({})", intersperse(l))
-            }
+            Value::List(l) => match abbreviation(l) {
+                Some((prefix, datum)) => write!(f, "{}{}", prefix, datum),
+                None => {
+                    write!(f, "(")?;
+                    write_items(f, l)?;
+                    write!(f, ")")
+                }
+            },
             Value::DottedList(xs, x) => {
-                write!(f, "({} . {})", intersperse(xs), x)
+                write!(f, "(")?;
+                write_items(f, xs)?;
+                write!(f, " . {})", x)
             }
             Value::PrimitiveFunc(_) => write!(f, "<primitive>"),
             Value::Func {
+                name: _,
                 params,
+                optionals,
                 vararg,
                 body: _,
                 closure: _,
-            } => {
-                let params = intersperse(params);
-                let vararg = match vararg {
-                    Some(arg) => format!(" . {}", arg),
-                    None => String::new(),
-                };
-                write!(f, "(lambda ({}{}) ...)", params, vararg)
-            }
+            } => write!(f, "(lambda {} ...)", lambda_list(params, optionals, vararg)),
             Value::IOFunc(_) => write!(f, "<IO primitive>"),
+            Value::NativeClosure(NativeClosure::Compose(_)) => write!(f, "<composed procedure>"),
+            Value::NativeClosure(NativeClosure::Curry(_, _)) => write!(f, "<curried procedure>"),
+            Value::NativeClosure(NativeClosure::Memoize(func, _)) => write!(f, "{}", func),
             Value::Port(_) => write!(f, "<IO port>"),
+            Value::Eof => write!(f, "#<eof>"),
+            Value::Unspecified => write!(f, "#<unspecified>"),
+            Value::Values(vals) => write_items(f, vals),
+            Value::Promise(_) => write!(f, "#<promise>"),
+            Value::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                write_items(f, &bytes.borrow())?;
+                write!(f, ")")
+            }
+            Value::Vector(vals) => {
+                write!(f, "#(")?;
+                write_items(f, &vals.borrow())?;
+                write!(f, ")")
+            }
+            Value::HashTable(_) => write!(f, "#<hash-table>"),
+            Value::Condition { kind, message } => write!(f, "#<condition {}: {}>", kind, message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::Value;
+    use crate::{env::Env, eval::eval, parser::parse_expr};
+
+    #[test]
+    fn shared_string() {
+        let mut env = Env::primitive_bindings();
+        let cases = vec![
+            ("'(1 \"two\" #(3))", "(1 \"two\" #(3))"),
+            ("(define v #(1 2))", "#(1 2)"),
+            ("(cons v (cons 'x (cons v '())))", "(#0=#(1 2) x #0#)"),
+            ("(define w #(3))", "#(3)"),
+            (
+                "(cons w (cons v (cons w (cons v '()))))",
+                "(#0=#(3) #1=#(1 2) #0# #1#)",
+            ),
+            ("'(v v)", "(v v)"),
+            ("(define l '(1 2))", "(1 2)"),
+            ("(cons l (cons l '()))", "(#0=(1 2) #0#)"),
+            ("(define d '(1 . 2))", "(1 . 2)"),
+            ("(cons d (cons v (cons d '())))", "(#0=(1 . 2) #(1 2) #0#)"),
+            ("(cons '() (cons '() '()))", "(() ())"),
+        ];
+        for (input, expected) in cases {
+            let val = eval(&mut env, &parse_expr(input).unwrap()).unwrap();
+            assert_eq!(expected, val.to_shared_string(), "{}", input);
         }
+
+        let cycle = Rc::new(RefCell::new(vec![Value::Number(1.into())]));
+        cycle.borrow_mut().push(Value::Vector(cycle.clone()));
+        assert_eq!(
+            "#0=#(1 #0#)",
+            Value::Vector(cycle.clone()).to_shared_string()
+        );
+        cycle.borrow_mut().clear();
+    }
+
+    #[test]
+    fn debug_json() {
+        let val = parse_expr("(f \"a\\\"b\" (x . 1/2) #(#t #\\c))").unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"kind":"list","items":["#,
+                r#"{"kind":"symbol","name":"f"},"#,
+                r#"{"kind":"string","value":"a\"b"},"#,
+                r#"{"kind":"pair","items":[{"kind":"symbol","name":"x"}],"#,
+                r#""tail":{"kind":"number","value":"1/2"}},"#,
+                r#"{"kind":"vector","items":[{"kind":"boolean","value":true},"#,
+                r#"{"kind":"char","value":"c"}]}]}"#,
+            ),
+            val.to_debug_json()
+        );
+
+        let mut env = Env::primitive_bindings();
+        let define = parse_expr("(define (g a #!optional (b 2) . rest) (+ a b))").unwrap();
+        let func = eval(&mut env, &define).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"kind":"procedure","name":"g","params":["a"],"#,
+                r#""optionals":[{"name":"b","default":{"kind":"number","value":"2"}}],"#,
+                r#""rest":"rest","body":[{"kind":"list","items":["#,
+                r#"{"kind":"symbol","name":"+"},{"kind":"symbol","name":"a"},"#,
+                r#"{"kind":"symbol","name":"b"}]}]}"#,
+            ),
+            func.to_debug_json()
+        );
     }
 }
@@ -1,4 +1,3 @@
-(define (not x)                 (if x #f #t))
 (define (null? obj)             (if (eqv? obj '()) #t #f))
 
 (define (list . objs)           objs)
@@ -7,9 +6,6 @@
 
 (define (flip func)             (lambda (arg1 arg2) (func arg2 arg1)))
 
-(define (curry func arg1)       (lambda (arg) (apply func (cons arg1 (list arg)))))
-(define (compose f g)           (lambda (arg) (f (apply g arg))))
-
 (define zero?                   (curry = 0))
 (define positive?               (curry < 0))
 (define negative?               (curry > 0))
@@ -26,7 +22,6 @@
         accum
         (foldl func (func accum (car lst)) (cdr lst))))
 
-(define fold foldl)
 (define reduce foldr)
 
 (define (unfold func init pred)
@@ -34,25 +29,18 @@
       (cons init '())
       (cons init (unfold func (func init) pred))))
 
-(define (sum . lst)             (fold + 0 lst))
-(define (product . lst)         (fold * 1 lst))
-(define (and . lst)             (fold && #t lst))
-(define (or . lst)              (fold || #f lst))
-
-(define (max first . rest)      (fold (lambda (old new) (if (> old new) old new)) first rest))
-(define (min first . rest)      (fold (lambda (old new) (if (< old new) old new)) first rest))
+(define (sum . lst)             (foldl + 0 lst))
+(define (product . lst)         (foldl * 1 lst))
 
-(define (length lst)            (fold (lambda (x y) (+ x 1)) 0 lst))
+(define (max first . rest)      (foldl (lambda (old new) (if (> old new) old new)) first rest))
+(define (min first . rest)      (foldl (lambda (old new) (if (< old new) old new)) first rest))
 
-(define (reverse lst)           (fold (flip cons) '() lst))
+(define (length lst)            (foldl (lambda (x y) (+ x 1)) 0 lst))
 
 (define (mem-helper pred op)    (lambda (acc next) (if (and (not acc) (pred (op next))) next acc)))
-(define (memq obj lst)          (fold (mem-helper (curry eq? obj) id) #f lst))
-(define (memv obj lst)          (fold (mem-helper (curry eqv? obj) id) #f lst))
-(define (member obj lst)        (fold (mem-helper (curry equal? obj) id) #f lst))
-(define (assq obj lst)          (fold (mem-helper (curry eq? obj) car) #f lst))
-(define (assv obj lst)          (fold (mem-helper (curry eqv? obj) car) #f lst))
-(define (assoc obj lst)         (fold (mem-helper (curry equal? obj) car) #f lst))
-
-(define (map func lst)          (foldr (lambda (x y) (cons (func x) y)) '() lst))
-(define (filter pred lst)       (foldr (lambda (x y) (if (pred x) (cons x y) y)) '() lst))
+(define (memq obj lst)          (foldl (mem-helper (curry eq? obj) id) #f lst))
+(define (memv obj lst)          (foldl (mem-helper (curry eqv? obj) id) #f lst))
+(define (member obj lst)        (foldl (mem-helper (curry equal? obj) id) #f lst))
+(define (assq obj lst)          (foldl (mem-helper (curry eq? obj) car) #f lst))
+(define (assv obj lst)          (foldl (mem-helper (curry eqv? obj) car) #f lst))
+(define (assoc obj lst)         (foldl (mem-helper (curry equal? obj) car) #f lst))
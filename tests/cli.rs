@@ -0,0 +1,164 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn scheme(args: &[&str], stdin: &str) -> Output {
+    scheme_with(
+        Command::new(env!("CARGO_BIN_EXE_scheme-rs")).args(args),
+        stdin,
+    )
+}
+
+fn scheme_with(command: &mut Command, stdin: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn eval_flag() {
+    let output = scheme(&["-e", "(+ 1 2)"], "");
+    assert!(output.status.success());
+    assert_eq!("3\n", stdout(&output));
+    let output = scheme(&["-e", "(car 1"], "");
+    assert!(!output.status.success());
+    assert_eq!("", stdout(&output));
+    assert!(stderr(&output).starts_with("Parse error: "));
+}
+
+#[test]
+fn file() {
+    let path = std::env::temp_dir().join("scheme-rs-cli-file.scm");
+    std::fs::write(&path, "(define x 20)\n(display (+ x 1))\n").unwrap();
+    let output = scheme(&[path.to_str().unwrap()], "");
+    assert_eq!("21", stdout(&output));
+}
+
+#[test]
+fn stdin() {
+    let output = scheme(&["-"], "(display \"from stdin\")\n(car '())");
+    assert!(!output.status.success());
+    assert_eq!("from stdin", stdout(&output));
+    assert_eq!(
+        "Eval error: Invalid type: expected pair, found ()\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn usage() {
+    let output = scheme(&["--help"], "");
+    assert_eq!(Some(2), output.status.code());
+    assert!(stderr(&output).starts_with("usage:"));
+}
+
+#[test]
+fn file_loads_relative_to_itself() {
+    let dir = std::env::temp_dir().join("scheme-rs-cli-relative");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("helper.scm"), "(define greeting \"hi\")").unwrap();
+    std::fs::write(
+        dir.join("main.scm"),
+        "(load \"helper.scm\")\n(display greeting)",
+    )
+    .unwrap();
+    let output = scheme(&[dir.join("main.scm").to_str().unwrap()], "");
+    assert_eq!("hi", stdout(&output));
+}
+
+#[test]
+fn load_path_from_environment() {
+    let dir = std::env::temp_dir().join("scheme-rs-cli-load-path");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.scm"), "(define from-lib 5)").unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_scheme-rs"));
+    command.arg("-").env("SCHEME_LOAD_PATH", &dir);
+    let output = scheme_with(&mut command, "(load \"lib.scm\")\n(display from-lib)");
+    assert_eq!("5", stdout(&output));
+}
+
+#[test]
+fn dump() {
+    let path = std::env::temp_dir().join("scheme-rs-cli-dump.scm");
+    std::fs::write(&path, "(f 1)\nx\n").unwrap();
+    let output = scheme(&["--dump", path.to_str().unwrap()], "");
+    assert_eq!(
+        concat!(
+            r#"{"kind":"list","items":[{"kind":"symbol","name":"f"},"#,
+            r#"{"kind":"number","value":"1"}]}"#,
+            "\n",
+            r#"{"kind":"symbol","name":"x"}"#,
+            "\n",
+        ),
+        stdout(&output)
+    );
+}
+
+#[test]
+fn optimized() {
+    let output = scheme(&["-O", "-"], "(define x (* 6 7))\n(display (+ x 1))");
+    assert_eq!("43", stdout(&output));
+}
+
+#[test]
+fn safe_primitives() {
+    let output = scheme(&["--safe", "-"], "(define (car x) x)");
+    assert!(!output.status.success());
+    assert_eq!("", stdout(&output));
+    assert_eq!(
+        "Eval error: Cannot redefine primitive: car\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn runaway_recursion() {
+    let output = scheme(&["-"], "(define (f n) (+ 1 (f n)))\n(f 0)");
+    assert_eq!(Some(1), output.status.code());
+    assert!(
+        stderr(&output).starts_with("Eval error: Stack overflow: calls nested deeper than 1000\n")
+    );
+}
+
+#[test]
+fn char_ready_on_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_scheme-rs"))
+        .args(["-e", "(char-ready?)"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Held open so that a read would wait for more input.
+    let stdin = child.stdin.take();
+    let output = child.wait_with_output().unwrap();
+    drop(stdin);
+    assert_eq!("#f\n", stdout(&output));
+
+    let path = std::env::temp_dir().join("scheme-rs-cli-char-ready.txt");
+    std::fs::write(&path, "").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_scheme-rs"))
+        .args(["-e", "(char-ready?)"])
+        .stdin(std::fs::File::open(&path).unwrap())
+        .output()
+        .unwrap();
+    assert_eq!("#t\n", stdout(&output));
+}